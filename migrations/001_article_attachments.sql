@@ -0,0 +1,10 @@
+ALTER TABLE FILES ADD COLUMN size INTEGER;
+
+CREATE TABLE ARTICLE_FILE (
+    article_id INTEGER NOT NULL,
+    file_id text NOT NULL,
+    user_id INTEGER NOT NULL,
+    create_time INTEGER NOT NULL,
+    PRIMARY KEY (article_id, file_id),
+    FOREIGN KEY (user_id) REFERENCES USER (id)
+);
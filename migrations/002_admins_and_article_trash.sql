@@ -0,0 +1,15 @@
+ALTER TABLE USER ADD COLUMN admin BOOLEAN NOT NULL DEFAULT(FALSE);
+
+CREATE TABLE DELETED_ARTICLE (
+    article_id INTEGER PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    delete_time INTEGER NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES USER (id)
+);
+
+CREATE INDEX DELETED_ARTICLE_TIME_INX ON DELETED_ARTICLE(delete_time);
+
+INSERT INTO DELETED_ARTICLE(article_id, user_id, delete_time)
+SELECT a.id, a.user_id, a.create_time FROM ARTICLE a
+WHERE a.version = (SELECT MAX(version) FROM ARTICLE WHERE id = a.id)
+AND NOT EXISTS (SELECT 1 FROM ARTICLE WHERE id = a.id AND active = TRUE)
@@ -0,0 +1,14 @@
+CREATE TABLE ARTICLE_SLUG (
+    article_id INTEGER PRIMARY KEY,
+    slug text NOT NULL
+);
+
+CREATE UNIQUE INDEX ARTICLE_SLUG_INX ON ARTICLE_SLUG(slug);
+
+CREATE TABLE ARTICLE_SLUG_REDIRECT (
+    slug text PRIMARY KEY,
+    article_id INTEGER NOT NULL,
+    create_time INTEGER NOT NULL
+);
+
+CREATE INDEX ARTICLE_SLUG_REDIRECT_ARTICLE_INX ON ARTICLE_SLUG_REDIRECT(article_id);
@@ -0,0 +1,10 @@
+CREATE TABLE ARTICLE_LINK (
+    source_id INTEGER NOT NULL,
+    target_id INTEGER,
+    target_slug text,
+    link text NOT NULL
+);
+
+CREATE INDEX ARTICLE_LINK_SOURCE_INX ON ARTICLE_LINK(source_id);
+CREATE INDEX ARTICLE_LINK_TARGET_ID_INX ON ARTICLE_LINK(target_id);
+CREATE INDEX ARTICLE_LINK_TARGET_SLUG_INX ON ARTICLE_LINK(target_slug);
@@ -0,0 +1,8 @@
+CREATE TABLE ARTICLE_TEMPLATE (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name text NOT NULL,
+    path_prefix text NOT NULL,
+    visibility text NOT NULL, -- public, private
+    content text NOT NULL,
+    deleted BOOLEAN NOT NULL DEFAULT(FALSE)
+);
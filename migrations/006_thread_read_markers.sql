@@ -0,0 +1,8 @@
+CREATE TABLE THREAD_READ (
+    user_id INTEGER NOT NULL,
+    thread_id INTEGER NOT NULL,
+    last_read_message_id INTEGER NOT NULL,
+    PRIMARY KEY (user_id, thread_id),
+    FOREIGN KEY (user_id) REFERENCES USER (id),
+    FOREIGN KEY (thread_id) REFERENCES THREAD (id)
+);
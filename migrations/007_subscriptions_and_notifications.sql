@@ -0,0 +1,28 @@
+CREATE TABLE SUBSCRIPTION (
+    user_id INTEGER NOT NULL,
+    target_type text NOT NULL, -- thread, subsection, article
+    target_id INTEGER NOT NULL,
+    PRIMARY KEY (user_id, target_type, target_id),
+    FOREIGN KEY (user_id) REFERENCES USER (id)
+);
+
+CREATE INDEX SUBSCRIPTION_TARGET_INX ON SUBSCRIPTION(target_type, target_id);
+
+ -- unread notifications of one user are grouped by thread or article
+CREATE TABLE NOTIFICATION (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    kind text NOT NULL, -- kind of the latest event in the group
+    thread_id INTEGER,
+    article_id INTEGER,
+    message_id INTEGER, -- first message or comment of the group
+    actor_id INTEGER NOT NULL, -- author of the latest event
+    event_count INTEGER NOT NULL,
+    create_time INTEGER NOT NULL,
+    update_time INTEGER NOT NULL,
+    read BOOLEAN NOT NULL DEFAULT(FALSE),
+    FOREIGN KEY (user_id) REFERENCES USER (id),
+    FOREIGN KEY (actor_id) REFERENCES USER (id)
+);
+
+CREATE INDEX NOTIFICATION_USER_INX ON NOTIFICATION(user_id, read, update_time);
@@ -0,0 +1,11 @@
+ -- thread_id, article_id and message_id are 0 when not applicable,
+ -- message_id is a message id for threads and a comment id for articles
+CREATE TABLE MENTION (
+    user_id INTEGER NOT NULL,
+    thread_id INTEGER NOT NULL,
+    article_id INTEGER NOT NULL,
+    message_id INTEGER NOT NULL,
+    create_time INTEGER NOT NULL,
+    PRIMARY KEY (user_id, thread_id, article_id, message_id),
+    FOREIGN KEY (user_id) REFERENCES USER (id)
+);
@@ -0,0 +1,27 @@
+CREATE TABLE EMAIL_SETTINGS (
+    user_id INTEGER PRIMARY KEY,
+    email text NOT NULL,
+    mode text NOT NULL, -- immediate, digest, off
+    unsubscribe_token text NOT NULL UNIQUE,
+    last_notified_time INTEGER NOT NULL, -- notifications updated before this time are already emailed
+    FOREIGN KEY (user_id) REFERENCES USER (id)
+);
+
+ -- rendered emails waiting for delivery, sent ones are kept with sent_time set
+CREATE TABLE EMAIL_OUTBOX (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    recipient text NOT NULL,
+    subject text NOT NULL,
+    text_body text NOT NULL,
+    html_body text NOT NULL,
+    unsubscribe_url text NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT(0),
+    next_attempt_time INTEGER NOT NULL,
+    last_error text,
+    create_time INTEGER NOT NULL,
+    sent_time INTEGER,
+    FOREIGN KEY (user_id) REFERENCES USER (id)
+);
+
+CREATE INDEX EMAIL_OUTBOX_PENDING_INX ON EMAIL_OUTBOX(sent_time, next_attempt_time);
@@ -0,0 +1,39 @@
+CREATE TABLE WEBHOOK (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    url text NOT NULL,
+    secret text NOT NULL, -- key of the HMAC-SHA256 payload signature
+    active BOOLEAN NOT NULL DEFAULT(TRUE),
+    create_time INTEGER NOT NULL
+);
+
+CREATE TABLE WEBHOOK_EVENT (
+    webhook_id INTEGER NOT NULL,
+    event text NOT NULL, -- thread_created, message_posted, article_created, article_updated, article_deleted, comment_posted
+    PRIMARY KEY (webhook_id, event),
+    FOREIGN KEY (webhook_id) REFERENCES WEBHOOK (id)
+);
+
+CREATE TABLE WEBHOOK_DELIVERY (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    webhook_id INTEGER NOT NULL,
+    event text NOT NULL,
+    payload text NOT NULL,
+    status text NOT NULL, -- pending, delivered, failed
+    attempts INTEGER NOT NULL DEFAULT(0),
+    next_attempt_time INTEGER NOT NULL,
+    create_time INTEGER NOT NULL,
+    FOREIGN KEY (webhook_id) REFERENCES WEBHOOK (id)
+);
+
+CREATE INDEX WEBHOOK_DELIVERY_PENDING_INX ON WEBHOOK_DELIVERY(status, next_attempt_time);
+CREATE INDEX WEBHOOK_DELIVERY_WEBHOOK_INX ON WEBHOOK_DELIVERY(webhook_id, id);
+
+CREATE TABLE WEBHOOK_DELIVERY_ATTEMPT (
+    delivery_id INTEGER NOT NULL,
+    attempt INTEGER NOT NULL,
+    time INTEGER NOT NULL,
+    response_status INTEGER,
+    error text,
+    PRIMARY KEY (delivery_id, attempt),
+    FOREIGN KEY (delivery_id) REFERENCES WEBHOOK_DELIVERY (id)
+);
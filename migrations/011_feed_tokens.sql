@@ -0,0 +1,6 @@
+ -- secret token in feed urls which gives access to the private articles of the user
+CREATE TABLE FEED_TOKEN (
+    user_id INTEGER PRIMARY KEY,
+    token text NOT NULL UNIQUE,
+    FOREIGN KEY (user_id) REFERENCES USER (id)
+);
@@ -0,0 +1,3 @@
+ALTER TABLE THREAD ADD COLUMN pinned INTEGER NOT NULL DEFAULT(0); -- 0 not pinned, 1 pinned in the subsection, 2 pinned globally
+ALTER TABLE THREAD ADD COLUMN locked BOOLEAN NOT NULL DEFAULT(FALSE);
+ALTER TABLE THREAD ADD COLUMN announcement BOOLEAN NOT NULL DEFAULT(FALSE)
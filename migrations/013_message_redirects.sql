@@ -0,0 +1,11 @@
+ -- old locations of messages moved to another thread by a merge or a split,
+ -- always point to the current location of the message
+CREATE TABLE MESSAGE_REDIRECT (
+    thread_id INTEGER NOT NULL,
+    message_id INTEGER NOT NULL,
+    new_thread_id INTEGER NOT NULL,
+    new_message_id INTEGER NOT NULL,
+    PRIMARY KEY (thread_id, message_id)
+);
+
+CREATE INDEX MESSAGE_REDIRECT_TARGET_INX ON MESSAGE_REDIRECT(new_thread_id, new_message_id)
@@ -0,0 +1,7 @@
+ALTER TABLE SECTION ADD COLUMN sort_order INTEGER NOT NULL DEFAULT(0);
+ALTER TABLE SUBSECTION ADD COLUMN sort_order INTEGER NOT NULL DEFAULT(0);
+ALTER TABLE SUBSECTION ADD COLUMN description text NOT NULL DEFAULT('');
+ -- set for threads deleted together with their section or subsection, they are restored with it
+ALTER TABLE THREAD ADD COLUMN cascade_deleted BOOLEAN NOT NULL DEFAULT(FALSE);
+UPDATE SECTION SET sort_order = id;
+UPDATE SUBSECTION SET sort_order = id
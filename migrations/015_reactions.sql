@@ -0,0 +1,12 @@
+ -- thread_id and article_id are 0 when not applicable,
+ -- message_id is a message id for threads and a comment id for articles
+CREATE TABLE REACTION (
+    thread_id INTEGER NOT NULL,
+    article_id INTEGER NOT NULL,
+    message_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    emoji text NOT NULL,
+    create_time INTEGER NOT NULL,
+    PRIMARY KEY (thread_id, article_id, message_id, user_id, emoji),
+    FOREIGN KEY (user_id) REFERENCES USER (id)
+)
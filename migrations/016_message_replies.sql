@@ -0,0 +1,4 @@
+ -- id of the replied message in the same thread
+ALTER TABLE MESSAGE ADD COLUMN reply_to INTEGER;
+
+CREATE INDEX MESSAGE_REPLY_INX ON MESSAGE(thread_id, reply_to) WHERE reply_to IS NOT NULL
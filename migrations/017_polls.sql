@@ -0,0 +1,29 @@
+ -- at most one poll per thread, created together with the thread
+CREATE TABLE POLL (
+    thread_id INTEGER PRIMARY KEY,
+    question text NOT NULL,
+    multiple BOOLEAN NOT NULL,
+    anonymous BOOLEAN NOT NULL,
+    hide_results BOOLEAN NOT NULL, -- results are shown only to voters until the poll is closed
+    close_time INTEGER,
+    create_time INTEGER NOT NULL,
+    FOREIGN KEY (thread_id) REFERENCES THREAD (id)
+);
+
+CREATE TABLE POLL_OPTION (
+    thread_id INTEGER NOT NULL,
+    id INTEGER NOT NULL,
+    text text NOT NULL,
+    PRIMARY KEY (thread_id, id),
+    FOREIGN KEY (thread_id) REFERENCES POLL (thread_id)
+);
+
+CREATE TABLE POLL_VOTE (
+    thread_id INTEGER NOT NULL,
+    option_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    create_time INTEGER NOT NULL,
+    PRIMARY KEY (thread_id, user_id, option_id),
+    FOREIGN KEY (thread_id, option_id) REFERENCES POLL_OPTION (thread_id, id),
+    FOREIGN KEY (user_id) REFERENCES USER (id)
+)
@@ -0,0 +1,26 @@
+ -- tag names are normalized: lower case, words joined with '-'
+CREATE TABLE TAG (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name text NOT NULL
+);
+
+CREATE UNIQUE INDEX TAG_NAME_INX ON TAG(name);
+
+CREATE TABLE THREAD_TAG (
+    thread_id INTEGER NOT NULL,
+    tag_id INTEGER NOT NULL,
+    PRIMARY KEY (thread_id, tag_id),
+    FOREIGN KEY (thread_id) REFERENCES THREAD (id),
+    FOREIGN KEY (tag_id) REFERENCES TAG (id)
+);
+
+CREATE INDEX THREAD_TAG_TAG_INX ON THREAD_TAG(tag_id);
+
+CREATE TABLE ARTICLE_TAG (
+    article_id INTEGER NOT NULL,
+    tag_id INTEGER NOT NULL,
+    PRIMARY KEY (article_id, tag_id),
+    FOREIGN KEY (tag_id) REFERENCES TAG (id)
+);
+
+CREATE INDEX ARTICLE_TAG_TAG_INX ON ARTICLE_TAG(tag_id)
@@ -0,0 +1,12 @@
+ -- context is thread:{id}, subsection:{id}, article:{id} or new-article
+CREATE TABLE DRAFT (
+    user_id INTEGER NOT NULL,
+    context text NOT NULL,
+    title text,
+    content text NOT NULL,
+    update_time INTEGER NOT NULL,
+    PRIMARY KEY (user_id, context),
+    FOREIGN KEY (user_id) REFERENCES USER (id)
+);
+
+CREATE INDEX DRAFT_UPDATE_TIME_INX ON DRAFT(update_time)
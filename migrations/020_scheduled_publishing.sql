@@ -0,0 +1,10 @@
+ -- threads with publish_time set are visible only to the author until they are published
+ALTER TABLE THREAD ADD COLUMN publish_time INTEGER;
+
+CREATE INDEX THREAD_PUBLISH_TIME_INX ON THREAD(publish_time);
+
+ -- articles with the scheduled visibility become public at publish_time
+CREATE TABLE ARTICLE_SCHEDULE (
+    article_id INTEGER PRIMARY KEY,
+    publish_time INTEGER NOT NULL
+)
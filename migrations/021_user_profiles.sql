@@ -0,0 +1,10 @@
+ -- name stays the login, display_name is shown instead of it when set
+ALTER TABLE USER ADD COLUMN display_name text;
+
+ALTER TABLE USER ADD COLUMN avatar_file_id text;
+
+ALTER TABLE USER ADD COLUMN bio text;
+
+ALTER TABLE USER ADD COLUMN timezone text;
+
+ALTER TABLE USER ADD COLUMN locale text
@@ -0,0 +1,10 @@
+ -- Single-use password reset tokens, created by an admin or requested by email
+CREATE TABLE PASSWORD_RESET (
+    token text PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    create_time INTEGER NOT NULL,
+    expire_time INTEGER NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES USER (id)
+);
+
+CREATE INDEX PASSWORD_RESET_USER_INX ON PASSWORD_RESET(user_id)
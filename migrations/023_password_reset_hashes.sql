@@ -0,0 +1,14 @@
+ -- Reset tokens are stored as SHA-256 hex, pending plain tokens are dropped.
+ -- requested is TRUE for links the user asked for by email, FALSE for the ones created by an admin.
+DROP TABLE PASSWORD_RESET;
+
+CREATE TABLE PASSWORD_RESET (
+    token_hash text PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    requested BOOLEAN NOT NULL,
+    create_time INTEGER NOT NULL,
+    expire_time INTEGER NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES USER (id)
+);
+
+CREATE INDEX PASSWORD_RESET_USER_INX ON PASSWORD_RESET(user_id)
@@ -3,17 +3,55 @@ use std::io;
 use r2d2::ManageConnection;
 use r2d2_sqlite::SqliteConnectionManager;
 use rusqlite::Connection;
+use crate::queries::{index_all_article_links, QueryResult};
+
+enum Migration {
+    Sql(&'static str),
+    Code(fn(&Connection) -> QueryResult<()>),
+}
+
+// Schema changes applied on top of db.sql, tracked with PRAGMA user_version.
+// Append new entries to the end, never reorder or edit already released ones.
+const MAIN_DB_MIGRATIONS: &[Migration] = &[
+    Migration::Sql(include_str!("../migrations/001_article_attachments.sql")),
+    Migration::Sql(include_str!("../migrations/002_admins_and_article_trash.sql")),
+    Migration::Sql(include_str!("../migrations/003_article_slugs.sql")),
+    Migration::Sql(include_str!("../migrations/004_article_links.sql")),
+    Migration::Code(index_all_article_links),
+    Migration::Sql(include_str!("../migrations/005_article_templates.sql")),
+    Migration::Sql(include_str!("../migrations/006_thread_read_markers.sql")),
+    Migration::Sql(include_str!("../migrations/007_subscriptions_and_notifications.sql")),
+    Migration::Sql(include_str!("../migrations/008_mentions.sql")),
+    Migration::Sql(include_str!("../migrations/009_email_notifications.sql")),
+    Migration::Sql(include_str!("../migrations/010_webhooks.sql")),
+    Migration::Sql(include_str!("../migrations/011_feed_tokens.sql")),
+    Migration::Sql(include_str!("../migrations/012_thread_flags.sql")),
+    Migration::Sql(include_str!("../migrations/013_message_redirects.sql")),
+    Migration::Sql(include_str!("../migrations/014_section_order.sql")),
+    Migration::Sql(include_str!("../migrations/015_reactions.sql")),
+    Migration::Sql(include_str!("../migrations/016_message_replies.sql")),
+    Migration::Sql(include_str!("../migrations/017_polls.sql")),
+    Migration::Sql(include_str!("../migrations/018_tags.sql")),
+    Migration::Sql(include_str!("../migrations/019_drafts.sql")),
+    Migration::Sql(include_str!("../migrations/020_scheduled_publishing.sql")),
+    Migration::Sql(include_str!("../migrations/021_user_profiles.sql")),
+    Migration::Sql(include_str!("../migrations/022_password_reset.sql")),
+    Migration::Sql(include_str!("../migrations/023_password_reset_hashes.sql")),
+];
 
 pub fn create_db() -> std::io::Result<()>
 {
     fs::create_dir_all("data")?;
 
+    let main_db_manager = SqliteConnectionManager::file("data/db.s3db");
     if !std::path::Path::new("data/db.s3db").exists() {
-        let main_db_manager = SqliteConnectionManager::file("data/db.s3db");
         let connection = main_db_manager.connect().expect("Create data/db.s3db error");
         execute(&connection, include_str!("../db.sql"))?;
     }
 
+    let connection = main_db_manager.connect().expect("Open data/db.s3db error");
+    migrate(&connection, MAIN_DB_MIGRATIONS)?;
+
     if !std::path::Path::new("data/user_passwords.s3db").exists() {
         let main_db_manager = SqliteConnectionManager::file("data/user_passwords.s3db");
         let connection = main_db_manager.connect().expect("Create data/user_passwords.s3db error");
@@ -29,6 +67,42 @@ pub fn create_db() -> std::io::Result<()>
     Ok(())
 }
 
+// Fresh main database in memory, for the tests of the queries
+#[cfg(test)]
+pub fn create_memory_db() -> crate::queries::DbConnection {
+    let pool = r2d2::Pool::builder().max_size(1).build(SqliteConnectionManager::memory()).unwrap();
+    let connection = pool.get().unwrap();
+
+    execute(&connection, include_str!("../db.sql")).unwrap();
+    migrate(&connection, MAIN_DB_MIGRATIONS).unwrap();
+
+    connection
+}
+
+fn migrate(conn: &Connection, migrations: &[Migration]) -> std::io::Result<()> {
+    let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0)).map_err(to_io_error)?;
+
+    for (i, migration) in migrations.iter().enumerate().skip(version) {
+        conn.execute_batch("BEGIN").map_err(to_io_error)?;
+        let result = match migration {
+            Migration::Sql(commands) => execute(conn, commands),
+            Migration::Code(f) => f(conn).map_err(|e| io::Error::other(e.to_string())),
+        };
+
+        if let Err(e) = result {
+            conn.execute_batch("ROLLBACK").map_err(to_io_error)?;
+            return Err(e);
+        }
+        conn.execute_batch(&format!("PRAGMA user_version = {}; COMMIT", i + 1)).map_err(to_io_error)?;
+    }
+
+    Ok(())
+}
+
+fn to_io_error(e: rusqlite::Error) -> io::Error {
+    io::Error::other(e.to_string())
+}
+
 fn execute(conn: &Connection, commands: &str) -> std::io::Result<()> {
     let lines = commands.split(";");
 
@@ -42,4 +116,4 @@ fn execute(conn: &Connection, commands: &str) -> std::io::Result<()> {
     }
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,105 @@
+use rusqlite::{Connection, OptionalExtension, params};
+use crate::queries::QueryResult;
+use crate::structs::{Draft, Drafts, LogicError, SaveDraft, User};
+
+// What the draft is going to be published as
+#[derive(Debug, PartialEq)]
+pub enum DraftContext {
+    // message in the thread
+    Thread(u32),
+    // new thread in the subsection
+    Subsection(u32),
+    // new version of the article
+    Article(u32),
+    NewArticle,
+}
+
+impl DraftContext {
+    pub fn parse(context: &str) -> Result<DraftContext, LogicError> {
+        let id = |value: &str| value.parse::<u32>().map_err(|_| LogicError::InvalidDraftContext);
+
+        match context.split_once(':') {
+            Some(("thread", value)) => Ok(DraftContext::Thread(id(value)?)),
+            Some(("subsection", value)) => Ok(DraftContext::Subsection(id(value)?)),
+            Some(("article", value)) => Ok(DraftContext::Article(id(value)?)),
+            None if context == "new-article" => Ok(DraftContext::NewArticle),
+            _ => Err(LogicError::InvalidDraftContext)
+        }
+    }
+
+    pub fn key(&self) -> String {
+        match self {
+            DraftContext::Thread(id) => format!("thread:{}", id),
+            DraftContext::Subsection(id) => format!("subsection:{}", id),
+            DraftContext::Article(id) => format!("article:{}", id),
+            DraftContext::NewArticle => "new-article".to_string(),
+        }
+    }
+}
+
+// Autosave target, a draft without any text is removed
+pub fn save_draft(conn: &Connection, draft: &SaveDraft, user: &User) -> QueryResult<()> {
+    let context = DraftContext::parse(&draft.context)?;
+
+    let title = draft.title.as_deref().filter(|title| !title.trim().is_empty());
+    if draft.content.trim().is_empty() && title.is_none() {
+        return delete_draft(conn, &context, user);
+    }
+
+    let t = chrono::offset::Utc::now();
+    let t = t.timestamp_millis();
+
+    conn.execute("INSERT OR REPLACE INTO DRAFT(user_id, context, title, content, update_time) VALUES(?, ?, ?, ?, ?)",
+                 params![user.id, context.key(), title, draft.content, t])?;
+
+    Ok(())
+}
+
+pub fn delete_draft(conn: &Connection, context: &DraftContext, user: &User) -> QueryResult<()> {
+    conn.execute("DELETE FROM DRAFT WHERE user_id = ? AND context = ?", params![user.id, context.key()])?;
+    Ok(())
+}
+
+fn extract_draft(row: &rusqlite::Row) -> rusqlite::Result<Draft> {
+    Ok(Draft {
+        context: row.get(0)?,
+        title: row.get(1)?,
+        content: row.get(2)?,
+        update_time: row.get(3)?,
+    })
+}
+
+pub fn query_draft(conn: &Connection, context: &str, user: &User) -> QueryResult<Option<Draft>> {
+    let context = DraftContext::parse(context)?;
+
+    let draft = conn.query_row("SELECT context, title, content, update_time FROM DRAFT WHERE user_id = ? AND context = ?",
+                               params![user.id, context.key()], extract_draft).optional()?;
+
+    Ok(draft)
+}
+
+pub fn query_drafts(conn: &Connection, user: &User) -> QueryResult<Drafts> {
+    let mut stmt = conn.prepare("SELECT context, title, content, update_time FROM DRAFT WHERE user_id = ? ORDER BY update_time DESC")?;
+    let drafts = stmt.query_map([user.id], extract_draft)?.collect::<Result<Vec<Draft>, _>>()?;
+
+    Ok(Drafts{drafts})
+}
+
+pub fn purge_expired_drafts(conn: &Connection, updated_before: i64) -> QueryResult<usize> {
+    let count = conn.execute("DELETE FROM DRAFT WHERE update_time < ?", [updated_before])?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draft_contexts_are_parsed() {
+        assert_eq!(DraftContext::parse("thread:12").unwrap(), DraftContext::Thread(12));
+        assert_eq!(DraftContext::parse("new-article").unwrap(), DraftContext::NewArticle);
+        assert_eq!(DraftContext::parse("subsection:3").unwrap().key(), "subsection:3");
+        assert!(DraftContext::parse("thread:").is_err());
+        assert!(DraftContext::parse("message:1").is_err());
+    }
+}
@@ -0,0 +1,331 @@
+use lettre::message::header::{HeaderName, HeaderValue};
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Address, SmtpTransport, Transport};
+use rusqlite::{Connection, OptionalExtension, params};
+use crate::notifications::query_unread_notifications_since;
+use crate::queries::{DbConnection, extract_user, QueryResult};
+use crate::settings::Settings;
+use crate::structs::{EmailMode, EmailSettings, LogicError, Notification, NotificationKind, SaveEmailSettings, User};
+
+pub const DIGEST_INTERVAL_HOURS: i64 = 24;
+const OUTBOX_BATCH_SIZE: u32 = 100;
+const RETRY_BASE_DELAY_SECONDS: i64 = 60;
+
+const NOTIFICATION_TXT: &str = include_str!("../templates/email/notification.txt");
+const NOTIFICATION_HTML: &str = include_str!("../templates/email/notification.html");
+const DIGEST_TXT: &str = include_str!("../templates/email/digest.txt");
+const DIGEST_HTML: &str = include_str!("../templates/email/digest.html");
+const PASSWORD_RESET_TXT: &str = include_str!("../templates/email/password_reset.txt");
+const PASSWORD_RESET_HTML: &str = include_str!("../templates/email/password_reset.html");
+
+struct Recipient {
+    user: User,
+    email: String,
+    mode: EmailMode,
+    unsubscribe_token: String,
+    last_notified_time: i64,
+}
+
+struct OutgoingEmail {
+    id: u64,
+    recipient: String,
+    subject: String,
+    text_body: String,
+    html_body: String,
+    unsubscribe_url: String,
+    attempts: u32,
+}
+
+pub fn query_email_settings(conn: &DbConnection, user: &User) -> QueryResult<EmailSettings> {
+    let settings = conn.query_row("SELECT email, mode FROM EMAIL_SETTINGS WHERE user_id = ?", [user.id], |row| Ok(EmailSettings {
+        email: row.get(0)?,
+        mode: row.get(1)?,
+    })).optional()?;
+
+    Ok(settings.unwrap_or(EmailSettings{email: None, mode: EmailMode::Off}))
+}
+
+// The unsubscribe token is only used for new settings, existing links keep working.
+// Turning emails back on starts from now, older notifications are not sent.
+pub fn save_email_settings(conn: &DbConnection, user: &User, settings: &SaveEmailSettings, unsubscribe_token: &str) -> QueryResult<()> {
+    let email = settings.email.trim();
+    if email.parse::<Address>().is_err() {
+        if settings.mode != EmailMode::Off {
+            return Err(LogicError::InvalidEmail.into());
+        }
+
+        // turning emails off doesn't need an address, the stored one is kept
+        conn.execute("UPDATE EMAIL_SETTINGS SET mode = 'off' WHERE user_id = ?", [user.id])?;
+        return Ok(());
+    }
+
+    let t = chrono::offset::Utc::now();
+    let t = t.timestamp_millis();
+
+    conn.execute("INSERT INTO EMAIL_SETTINGS(user_id, email, mode, unsubscribe_token, last_notified_time) VALUES(?, ?, ?, ?, ?) \
+        ON CONFLICT(user_id) DO UPDATE SET email = excluded.email, mode = excluded.mode, \
+        last_notified_time = CASE WHEN mode = 'off' THEN excluded.last_notified_time ELSE last_notified_time END",
+                 params![user.id, email, settings.mode, unsubscribe_token, t])?;
+
+    Ok(())
+}
+
+pub fn is_unsubscribe_token_valid(conn: &DbConnection, token: &str) -> QueryResult<bool> {
+    let found = conn.query_row("SELECT 1 FROM EMAIL_SETTINGS WHERE unsubscribe_token = ?", [token], |_| Ok(())).optional()?;
+
+    Ok(found.is_some())
+}
+
+// Returns false when the token is unknown
+pub fn unsubscribe_by_token(conn: &DbConnection, token: &str) -> QueryResult<bool> {
+    let count = conn.execute("UPDATE EMAIL_SETTINGS SET mode = 'off' WHERE unsubscribe_token = ?", [token])?;
+
+    Ok(count > 0)
+}
+
+pub fn render_template(template: &str, values: &[(&str, &str)]) -> String {
+    let mut result = template.to_string();
+    for (name, value) in values {
+        result = result.replace(&format!("{{{{{}}}}}", name), value);
+    }
+    result
+}
+
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn describe_notification(notification: &Notification) -> String {
+    let title = notification.title.as_deref().unwrap_or("deleted");
+    let actor = &notification.actor.name;
+
+    let text = match notification.kind {
+        NotificationKind::Message => format!("{} posted in \"{}\"", actor, title),
+        NotificationKind::Thread => format!("{} started the thread \"{}\"", actor, title),
+        NotificationKind::Comment => format!("{} commented on \"{}\"", actor, title),
+        NotificationKind::ArticleUpdate => format!("{} updated the article \"{}\"", actor, title),
+        NotificationKind::Mention => format!("{} mentioned you in \"{}\"", actor, title),
+    };
+
+    if notification.count > 1 {
+        format!("{} ({} new events)", text, notification.count)
+    } else {
+        text
+    }
+}
+
+fn notification_url(notification: &Notification, base_url: &str) -> String {
+    match (notification.thread_id, notification.article_id) {
+        (Some(thread_id), _) => format!("{}/forum/t/{}?page={}", base_url, thread_id, notification.page.unwrap_or(1)),
+        (None, Some(article_id)) => format!("{}/article/a/{}", base_url, article_id),
+        (None, None) => base_url.to_string(),
+    }
+}
+
+// Subject, plain-text and HTML bodies
+fn render_email(notifications: &[Notification], recipient: &Recipient, unsubscribe_url: &str, base_url: &str) -> (String, String, String) {
+    let (text_template, html_template, subject) = match recipient.mode {
+        EmailMode::Digest => (DIGEST_TXT, DIGEST_HTML, format!("The Ark: {} new notifications", notifications.len())),
+        _ => (NOTIFICATION_TXT, NOTIFICATION_HTML, format!("The Ark: {}", describe_notification(&notifications[0]))),
+    };
+
+    let text_items = notifications.iter()
+        .map(|n| format!("- {}\n  {}", describe_notification(n), notification_url(n, base_url)))
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    let html_items = notifications.iter()
+        .map(|n| format!("<li><a href=\"{}\">{}</a></li>", escape_html(&notification_url(n, base_url)), escape_html(&describe_notification(n))))
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    let text = render_template(text_template, &[("user", &recipient.user.name), ("items", &text_items), ("unsubscribe_url", unsubscribe_url)]);
+    let html = render_template(html_template, &[("user", &escape_html(&recipient.user.name)), ("items", &html_items), ("unsubscribe_url", &escape_html(unsubscribe_url))]);
+
+    (subject, text, html)
+}
+
+fn enqueue_email(conn: &Connection, recipient: &Recipient, notifications: &[Notification], base_url: &str, t: i64) -> QueryResult<()> {
+    let unsubscribe_url = format!("{}/unsubscribe/{}", base_url, recipient.unsubscribe_token);
+    let (subject, text, html) = render_email(notifications, recipient, &unsubscribe_url, base_url);
+
+    conn.execute("INSERT INTO EMAIL_OUTBOX(user_id, recipient, subject, text_body, html_body, unsubscribe_url, next_attempt_time, create_time) \
+        VALUES(?, ?, ?, ?, ?, ?, ?, ?)",
+                 params![recipient.user.id, recipient.email, subject, text, html, unsubscribe_url, t, t])?;
+
+    Ok(())
+}
+
+// Account emails are sent regardless of the notification mode and have no unsubscribe link.
+// Returns false when the user has no email.
+pub fn enqueue_password_reset_email(conn: &Connection, user: &User, reset_url: &str, hours: u32) -> QueryResult<bool> {
+    let email: Option<Option<String>> = conn.query_row("SELECT email FROM EMAIL_SETTINGS WHERE user_id = ?", [user.id], |row| row.get(0)).optional()?;
+    let email = match email.flatten() {
+        Some(email) => email,
+        None => return Ok(false),
+    };
+
+    let hours = hours.to_string();
+    let text = render_template(PASSWORD_RESET_TXT, &[("user", &user.name), ("reset_url", reset_url), ("hours", &hours)]);
+    let html = render_template(PASSWORD_RESET_HTML, &[("user", &escape_html(&user.name)), ("reset_url", &escape_html(reset_url)), ("hours", &hours)]);
+
+    let t = chrono::offset::Utc::now();
+    let t = t.timestamp_millis();
+
+    conn.execute("INSERT INTO EMAIL_OUTBOX(user_id, recipient, subject, text_body, html_body, unsubscribe_url, next_attempt_time, create_time) \
+        VALUES(?, ?, ?, ?, ?, '', ?, ?)",
+                 params![user.id, email, "The Ark: password reset", text, html, t, t])?;
+
+    Ok(true)
+}
+
+// Puts unread notifications into the outbox: one email per notification in immediate mode,
+// one email a day in digest mode. Returns the number of queued emails.
+pub fn enqueue_notification_emails(conn: &mut DbConnection, base_url: &str) -> QueryResult<usize> {
+    let transaction = conn.transaction()?;
+
+    let t = chrono::offset::Utc::now();
+    let digest_before = (t - chrono::Duration::hours(DIGEST_INTERVAL_HOURS)).timestamp_millis();
+    let t = t.timestamp_millis();
+
+    let mut stmt = transaction.prepare(
+        "SELECT u.id, u.name, u.display_name, u.avatar_file_id, s.email, s.mode, s.unsubscribe_token, s.last_notified_time \
+        FROM EMAIL_SETTINGS s INNER JOIN USER u ON u.id = s.user_id \
+        WHERE s.mode = 'immediate' OR (s.mode = 'digest' AND s.last_notified_time <= ?)",
+    )?;
+
+    let recipients = stmt.query_map([digest_before], |row| Ok(Recipient {
+        user: extract_user(row, 0)?,
+        email: row.get(4)?,
+        mode: row.get(5)?,
+        unsubscribe_token: row.get(6)?,
+        last_notified_time: row.get(7)?,
+    }))?.collect::<Result<Vec<Recipient>, _>>()?;
+    drop(stmt);
+
+    let mut count = 0;
+    for recipient in &recipients {
+        let notifications = query_unread_notifications_since(&transaction, recipient.user.id, recipient.last_notified_time)?;
+
+        if !notifications.is_empty() {
+            if recipient.mode == EmailMode::Digest {
+                enqueue_email(&transaction, recipient, &notifications, base_url, t)?;
+                count += 1;
+            } else {
+                for notification in &notifications {
+                    enqueue_email(&transaction, recipient, std::slice::from_ref(notification), base_url, t)?;
+                    count += 1;
+                }
+            }
+        }
+
+        transaction.execute("UPDATE EMAIL_SETTINGS SET last_notified_time = ? WHERE user_id = ?", params![t, recipient.user.id])?;
+    }
+
+    transaction.commit()?;
+    Ok(count)
+}
+
+pub fn build_smtp_transport(settings: &Settings) -> QueryResult<Option<SmtpTransport>> {
+    let host = match &settings.smtp_host {
+        Some(host) => host,
+        None => return Ok(None)
+    };
+
+    let builder = match settings.smtp_security.as_str() {
+        "none" => SmtpTransport::builder_dangerous(host),
+        "starttls" => SmtpTransport::starttls_relay(host)?,
+        "tls" => SmtpTransport::relay(host)?,
+        other => panic!("Invalid value of ARK_SMTP_SECURITY: {}", other)
+    };
+
+    let mut builder = builder.port(settings.smtp_port);
+    if let (Some(user), Some(password)) = (&settings.smtp_user, &settings.smtp_password) {
+        builder = builder.credentials(Credentials::new(user.clone(), password.clone()));
+    }
+
+    Ok(Some(builder.build()))
+}
+
+fn build_message(email: &OutgoingEmail, from: &str) -> QueryResult<lettre::Message> {
+    let mut builder = lettre::Message::builder()
+        .from(from.parse::<Mailbox>()?)
+        .to(email.recipient.parse::<Mailbox>()?)
+        .subject(email.subject.clone());
+
+    if !email.unsubscribe_url.is_empty() {
+        builder = builder
+            .raw_header(HeaderValue::new(HeaderName::new_from_ascii_str("List-Unsubscribe"), format!("<{}>", email.unsubscribe_url)))
+            .raw_header(HeaderValue::new(HeaderName::new_from_ascii_str("List-Unsubscribe-Post"), "List-Unsubscribe=One-Click".to_string()));
+    }
+
+    let message = builder.multipart(MultiPart::alternative_plain_html(email.text_body.clone(), email.html_body.clone()))?;
+
+    Ok(message)
+}
+
+// Sends due emails from the outbox, failed ones are retried with exponential backoff
+// until max_attempts is reached. Returns (sent, failed) counts.
+pub fn deliver_pending_emails(conn: &DbConnection, transport: &SmtpTransport, from: &str, max_attempts: u32) -> QueryResult<(usize, usize)> {
+    let t = chrono::offset::Utc::now();
+    let t = t.timestamp_millis();
+
+    let mut stmt = conn.prepare(
+        "SELECT id, recipient, subject, text_body, html_body, unsubscribe_url, attempts FROM EMAIL_OUTBOX \
+        WHERE sent_time IS NULL AND attempts < ? AND next_attempt_time <= ? \
+        ORDER BY id LIMIT ?",
+    )?;
+
+    let emails = stmt.query_map(params![max_attempts, t, OUTBOX_BATCH_SIZE], |row| Ok(OutgoingEmail {
+        id: row.get(0)?,
+        recipient: row.get(1)?,
+        subject: row.get(2)?,
+        text_body: row.get(3)?,
+        html_body: row.get(4)?,
+        unsubscribe_url: row.get(5)?,
+        attempts: row.get(6)?,
+    }))?.collect::<Result<Vec<OutgoingEmail>, _>>()?;
+
+    let mut sent = 0;
+    let mut failed = 0;
+    for email in &emails {
+        let result = build_message(email, from).and_then(|message| Ok(transport.send(&message)?));
+        let t = chrono::offset::Utc::now().timestamp_millis();
+
+        match result {
+            Ok(_) => {
+                conn.execute("UPDATE EMAIL_OUTBOX SET sent_time = ?, attempts = attempts + 1, last_error = NULL WHERE id = ?", params![t, email.id])?;
+                sent += 1;
+            }
+            Err(e) => {
+                let next_attempt_time = t + retry_delay_seconds(email.attempts) * 1000;
+                conn.execute("UPDATE EMAIL_OUTBOX SET attempts = attempts + 1, next_attempt_time = ?, last_error = ? WHERE id = ?",
+                             params![next_attempt_time, e.to_string(), email.id])?;
+                failed += 1;
+            }
+        }
+    }
+
+    Ok((sent, failed))
+}
+
+pub fn retry_delay_seconds(attempts: u32) -> i64 {
+    RETRY_BASE_DELAY_SECONDS << attempts.min(10)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn email_templates_are_rendered() {
+        assert_eq!(render_template("Hi {{user}}, {{user}}! {{other}}", &[("user", "bob")]), "Hi bob, bob! {{other}}");
+        assert_eq!(escape_html("<a href=\"x\">&</a>"), "&lt;a href=&quot;x&quot;&gt;&amp;&lt;/a&gt;");
+        assert_eq!(retry_delay_seconds(0), 60);
+        assert_eq!(retry_delay_seconds(3), 480);
+    }
+}
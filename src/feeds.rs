@@ -0,0 +1,269 @@
+use std::time::{Duration, UNIX_EPOCH};
+use actix_web::http::header;
+use actix_web::http::header::HttpDate;
+use actix_web::{HttpRequest, HttpResponse};
+use data_encoding::HEXLOWER;
+use ring::digest::{digest, SHA256};
+use rusqlite::{OptionalExtension, params};
+use crate::queries::{article_visibility_restriction, DbConnection, extract_user, MESSAGES_PER_PAGE, query_threads, QueryResult};
+use crate::structs::{FeedToken, LogicError, ThreadQuery, ThreadsQueryType, User};
+
+pub const FEED_ENTRIES: u32 = 50;
+
+pub struct FeedEntry {
+    pub id: String,
+    pub title: String,
+    pub link: String,
+    pub author: String,
+    pub updated: i64,
+    pub content: String,
+}
+
+pub struct Feed {
+    pub id: String,
+    pub title: String,
+    pub link: String,
+    pub entries: Vec<FeedEntry>,
+}
+
+impl Feed {
+    // Time of the latest entry, feeds without entries are never modified
+    pub fn updated(&self) -> i64 {
+        self.entries.iter().map(|e| e.updated).max().unwrap_or(0)
+    }
+}
+
+pub fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn format_time(millis: i64) -> String {
+    chrono::DateTime::from_timestamp_millis(millis)
+        .unwrap_or_default()
+        .to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
+
+pub fn render_atom(feed: &Feed) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str(&format!("<id>{}</id>\n<title>{}</title>\n<link rel=\"alternate\" href=\"{}\"/>\n<updated>{}</updated>\n",
+                          escape_xml(&feed.id), escape_xml(&feed.title), escape_xml(&feed.link), format_time(feed.updated())));
+
+    for entry in &feed.entries {
+        xml.push_str(&format!("<entry>\n<id>{}</id>\n<title>{}</title>\n<link rel=\"alternate\" href=\"{}\"/>\n<author><name>{}</name></author>\n<updated>{}</updated>\n<content type=\"text\">{}</content>\n</entry>\n",
+                              escape_xml(&entry.id), escape_xml(&entry.title), escape_xml(&entry.link), escape_xml(&entry.author),
+                              format_time(entry.updated), escape_xml(&entry.content)));
+    }
+
+    xml.push_str("</feed>\n");
+    xml
+}
+
+// Renders the feed or answers 304 when the client has the current version,
+// If-None-Match takes precedence over If-Modified-Since
+pub fn feed_response(req: &HttpRequest, feed: &Feed) -> HttpResponse {
+    let xml = render_atom(feed);
+    let etag = format!("\"{}\"", HEXLOWER.encode(&digest(&SHA256, xml.as_bytes()).as_ref()[..16]));
+    let last_modified = HttpDate::from(UNIX_EPOCH + Duration::from_secs((feed.updated() / 1000) as u64));
+
+    let not_modified = match req.headers().get(header::IF_NONE_MATCH).and_then(|v| v.to_str().ok()) {
+        Some(tags) => tags.split(',').map(str::trim).any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag),
+        None => req.headers().get(header::IF_MODIFIED_SINCE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<HttpDate>().ok())
+            .is_some_and(|since| last_modified <= since)
+    };
+
+    let mut response = if not_modified { HttpResponse::NotModified() } else { HttpResponse::Ok() };
+    response.insert_header((header::ETAG, etag))
+        .insert_header((header::LAST_MODIFIED, last_modified))
+        .insert_header((header::CACHE_CONTROL, "no-cache"));
+
+    if not_modified {
+        response.finish()
+    } else {
+        response.content_type("application/atom+xml; charset=utf-8").body(xml)
+    }
+}
+
+fn thread_link(base_url: &str, thread_id: u32) -> String {
+    format!("{}/forum/t/{}", base_url, thread_id)
+}
+
+// Recently active threads, all of them or of a section or subsection
+pub fn query_threads_feed(conn: &DbConnection, query_type: ThreadsQueryType, id: Option<u32>, base_url: &str) -> QueryResult<Feed> {
+    let (title, link, feed_path) = match (&query_type, id) {
+        (ThreadsQueryType::All, _) => ("The Ark: forum".to_string(), format!("{}/forum", base_url), "threads".to_string()),
+        (ThreadsQueryType::Section, Some(id)) => {
+            let name: String = conn.query_row("SELECT name FROM SECTION WHERE id = ?", [id], |row| row.get(0))
+                .optional()?.ok_or(LogicError::SectionNotFound)?;
+            (format!("The Ark: {}", name), format!("{}/forum/s/{}", base_url, id), format!("section/{}", id))
+        }
+        (ThreadsQueryType::SubSection, Some(id)) => {
+            let name: String = conn.query_row("SELECT name FROM SUBSECTION WHERE id = ?", [id], |row| row.get(0))
+                .optional()?.ok_or(LogicError::SubsectionNotFound)?;
+            (format!("The Ark: {}", name), format!("{}/forum/ss/{}", base_url, id), format!("subsection/{}", id))
+        }
+        _ => return Err(LogicError::SectionNotFound.into())
+    };
+
+    let threads = query_threads(conn, &ThreadQuery{query_type, id, page: 1, tags: None}, &None)?;
+
+    let entries = threads.into_iter().map(|thread| FeedEntry {
+        id: thread_link(base_url, thread.id),
+        link: thread_link(base_url, thread.id),
+        content: format!("{} messages, the last one by {}", thread.total_messages, thread.last_message_user.name),
+        title: thread.name,
+        author: thread.author.name,
+        updated: thread.last_message_date_time as i64,
+    }).collect();
+
+    Ok(Feed {
+        id: format!("{}/feed/{}", base_url, feed_path),
+        title,
+        link,
+        entries,
+    })
+}
+
+// Latest messages of the thread
+pub fn query_thread_feed(conn: &DbConnection, thread_id: u32, base_url: &str) -> QueryResult<Feed> {
+    let name: String = conn.query_row("SELECT tn.name FROM THREAD t INNER JOIN THREAD_NAME_FTS tn ON tn.rowid = t.name_id WHERE t.id = ? AND t.deleted = FALSE AND t.publish_time IS NULL",
+                                      [thread_id], |row| row.get(0))
+        .optional()?.ok_or(LogicError::ThreadNotFound)?;
+
+    let mut stmt = conn.prepare(
+        "SELECT m.id, u.name, m.update_time, c.content, \
+        (SELECT count(1) FROM MESSAGE p WHERE p.thread_id = m.thread_id AND p.id < m.id) / ? + 1 \
+        FROM MESSAGE m INNER JOIN USER u ON u.id = m.user_id INNER JOIN MESSAGE_CONTENT_FTS c ON c.rowid = m.content_id \
+        WHERE m.thread_id = ? ORDER BY m.id DESC LIMIT ?",
+    )?;
+
+    let entries = stmt.query_map(params![MESSAGES_PER_PAGE, thread_id, FEED_ENTRIES], |row| {
+        let id: u32 = row.get(0)?;
+        let page: u32 = row.get(4)?;
+        let link = format!("{}?page={}#m{}", thread_link(base_url, thread_id), page, id);
+
+        Ok(FeedEntry {
+            id: link.clone(),
+            title: format!("{} #{}", name, id),
+            link,
+            author: row.get(1)?,
+            updated: row.get(2)?,
+            content: row.get(3)?,
+        })
+    })?.collect::<Result<Vec<FeedEntry>, _>>()?;
+
+    Ok(Feed {
+        id: format!("{}/feed/thread/{}", base_url, thread_id),
+        title: format!("The Ark: {}", name),
+        link: thread_link(base_url, thread_id),
+        entries,
+    })
+}
+
+// Recently changed articles, every version is a separate entry.
+// Private articles are included only for the owner of the feed token.
+// Versions that were private at the time are shown only to the owner of the article
+fn version_visibility_restriction(user: &Option<User>) -> &str {
+    if user.is_some() {
+        "(v.visibility = 'public' OR v.id IN (SELECT a.id FROM ARTICLE a WHERE a.active = TRUE AND a.user_id = ?))"
+    } else {
+        "v.visibility = 'public'"
+    }
+}
+
+pub fn query_articles_feed(conn: &DbConnection, user: &Option<User>, base_url: &str) -> QueryResult<Feed> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT v.id, v.version, v.active, COALESCE(c.name, v.name), COALESCE(c.content, v.content), u.name, v.create_time \
+        FROM ARTICLE v INNER JOIN USER u ON u.id = v.user_id LEFT JOIN ARTICLE_CONTENT_FTS c ON c.rowid = v.content_id \
+        WHERE v.id IN (SELECT a.id FROM ARTICLE a WHERE a.active = TRUE AND a.visibility <> 'scheduled' AND {}) AND {} \
+        ORDER BY v.create_time DESC LIMIT ?", article_visibility_restriction(user), version_visibility_restriction(user)),
+    )?;
+
+    let map_row = |row: &rusqlite::Row| {
+        let id: u32 = row.get(0)?;
+        let version: u32 = row.get(1)?;
+        let active: bool = row.get(2)?;
+        let name: String = row.get(3)?;
+        let link = if active { format!("{}/article/a/{}", base_url, id) } else { format!("{}/article/d/{}/{}", base_url, id, version) };
+
+        Ok(FeedEntry {
+            id: format!("{}/article/d/{}/{}", base_url, id, version),
+            title: format!("{} (version {})", name, version),
+            link,
+            author: row.get(5)?,
+            updated: row.get(6)?,
+            content: row.get(4)?,
+        })
+    };
+
+    let entries = match user {
+        Some(user) => stmt.query_map(params![user.id, user.id, FEED_ENTRIES], map_row)?,
+        None => stmt.query_map(params![FEED_ENTRIES], map_row)?,
+    }.collect::<Result<Vec<FeedEntry>, _>>()?;
+
+    Ok(Feed {
+        id: format!("{}/feed/articles", base_url),
+        title: "The Ark: articles".to_string(),
+        link: format!("{}/articles", base_url),
+        entries,
+    })
+}
+
+pub fn query_feed_token_user(conn: &DbConnection, token: &str) -> QueryResult<Option<User>> {
+    let user = conn.query_row("SELECT u.id, u.name, u.display_name, u.avatar_file_id FROM FEED_TOKEN f INNER JOIN USER u ON u.id = f.user_id WHERE f.token = ?", [token],
+                              |row| extract_user(row, 0)).optional()?;
+
+    Ok(user)
+}
+
+pub fn query_feed_token(conn: &DbConnection, user: &User) -> QueryResult<FeedToken> {
+    let token = conn.query_row("SELECT token FROM FEED_TOKEN WHERE user_id = ?", [user.id], |row| row.get(0)).optional()?;
+
+    Ok(FeedToken{token})
+}
+
+// Returns the existing token, the new one is stored only if the user has none or reset is requested
+pub fn save_feed_token(conn: &DbConnection, user: &User, new_token: &str, reset: bool) -> QueryResult<FeedToken> {
+    if reset {
+        conn.execute("INSERT OR REPLACE INTO FEED_TOKEN(user_id, token) VALUES(?, ?)", params![user.id, new_token])?;
+    } else {
+        conn.execute("INSERT OR IGNORE INTO FEED_TOKEN(user_id, token) VALUES(?, ?)", params![user.id, new_token])?;
+    }
+
+    let token = conn.query_row("SELECT token FROM FEED_TOKEN WHERE user_id = ?", [user.id], |row| row.get(0))?;
+
+    Ok(FeedToken{token})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn atom_feed_is_rendered() {
+        let feed = Feed {
+            id: "http://ark/feed/threads".to_string(),
+            title: "Q&A".to_string(),
+            link: "http://ark/forum".to_string(),
+            entries: vec![FeedEntry {
+                id: "http://ark/forum/t/1".to_string(),
+                title: "<b>".to_string(),
+                link: "http://ark/forum/t/1".to_string(),
+                author: "bob".to_string(),
+                updated: 1_000_000_000_000,
+                content: "a \"quote\"".to_string(),
+            }],
+        };
+
+        let xml = render_atom(&feed);
+        assert!(xml.contains("<title>Q&amp;A</title>"));
+        assert!(xml.contains("<title>&lt;b&gt;</title>"));
+        assert!(xml.contains("<updated>2001-09-09T01:46:40Z</updated>"));
+        assert!(xml.contains("<content type=\"text\">a &quot;quote&quot;</content>"));
+    }
+}
@@ -0,0 +1,156 @@
+use std::time::Duration;
+use actix_web::web::Bytes;
+use futures_util::Stream;
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use crate::queries::QueryResult;
+use crate::structs::{ArticleVisibility, LogicError, User};
+
+pub const EVENT_CHANNEL_CAPACITY: usize = 1024;
+pub const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Serialize, Copy, Clone)]
+pub enum LiveEventKind {
+    #[serde(rename = "message")]
+    Message,
+    #[serde(rename = "messageEdited")]
+    MessageEdited,
+    #[serde(rename = "thread")]
+    Thread,
+    #[serde(rename = "article")]
+    Article,
+    #[serde(rename = "articleUpdate")]
+    ArticleUpdate,
+    #[serde(rename = "comment")]
+    Comment,
+    #[serde(rename = "commentEdited")]
+    CommentEdited,
+}
+
+#[derive(Serialize, Clone)]
+pub struct LiveEvent {
+    pub kind: LiveEventKind,
+    #[serde(rename = "threadId")]
+    pub thread_id: Option<u32>,
+    #[serde(rename = "subsectionId")]
+    pub subsection_id: Option<u32>,
+    #[serde(rename = "articleId")]
+    pub article_id: Option<u32>,
+    // message id for threads, comment id for articles
+    #[serde(rename = "messageId")]
+    pub message_id: Option<u32>,
+    pub user: User,
+    // visibility and owner of the article, the event is not sent to users who can't see it
+    #[serde(skip)]
+    pub article_access: Option<(ArticleVisibility, u32)>,
+    // author of a thread that is not published yet, the only user who receives its events
+    #[serde(skip)]
+    pub scheduled_thread_author: Option<u32>,
+}
+
+// Without any filter all events are received, otherwise events matching any of the filters
+#[derive(Deserialize)]
+pub struct LiveEventsQuery {
+    #[serde(rename = "threadId")]
+    pub thread_id: Option<u32>,
+    #[serde(rename = "subsectionId")]
+    pub subsection_id: Option<u32>,
+    #[serde(rename = "articleId")]
+    pub article_id: Option<u32>,
+}
+
+impl LiveEventsQuery {
+    fn matches(&self, event: &LiveEvent, user: &User) -> bool {
+        if let Some((visibility, owner_id)) = event.article_access {
+            if !matches!(visibility, ArticleVisibility::Public) && owner_id != user.id {
+                return false;
+            }
+        }
+
+        if event.scheduled_thread_author.is_some_and(|author_id| author_id != user.id) {
+            return false;
+        }
+
+        if self.thread_id.is_none() && self.subsection_id.is_none() && self.article_id.is_none() {
+            return true;
+        }
+
+        (self.thread_id.is_some() && self.thread_id == event.thread_id)
+            || (self.subsection_id.is_some() && self.subsection_id == event.subsection_id)
+            || (self.article_id.is_some() && self.article_id == event.article_id)
+    }
+}
+
+pub struct LiveEvents {
+    sender: broadcast::Sender<LiveEvent>,
+}
+
+impl LiveEvents {
+    pub fn new() -> LiveEvents {
+        let (sender, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        LiveEvents { sender }
+    }
+
+    pub fn publish(&self, event: LiveEvent) {
+        // fails only when nobody is listening
+        let _ = self.sender.send(event);
+    }
+
+    // Server-Sent Events stream of the events visible to the user, a comment line is sent
+    // periodically to keep the connection open. Slow clients skip the events they missed.
+    pub fn subscribe(&self, filter: LiveEventsQuery, user: User) -> impl Stream<Item = Result<Bytes, actix_web::Error>> {
+        let receiver = self.sender.subscribe();
+
+        futures_util::stream::unfold((receiver, filter, user), |(mut receiver, filter, user)| async move {
+            loop {
+                match actix_web::rt::time::timeout(KEEP_ALIVE_INTERVAL, receiver.recv()).await {
+                    Err(_) => return Some((Ok(Bytes::from_static(b": keep-alive\n\n")), (receiver, filter, user))),
+                    Ok(Ok(event)) if filter.matches(&event, &user) => {
+                        let data = serde_json::to_string(&event).unwrap_or_default();
+                        return Some((Ok(Bytes::from(format!("data: {}\n\n", data))), (receiver, filter, user)));
+                    }
+                    Ok(Ok(_)) | Ok(Err(RecvError::Lagged(_))) => continue,
+                    Ok(Err(RecvError::Closed)) => return None,
+                }
+            }
+        })
+    }
+}
+
+pub fn thread_event(conn: &Connection, kind: LiveEventKind, thread_id: u32, message_id: Option<u32>, user: &User) -> QueryResult<LiveEvent> {
+    let (subsection_id, scheduled, author_id): (u32, bool, u32) = conn.query_row("SELECT subsection_id, publish_time IS NOT NULL, author_id FROM THREAD WHERE id = ?", [thread_id],
+                                                                                 |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .optional()?
+        .ok_or(LogicError::ThreadNotFound)?;
+
+    Ok(LiveEvent {
+        kind,
+        thread_id: Some(thread_id),
+        subsection_id: Some(subsection_id),
+        article_id: None,
+        message_id,
+        user: user.clone(),
+        article_access: None,
+        scheduled_thread_author: if scheduled {Some(author_id)} else {None},
+    })
+}
+
+pub fn article_event(conn: &Connection, kind: LiveEventKind, article_id: u32, comment_id: Option<u32>, user: &User) -> QueryResult<LiveEvent> {
+    let access: (ArticleVisibility, u32) = conn.query_row("SELECT visibility, user_id FROM ARTICLE WHERE id = ? AND active = TRUE", [article_id],
+                                                          |row| Ok((row.get(0)?, row.get(1)?)))
+        .optional()?
+        .ok_or(LogicError::ArticleNotFound)?;
+
+    Ok(LiveEvent {
+        kind,
+        thread_id: None,
+        subsection_id: None,
+        article_id: Some(article_id),
+        message_id: comment_id,
+        user: user.clone(),
+        article_access: Some(access),
+        scheduled_thread_author: None,
+    })
+}
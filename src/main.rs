@@ -1,23 +1,40 @@
 mod queries;
 mod structs;
 mod create_db;
+mod settings;
+mod notifications;
+mod mentions;
+mod email;
+mod live_events;
+mod webhooks;
+mod feeds;
+mod reactions;
+mod polls;
+mod tags;
+mod drafts;
+mod scheduling;
+mod profiles;
+mod passwords;
 
 use std::fs::File;
 use std::future::Future;
 use std::io::{BufReader, Read};
 use std::pin::Pin;
 use std::sync::Mutex;
+use std::time::Duration;
 use actix_files::NamedFile;
 use actix_web::{App, cookie, error, FromRequest, get, HttpRequest, HttpResponse, HttpServer, post, Responder, Result, web};
 use actix_web::dev::Payload;
 use r2d2::Pool;
+use rusqlite::Connection;
 use r2d2_sqlite::SqliteConnectionManager;
 use rand::distributions::Alphanumeric;
 use rand::prelude::*;
 use rand_chacha::ChaCha20Rng;
+use lettre::SmtpTransport;
 use structs::{ForumStructure, Message, MessagesQuery, MessagesQueryResult, Section, SubSection, Thread, ThreadQuery, ThreadsQueryType, User};
-use crate::queries::{add_favorite_article, create_article, create_section, create_sub_section, create_thread, delete_article, delete_thread, find_user, move_subsection, move_thread, query_article, query_article_comments, query_articles, query_articles_by_search_params, query_favorite_articles, query_file, query_forum_structure, query_messages_by_search_params, query_sessions, query_thread, query_thread_count, query_thread_messages, query_threads, query_user_by_session, QueryResult, remove_all_sessions, remove_favorite_article, remove_session, rename_section, rename_subsection, rename_thread, save_comment, save_file_mapping, save_message, save_user, save_user_password, save_user_session, update_article, update_comment, update_message, verify_user_password};
-use crate::structs::{AddFavoriteArticle, Article, Articles, ArticleSearchParams, ArticleSearchResults, CommentsQuery, CommentsQueryResult, CreateArticle, CreateThreadMessage, DeleteArticle, DeleteThread, GetArticle, LogicError, LogoutParams, MessageSearchParams, MessageSearchResults, MoveSubsection, MoveThread, PostComment, PostMessage, PostNewSection, PostNewSubsection, RemoveFavoriteArticle, RemoveSession, RenameSection, RenameSubsection, RenameThread, SignOnParams, SignUpParams, ThreadQueryResult, UpdateArticle, UpdateComment, UpdateMessage, UploadedFile, UploadedFiles, UploadedFileWithLocation, UserSessions};
+use crate::queries::{add_favorite_article, check_thread_visible, fill_unread_info, is_thread_visible, mark_thread_read, mark_threads_read, apply_article_template, attach_article_file, backfill_article_slugs, create_article, delete_article_template, query_article_template, query_article_templates, save_article_template, create_section, create_sub_section, create_thread, delete_article, delete_thread, find_user, move_subsection, move_thread, query_article, query_article_attachments, query_article_comments, query_articles, query_articles_by_search_params, query_broken_links, query_favorite_articles, query_file, query_forum_structure, query_messages_by_search_params, query_sessions, query_thread, query_thread_count, query_thread_messages, query_threads, query_user_by_session, query_user_is_admin, query_deleted_articles, purge_deleted_articles, resolve_article_slug, restore_article, DbConnection, QueryResult, remove_all_sessions, remove_other_sessions, remove_user_sessions, remove_article_attachment, remove_favorite_article, remove_session, rename_section, rename_subsection, rename_thread, save_comment, save_file_mapping, save_message, save_user, save_user_password, save_user_session, update_article, update_comment, update_message, update_thread_flags, merge_threads, split_thread, query_message_location, query_comment_location, message_location_path, comment_location_path, query_message_replies, update_subsection_description, delete_section, delete_subsection, restore_section, restore_subsection, reorder_sections, reorder_subsections, query_deleted_sections, verify_user_password};
+use crate::structs::{AddFavoriteArticle, SubscriptionTarget, Article, ArticleAttachments, ArticleAttachmentsQuery, Articles, ArticleTemplates, DeleteArticleTemplate, SaveArticleTemplate, ArticleSearchParams, ArticleSearchResults, ArticlesQuery, BrokenLinks, CommentsQuery, CommentsQueryResult, CreateArticle, CreateThreadMessage, DeleteArticle, DeletedArticles, DeleteThread, DeleteWebhook, EmailSettings, FeedToken, FeedTokenQuery, GetArticle, GetArticleByPath, LogicError, LogoutParams, MarkNotificationsRead, MarkThreadRead, MarkThreadsRead, NotificationCount, Notifications, NotificationsQuery, MessageSearchParams, MessageSearchResults, MoveSubsection, MoveThread, PostComment, PostMessage, PostNewSection, PostNewSubsection, RemoveArticleAttachment, RemoveFavoriteArticle, RemoveSession, RenameSection, RenameSubsection, RenameThread, RestoreArticle, SaveEmailSettings, SaveWebhook, SignOnParams, SignUpParams, Subscribe, Subscriptions, ThreadQueryResult, TrashQuery, UpdateArticle, UpdateComment, UpdateMessage, UpdateThreadFlags, UploadedFile, Poll, PollQuery, PollVote, MergeTags, RenameTag, DeleteDraft, ScheduledPublications, ChangePassword, CreatePasswordReset, PasswordHashReport, PasswordResetLink, RequestPasswordReset, ResetPasswordForm, ProfileActivityQuery, ProfileComments, ProfileQuery, UpdateProfile, UserProfile, Draft, DraftQuery, Drafts, SaveDraft, SetThreadTags, Tags, TagSearchQuery, AllowedReactions, ReactionParams, UpdateSubsectionDescription, DeleteSection, DeleteSubsection, RestoreSection, RestoreSubsection, ReorderSections, ReorderSubsections, DeletedSections, MergeThreads, SplitThread, SplitThreadResult, MessageLocation, MessageLocateQuery, CommentLocation, CommentLocateQuery, MessageReplies, MessageRepliesQuery, Users, UserSearchQuery, UploadedFiles, UploadedFileWithLocation, UserSessions, WebhookDeliveries, WebhookDeliveriesQuery, Webhooks};
 
 use data_encoding::HEXLOWER;
 use ring::digest::{Context, Digest, SHA256};
@@ -25,9 +42,23 @@ use ring::digest::{Context, Digest, SHA256};
 use actix_easy_multipart::tempfile::Tempfile;
 use actix_easy_multipart::MultipartForm;
 use actix_web::http::header::{Charset, ContentDisposition, DispositionParam, DispositionType, ExtendedValue};
-use actix_web::http::StatusCode;
+use actix_web::http::{header, StatusCode};
 use actix_web::web::Bytes;
 use crate::create_db::create_db;
+use crate::feeds::{feed_response, query_articles_feed, query_feed_token, query_feed_token_user, query_thread_feed, query_threads_feed, save_feed_token, Feed};
+use crate::webhooks::{delete_webhook, deliver_pending_webhooks, query_webhook_deliveries, query_webhooks, save_webhook};
+use crate::live_events::{article_event, thread_event, LiveEvent, LiveEventKind, LiveEvents, LiveEventsQuery};
+use crate::email::{build_smtp_transport, deliver_pending_emails, enqueue_notification_emails, enqueue_password_reset_email, is_unsubscribe_token_valid, query_email_settings, save_email_settings, unsubscribe_by_token};
+use crate::mentions::search_users;
+use crate::polls::{query_poll, save_poll_vote};
+use crate::reactions::{add_reaction, query_reaction_users, remove_reaction, ReactionTarget};
+use crate::drafts::{delete_draft, DraftContext, purge_expired_drafts, query_draft, query_drafts, save_draft};
+use crate::passwords::{check_password_strength, client_password_digest, consume_password_reset, create_password_reset, delete_password_reset, password_policy, password_reset_url, query_password_hash_report, query_password_reset_user, render_password_reset_page, request_password_reset};
+use crate::profiles::{query_profile, query_user_articles, query_user_comments, query_user_messages, query_user_threads, update_profile};
+use crate::scheduling::{publish_due_articles, publish_due_threads, query_scheduled_publications, scheduled_time, SCHEDULER_INTERVAL};
+use crate::tags::{merge_tags, parse_tags, query_tag_cloud, rename_tag, search_tags, set_thread_tags};
+use crate::notifications::{mark_notifications_read, query_notifications, query_subscriptions, query_unread_notification_count, subscribe, unsubscribe};
+use crate::settings::Settings;
 
 async fn index() -> Result<HttpResponse> {
     Ok(HttpResponse::build(StatusCode::OK)
@@ -105,13 +136,13 @@ async fn get_forum_structure(storage: web::Data<DbStorage>) -> Result<impl Respo
 }
 
 #[get("/api/threads")]
-async fn get_threads(params: web::Query<ThreadQuery>, storage: web::Data<DbStorage>) -> Result<impl Responder> {
+async fn get_threads(params: web::Query<ThreadQuery>, storage: web::Data<DbStorage>, principal: Option<CallerPrincipal>) -> Result<impl Responder> {
     let pool = storage.main_db_pool.clone();
 
     let threads: Result<ThreadQueryResult, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
         let conn = pool.get()?;
 
-        let threads = query_threads(&conn, &params)?;
+        let threads = query_threads(&conn, &params, &principal.map(|p| p.user))?;
         let count = query_thread_count(&conn, &params)?;
 
         return Ok(ThreadQueryResult{threads, count})
@@ -122,26 +153,389 @@ async fn get_threads(params: web::Query<ThreadQuery>, storage: web::Data<DbStora
 }
 
 #[get("/api/messages")]
-async fn get_messages(params: web::Query<MessagesQuery>, storage: web::Data<DbStorage>) -> Result<impl Responder> {
+async fn get_messages(params: web::Query<MessagesQuery>, storage: web::Data<DbStorage>, principal: Option<CallerPrincipal>) -> Result<impl Responder> {
     let pool = storage.main_db_pool.clone();
 
     let messages: Result<Option<MessagesQueryResult>, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
         let conn = pool.get()?;
 
+        let user = principal.as_ref().map(|p| p.user.clone());
+        if !is_thread_visible(&conn, params.thread_id, &user)? {
+            return Ok(None);
+        }
+
         let thread = query_thread(&conn, params.thread_id)?;
         if let None = thread {
             return Ok(None);
         }
 
-        let thread = thread.unwrap();
-        let messages = query_thread_messages(&conn, params.thread_id, params.page)?;
-        Ok(Some(MessagesQueryResult{thread, messages}))
+        let mut thread = thread.unwrap();
+        let messages = query_thread_messages(&conn, params.thread_id, params.page, &user)?;
+        let poll = query_poll(&conn, params.thread_id, &user)?;
+
+        if let Some(principal) = principal {
+            fill_unread_info(&conn, &mut thread, &principal.user)?;
+
+            if let Some(last) = messages.last() {
+                mark_thread_read(&conn, &principal.user, params.thread_id, Some(last.id))?;
+            }
+        }
+
+        Ok(Some(MessagesQueryResult{thread, messages, poll}))
     })
     .await?;
 
     Ok(web::Json(messages.map_err(error::ErrorInternalServerError)?))
 }
 
+#[get("/api/poll")]
+async fn get_poll(params: web::Query<PollQuery>, storage: web::Data<DbStorage>, principal: Option<CallerPrincipal>) -> Result<impl Responder> {
+    let res: Result<Poll, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let pool = storage.main_db_pool.clone();
+        let conn = pool.get()?;
+        let user = principal.map(|p| p.user);
+
+        check_thread_visible(&conn, params.thread_id, &user)?;
+        let poll = query_poll(&conn, params.thread_id, &user)?;
+        Ok(poll.ok_or(LogicError::PollNotFound)?)
+    })
+    .await?;
+
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
+}
+
+// Returns the poll with the results visible after voting
+#[post("/api/poll/vote")]
+async fn post_poll_vote(params: web::Json<PollVote>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
+    let res: Result<Poll, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let pool = storage.main_db_pool.clone();
+        let mut conn = pool.get()?;
+        save_poll_vote(&mut conn, params.thread_id, &params.option_ids, &caller.user)?;
+        let poll = query_poll(&conn, params.thread_id, &Some(caller.user))?;
+        Ok(poll.ok_or(LogicError::PollNotFound)?)
+    })
+    .await?;
+
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
+}
+
+#[post("/api/thread/mark-read")]
+async fn post_mark_thread_read(params: web::Json<MarkThreadRead>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let pool = storage.main_db_pool.clone();
+        let conn = pool.get()?;
+        check_thread_visible(&conn, params.thread_id, &Some(caller.user.clone()))?;
+        mark_thread_read(&conn, &caller.user, params.thread_id, params.message_id)?;
+        Ok(())
+    })
+    .await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[post("/api/threads/mark-read")]
+async fn post_mark_threads_read(params: web::Json<MarkThreadsRead>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let pool = storage.main_db_pool.clone();
+        mark_threads_read(&pool.get()?, &caller.user, &params)?;
+        Ok(())
+    })
+    .await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[post("/api/subscribe")]
+async fn post_subscribe(params: web::Json<Subscribe>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let pool = storage.main_db_pool.clone();
+        let conn = pool.get()?;
+        if matches!(params.target_type, SubscriptionTarget::Thread) {
+            check_thread_visible(&conn, params.target_id, &Some(caller.user.clone()))?;
+        }
+        subscribe(&conn, caller.user.id, params.target_type, params.target_id)?;
+        Ok(())
+    })
+    .await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[post("/api/unsubscribe")]
+async fn post_unsubscribe(params: web::Json<Subscribe>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let pool = storage.main_db_pool.clone();
+        unsubscribe(&pool.get()?, &caller.user, params.target_type, params.target_id)?;
+        Ok(())
+    })
+    .await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[get("/api/subscriptions")]
+async fn get_subscriptions(storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
+    let pool = storage.main_db_pool.clone();
+
+    let res: Result<Subscriptions, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let result = query_subscriptions(&pool.get()?, &caller.user)?;
+        Ok(result)
+    })
+    .await?;
+
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
+}
+
+#[get("/api/notifications")]
+async fn get_notifications(params: web::Query<NotificationsQuery>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
+    let pool = storage.main_db_pool.clone();
+
+    let res: Result<Notifications, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let result = query_notifications(&pool.get()?, &caller.user, params.page)?;
+        Ok(result)
+    })
+    .await?;
+
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
+}
+
+#[get("/api/notifications/count")]
+async fn get_notification_count(storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
+    let pool = storage.main_db_pool.clone();
+
+    let res: Result<NotificationCount, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let result = query_unread_notification_count(&pool.get()?, &caller.user)?;
+        Ok(result)
+    })
+    .await?;
+
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
+}
+
+#[post("/api/notifications/mark-read")]
+async fn post_mark_notifications_read(params: web::Json<MarkNotificationsRead>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let pool = storage.main_db_pool.clone();
+        mark_notifications_read(&mut pool.get()?, &caller.user, &params)?;
+        Ok(())
+    })
+    .await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[get("/api/events")]
+async fn get_live_events(params: web::Query<LiveEventsQuery>, live_events: web::Data<LiveEvents>, caller: CallerPrincipal) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .streaming(live_events.subscribe(params.into_inner(), caller.user))
+}
+
+#[get("/feed/threads.atom")]
+async fn get_threads_feed(req: HttpRequest, storage: web::Data<DbStorage>, settings: web::Data<Settings>) -> Result<HttpResponse> {
+    threads_feed(req, storage, settings, ThreadsQueryType::All, None).await
+}
+
+#[get("/feed/section/{id}.atom")]
+async fn get_section_feed(req: HttpRequest, id: web::Path<u32>, storage: web::Data<DbStorage>, settings: web::Data<Settings>) -> Result<HttpResponse> {
+    threads_feed(req, storage, settings, ThreadsQueryType::Section, Some(id.into_inner())).await
+}
+
+#[get("/feed/subsection/{id}.atom")]
+async fn get_subsection_feed(req: HttpRequest, id: web::Path<u32>, storage: web::Data<DbStorage>, settings: web::Data<Settings>) -> Result<HttpResponse> {
+    threads_feed(req, storage, settings, ThreadsQueryType::SubSection, Some(id.into_inner())).await
+}
+
+async fn threads_feed(req: HttpRequest, storage: web::Data<DbStorage>, settings: web::Data<Settings>, query_type: ThreadsQueryType, id: Option<u32>) -> Result<HttpResponse> {
+    let pool = storage.main_db_pool.clone();
+
+    let feed: Result<Feed, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        query_threads_feed(&pool.get()?, query_type, id, &settings.base_url)
+    })
+    .await?;
+
+    Ok(feed_response(&req, &feed.map_err(error::ErrorInternalServerError)?))
+}
+
+#[get("/feed/thread/{id}.atom")]
+async fn get_thread_feed(req: HttpRequest, id: web::Path<u32>, storage: web::Data<DbStorage>, settings: web::Data<Settings>) -> Result<HttpResponse> {
+    let pool = storage.main_db_pool.clone();
+
+    let feed: Result<Feed, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        query_thread_feed(&pool.get()?, id.into_inner(), &settings.base_url)
+    })
+    .await?;
+
+    Ok(feed_response(&req, &feed.map_err(error::ErrorInternalServerError)?))
+}
+
+#[get("/feed/articles.atom")]
+async fn get_articles_feed(req: HttpRequest, params: web::Query<FeedTokenQuery>, storage: web::Data<DbStorage>, settings: web::Data<Settings>) -> Result<HttpResponse> {
+    let pool = storage.main_db_pool.clone();
+
+    let feed: Result<Feed, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let conn = pool.get()?;
+
+        let user = match &params.token {
+            Some(token) => Some(query_feed_token_user(&conn, token)?.ok_or(LogicError::AccessDenied)?),
+            None => None
+        };
+
+        query_articles_feed(&conn, &user, &settings.base_url)
+    })
+    .await?;
+
+    Ok(feed_response(&req, &feed.map_err(error::ErrorInternalServerError)?))
+}
+
+#[get("/api/feed-token")]
+async fn get_feed_token(storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
+    let pool = storage.main_db_pool.clone();
+
+    let res: Result<FeedToken, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        query_feed_token(&pool.get()?, &caller.user)
+    })
+    .await?;
+
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
+}
+
+// Creates the token if the user has none, otherwise returns the existing one
+#[post("/api/feed-token")]
+async fn post_create_feed_token(storage: web::Data<DbStorage>, rng: web::Data<RandomGenerator>, caller: CallerPrincipal) -> Result<impl Responder> {
+    feed_token(storage, rng, caller, false).await
+}
+
+#[post("/api/feed-token/reset")]
+async fn post_reset_feed_token(storage: web::Data<DbStorage>, rng: web::Data<RandomGenerator>, caller: CallerPrincipal) -> Result<impl Responder> {
+    feed_token(storage, rng, caller, true).await
+}
+
+async fn feed_token(storage: web::Data<DbStorage>, rng: web::Data<RandomGenerator>, caller: CallerPrincipal, reset: bool) -> Result<web::Json<FeedToken>> {
+    let new_token: String = {
+        let mut rng = rng.rng.lock().unwrap();
+        (&mut *rng).sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect()
+    };
+
+    let pool = storage.main_db_pool.clone();
+
+    let res: Result<FeedToken, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        save_feed_token(&pool.get()?, &caller.user, &new_token, reset)
+    })
+    .await?;
+
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
+}
+
+#[get("/api/email-settings")]
+async fn get_email_settings(storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
+    let pool = storage.main_db_pool.clone();
+
+    let res: Result<EmailSettings, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let result = query_email_settings(&pool.get()?, &caller.user)?;
+        Ok(result)
+    })
+    .await?;
+
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
+}
+
+#[post("/api/email-settings")]
+async fn post_email_settings(params: web::Json<SaveEmailSettings>, storage: web::Data<DbStorage>, rng: web::Data<RandomGenerator>, caller: CallerPrincipal) -> Result<impl Responder> {
+    let unsubscribe_token: String = {
+        let mut rng = rng.rng.lock().unwrap();
+        (&mut *rng).sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect()
+    };
+
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let pool = storage.main_db_pool.clone();
+        save_email_settings(&pool.get()?, &caller.user, &params, &unsubscribe_token)?;
+        Ok(())
+    })
+    .await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+// Opened from the link in an email. Only asks for confirmation, so that mail scanners
+// and link prefetchers following the link don't unsubscribe the user.
+async fn unsubscribe_page(token: web::Path<String>, storage: web::Data<DbStorage>) -> Result<HttpResponse> {
+    let pool = storage.main_db_pool.clone();
+
+    let res: Result<bool, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        is_unsubscribe_token_valid(&pool.get()?, &token)
+    })
+    .await?;
+
+    if res.map_err(error::ErrorInternalServerError)? {
+        Ok(HttpResponse::Ok()
+            .content_type("text/html; charset=utf-8")
+            .body("<!DOCTYPE html><html><body><p>Unsubscribe from email notifications?</p><form method=\"post\"><button type=\"submit\">Unsubscribe</button></form><p><a href=\"/\">The Ark</a></p></body></html>"))
+    } else {
+        Ok(HttpResponse::NotFound().finish())
+    }
+}
+
+// Posted from the confirmation page or by a mail client (RFC 8058 one-click unsubscribe)
+async fn unsubscribe_email(token: web::Path<String>, storage: web::Data<DbStorage>) -> Result<HttpResponse> {
+    let pool = storage.main_db_pool.clone();
+
+    let res: Result<bool, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        unsubscribe_by_token(&pool.get()?, &token)
+    })
+    .await?;
+
+    if res.map_err(error::ErrorInternalServerError)? {
+        Ok(HttpResponse::Ok()
+            .content_type("text/html; charset=utf-8")
+            .body("<!DOCTYPE html><html><body><p>You have been unsubscribed from email notifications.</p><p><a href=\"/\">The Ark</a></p></body></html>"))
+    } else {
+        Ok(HttpResponse::NotFound().finish())
+    }
+}
+
+async fn run_email_delivery(pool: Pool<SqliteConnectionManager>, settings: Settings, transport: SmtpTransport) {
+    let mut interval = actix_web::rt::time::interval(Duration::from_secs(60));
+
+    loop {
+        interval.tick().await;
+
+        let pool = pool.clone();
+        let settings = settings.clone();
+        let transport = transport.clone();
+        let res: Result<QueryResult<(usize, usize, usize)>, _> = web::block(move || {
+            let queued = enqueue_notification_emails(&mut pool.get()?, &settings.base_url)?;
+            let (sent, failed) = deliver_pending_emails(&pool.get()?, &transport, &settings.email_from, settings.email_max_attempts)?;
+            Ok((queued, sent, failed))
+        }).await;
+
+        match res {
+            Ok(Ok((queued, sent, failed))) if queued + sent + failed > 0 => println!("Emails queued: {}, sent: {}, failed: {}", queued, sent, failed),
+            Ok(Err(e)) => eprintln!("Email delivery error: {}", e),
+            _ => {}
+        }
+    }
+}
+
 #[get("/api/comments")]
 async fn get_article_comments(params: web::Query<CommentsQuery>, storage: web::Data<DbStorage>, principal: Option<CallerPrincipal>) -> Result<impl Responder> {
     let pool = storage.main_db_pool.clone();
@@ -174,6 +568,7 @@ async fn get_search_messages(params: web::Query<MessageSearchParams>, storage: w
 #[post("/signon")]
 async fn signon(params: web::Json<SignOnParams>,
                 storage: web::Data<DbStorage>,
+                settings: web::Data<Settings>,
                 rng: web::Data<RandomGenerator>) -> Result<impl Responder> {
     let pool = storage.main_db_pool.clone();
     let pass_pool = storage.user_passwords_db_pool.clone();
@@ -198,7 +593,7 @@ async fn signon(params: web::Json<SignOnParams>,
         }
 
         let user = user.unwrap();
-        let verified = verify_user_password(&pass_pool.get()?, &user, &params.password)?;
+        let verified = verify_user_password(&pass_pool.get()?, &user, &params.password, &settings.argon2_params)?;
 
         if !verified {
             return Ok(None);
@@ -224,12 +619,15 @@ async fn signon(params: web::Json<SignOnParams>,
 
 #[post("/signup")]
 async fn signup(params: web::Json<SignUpParams>,
-                storage: web::Data<DbStorage>) -> Result<impl Responder> {
+                storage: web::Data<DbStorage>,
+                settings: web::Data<Settings>) -> Result<impl Responder> {
 
     let pool = storage.main_db_pool.clone();
     let pass_pool = storage.user_passwords_db_pool.clone();
 
     let result: Result<bool, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        check_password_strength(&params.password, &params.user_name, &settings)?;
+
         let conn = &pool.get()?;
         let user = find_user(&conn, &params.user_name)?;
 
@@ -245,7 +643,7 @@ async fn signup(params: web::Json<SignUpParams>,
         }
 
         let user = user.unwrap();
-        save_user_password(&pass_pool.get()?, &user, &params.password)?;
+        save_user_password(&pass_pool.get()?, &user, &client_password_digest(&params.password), &settings.argon2_params)?;
 
         return Ok(true);
     }).await?;
@@ -259,44 +657,268 @@ async fn signup(params: web::Json<SignUpParams>,
     Ok(HttpResponse::Ok().body("\"OK\""))
 }
 
-fn get_user_session(req: &HttpRequest) -> Option<String> {
-    let session = req.cookie("SESSION");
-    if session.is_none() {
-        return None;
-    }
-
-    Some(session.unwrap().value().to_string())
+#[get("/api/password/policy")]
+async fn get_password_policy(settings: web::Data<Settings>) -> Result<impl Responder> {
+    Ok(web::Json(password_policy(&settings)))
 }
 
-#[get("/api/current-user")]
-async fn get_current_user(caller: CallerPrincipal) -> Result<impl Responder> {
-    Ok(web::Json(caller.user))
-}
+// Other sessions of the user are signed out
+#[post("/api/password/change")]
+async fn post_change_password(req: HttpRequest, params: web::Json<ChangePassword>, storage: web::Data<DbStorage>, settings: web::Data<Settings>, caller: CallerPrincipal) -> Result<impl Responder> {
+    let session = get_user_session(&req).ok_or(error::ErrorInternalServerError(LogicError::NoSession))?;
 
-#[get("/api/current-user-sessions")]
-async fn get_user_sessions(req: HttpRequest, storage: web::Data<DbStorage>) -> Result<impl Responder> {
-    let session = get_user_session(&req);
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let pass_conn = storage.user_passwords_db_pool.get()?;
+        let hash_params = settings.argon2_params.clone();
 
-    let res: Result<UserSessions, Box<dyn std::error::Error + Send + Sync>>;
+        if !verify_user_password(&pass_conn, &caller.user, &client_password_digest(&params.current_password), &hash_params)? {
+            return Err(LogicError::WrongPassword.into());
+        }
 
-    if session.is_none() {
-        res = Ok(UserSessions{sessions: Vec::new()});
-    }
-    else {
-        res = web::block(move || {
-            let pool = storage.user_sessions_db_pool.clone();
-            let s = query_sessions(&mut pool.get()?, &session.unwrap())?;
-            Ok(s)
-        })
-        .await?;
-    }
+        check_password_strength(&params.new_password, &caller.user.name, &settings)?;
+        save_user_password(&pass_conn, &caller.user, &client_password_digest(&params.new_password), &hash_params)?;
+        remove_other_sessions(&storage.user_sessions_db_pool.get()?, &session)?;
+        Ok(())
+    })
+    .await?;
 
-    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
-}
+    res.map_err(error::ErrorInternalServerError)?;
 
-#[post("/api/current-user-sessions/remove")]
-async fn post_remove_user_session(req: HttpRequest, storage: web::Data<DbStorage>, params: web::Json<RemoveSession>) -> Result<impl Responder> {
-    let session = get_user_session(&req);
+    Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+// The link is returned to the admin and also emailed when the user has an email
+#[post("/api/admin/password-reset")]
+async fn post_create_password_reset(params: web::Json<CreatePasswordReset>, storage: web::Data<DbStorage>, settings: web::Data<Settings>, rng: web::Data<RandomGenerator>, caller: CallerPrincipal) -> Result<impl Responder> {
+    let token: String = {
+        let mut rng = rng.rng.lock().unwrap();
+        (&mut *rng).sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect()
+    };
+
+    let res: Result<PasswordResetLink, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        if !caller.admin {
+            return Err(LogicError::AccessDenied.into());
+        }
+
+        let conn = storage.main_db_pool.get()?;
+        let expire_time = create_password_reset(&conn, params.user_id, &token, settings.password_reset_hours)?;
+        let url = password_reset_url(&settings.base_url, &token);
+
+        let user = query_password_reset_user(&conn, &token)?.ok_or(LogicError::UserNotFound)?;
+        let emailed = settings.smtp_host.is_some()
+            && enqueue_password_reset_email(&conn, &user, &url, settings.password_reset_hours)?;
+
+        Ok(PasswordResetLink{url, expire_time, emailed})
+    })
+    .await?;
+
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
+}
+
+#[get("/api/admin/password-hashes")]
+async fn get_password_hash_report(storage: web::Data<DbStorage>, settings: web::Data<Settings>, caller: CallerPrincipal) -> Result<impl Responder> {
+    let res: Result<PasswordHashReport, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        if !caller.admin {
+            return Err(LogicError::AccessDenied.into());
+        }
+
+        let result = query_password_hash_report(&*storage.user_passwords_db_pool.get()?, &settings.argon2_params)?;
+        Ok(result)
+    })
+    .await?;
+
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
+}
+
+// Always answers OK so that the form can't be used to find out user names or emails
+#[post("/password-reset")]
+async fn post_request_password_reset(params: web::Json<RequestPasswordReset>, storage: web::Data<DbStorage>, settings: web::Data<Settings>, rng: web::Data<RandomGenerator>) -> Result<impl Responder> {
+    if settings.smtp_host.is_none() {
+        return Ok(HttpResponse::Ok().body("\"OK\""));
+    }
+
+    let token: String = {
+        let mut rng = rng.rng.lock().unwrap();
+        (&mut *rng).sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect()
+    };
+
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let conn = storage.main_db_pool.get()?;
+
+        if let Some(user) = find_user(&conn, params.user_name.trim())? {
+            if request_password_reset(&conn, user.id, &token, settings.password_reset_hours, settings.password_reset_cooldown_minutes)? {
+                let url = password_reset_url(&settings.base_url, &token);
+                if !enqueue_password_reset_email(&conn, &user, &url, settings.password_reset_hours)? {
+                    delete_password_reset(&conn, &token)?;
+                }
+            }
+        }
+
+        Ok(())
+    })
+    .await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+// Opened from the reset link
+async fn password_reset_page(token: web::Path<String>, storage: web::Data<DbStorage>) -> Result<HttpResponse> {
+    let pool = storage.main_db_pool.clone();
+
+    let res: Result<Option<User>, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        query_password_reset_user(&*pool.get()?, &token)
+    })
+    .await?;
+
+    match res.map_err(error::ErrorInternalServerError)? {
+        Some(user) => Ok(HttpResponse::Ok()
+            .content_type("text/html; charset=utf-8")
+            .body(render_password_reset_page(&user, None))),
+        None => Ok(HttpResponse::NotFound().finish())
+    }
+}
+
+// Sets the new password from the reset form, all sessions of the user are signed out
+async fn reset_password(token: web::Path<String>, form: web::Form<ResetPasswordForm>, storage: web::Data<DbStorage>, settings: web::Data<Settings>) -> Result<HttpResponse> {
+    let res: Result<Result<(), (User, String)>, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let conn = storage.main_db_pool.get()?;
+
+        let user = match query_password_reset_user(&conn, &token)? {
+            Some(user) => user,
+            None => return Err(LogicError::AccessDenied.into()),
+        };
+
+        if form.password != form.confirmation {
+            return Ok(Err((user, "Passwords do not match".to_string())));
+        }
+
+        if let Err(err) = check_password_strength(&form.password, &user.name, &settings) {
+            return Ok(Err((user, err.to_string())));
+        }
+
+        let user = consume_password_reset(&conn, &token)?.ok_or(LogicError::AccessDenied)?;
+        save_user_password(&storage.user_passwords_db_pool.get()?, &user, &client_password_digest(&form.password), &settings.argon2_params)?;
+        remove_user_sessions(&storage.user_sessions_db_pool.get()?, user.id)?;
+
+        Ok(Ok(()))
+    })
+    .await?;
+
+    match res {
+        Ok(Ok(())) => Ok(HttpResponse::Ok()
+            .content_type("text/html; charset=utf-8")
+            .body("<!DOCTYPE html><html><body><p>Your password has been changed.</p><p><a href=\"/\">The Ark</a></p></body></html>")),
+        Ok(Err((user, error))) => Ok(HttpResponse::Ok()
+            .content_type("text/html; charset=utf-8")
+            .body(render_password_reset_page(&user, Some(&error)))),
+        Err(_) => Ok(HttpResponse::NotFound().finish())
+    }
+}
+
+fn get_user_session(req: &HttpRequest) -> Option<String> {
+    let session = req.cookie("SESSION");
+    if session.is_none() {
+        return None;
+    }
+
+    Some(session.unwrap().value().to_string())
+}
+
+#[get("/api/current-user")]
+async fn get_current_user(caller: CallerPrincipal) -> Result<impl Responder> {
+    Ok(web::Json(caller.user))
+}
+
+#[get("/api/users/search")]
+async fn get_search_users(params: web::Query<UserSearchQuery>, storage: web::Data<DbStorage>) -> Result<impl Responder> {
+    let pool = storage.main_db_pool.clone();
+
+    let res: Result<Users, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let result = search_users(&*pool.get()?, &params.prefix)?;
+        Ok(result)
+    })
+    .await?;
+
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
+}
+
+#[get("/api/reactions/allowed")]
+async fn get_allowed_reactions(settings: web::Data<Settings>) -> Result<impl Responder> {
+    Ok(web::Json(AllowedReactions{reactions: settings.reactions.clone()}))
+}
+
+#[post("/api/reaction")]
+async fn post_add_reaction(params: web::Json<ReactionParams>, storage: web::Data<DbStorage>, settings: web::Data<Settings>, caller: CallerPrincipal) -> Result<impl Responder> {
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let pool = storage.main_db_pool.clone();
+        add_reaction(&*pool.get()?, ReactionTarget::from_params(&params)?, &params.emoji, &settings.reactions, &caller.user)?;
+        Ok(())
+    })
+    .await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[post("/api/reaction/remove")]
+async fn post_remove_reaction(params: web::Json<ReactionParams>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let pool = storage.main_db_pool.clone();
+        remove_reaction(&*pool.get()?, ReactionTarget::from_params(&params)?, &params.emoji, &caller.user)?;
+        Ok(())
+    })
+    .await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[get("/api/reaction/users")]
+async fn get_reaction_users(params: web::Query<ReactionParams>, storage: web::Data<DbStorage>, principal: Option<CallerPrincipal>) -> Result<impl Responder> {
+    let res: Result<Users, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let pool = storage.main_db_pool.clone();
+        let result = query_reaction_users(&*pool.get()?, ReactionTarget::from_params(&params)?, &params.emoji, &principal.map(|p| p.user))?;
+        Ok(result)
+    })
+    .await?;
+
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
+}
+
+#[get("/api/current-user-sessions")]
+async fn get_user_sessions(req: HttpRequest, storage: web::Data<DbStorage>) -> Result<impl Responder> {
+    let session = get_user_session(&req);
+
+    let res: Result<UserSessions, Box<dyn std::error::Error + Send + Sync>>;
+
+    if session.is_none() {
+        res = Ok(UserSessions{sessions: Vec::new()});
+    }
+    else {
+        res = web::block(move || {
+            let pool = storage.user_sessions_db_pool.clone();
+            let s = query_sessions(&mut pool.get()?, &session.unwrap())?;
+            Ok(s)
+        })
+        .await?;
+    }
+
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
+}
+
+#[post("/api/current-user-sessions/remove")]
+async fn post_remove_user_session(req: HttpRequest, storage: web::Data<DbStorage>, params: web::Json<RemoveSession>) -> Result<impl Responder> {
+    let session = get_user_session(&req);
 
     let res: Result<UserSessions, Box<dyn std::error::Error + Send + Sync>>;
 
@@ -361,19 +983,44 @@ async fn post_logout(req: HttpRequest, storage: web::Data<DbStorage>, params: we
     Ok(result)
 }
 
+// Events are best-effort: the change is already saved, so a failure is only logged
+async fn publish_live_event<F>(storage: &DbStorage, live_events: &LiveEvents, build_event: F)
+    where F: FnOnce(&Connection) -> QueryResult<LiveEvent> + Send + 'static {
+    let pool = storage.main_db_pool.clone();
+
+    let res = web::block(move || -> QueryResult<LiveEvent> {
+        build_event(&*pool.get()?)
+    }).await;
+
+    match res {
+        Ok(Ok(event)) => live_events.publish(event),
+        Ok(Err(e)) => eprintln!("Live event error: {}", e),
+        Err(e) => eprintln!("Live event error: {}", e),
+    }
+}
+
 #[post("/api/thread")]
 async fn post_thread(params: web::Json<CreateThreadMessage>,
                       storage: web::Data<DbStorage>,
+                      live_events: web::Data<LiveEvents>,
                       caller: CallerPrincipal) -> Result<impl Responder> {
 
-    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
-        let pool = storage.main_db_pool.clone();
-        create_thread(&mut pool.get()?, &params, &caller.user)?;
-        Ok(())
+    let user = caller.user.clone();
+    // the event of a scheduled thread is sent when it is published
+    let scheduled = scheduled_time(params.publish_at).is_some();
+
+    let pool = storage.main_db_pool.clone();
+    let res: Result<u32, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let mut conn = pool.get()?;
+        create_thread(&mut conn, &params, &caller.user)
     })
     .await?;
 
-    res.map_err(error::ErrorInternalServerError)?;
+    let thread_id = res.map_err(error::ErrorInternalServerError)?;
+
+    if !scheduled {
+        publish_live_event(&storage, &live_events, move |conn| thread_event(conn, LiveEventKind::Thread, thread_id, Some(1), &user)).await;
+    }
 
     return Ok(HttpResponse::Ok().body("\"OK\""))
 }
@@ -381,288 +1028,1124 @@ async fn post_thread(params: web::Json<CreateThreadMessage>,
 #[post("/api/message")]
 async fn post_message(params: web::Json<PostMessage>,
                     storage: web::Data<DbStorage>,
+                    live_events: web::Data<LiveEvents>,
                     caller: CallerPrincipal) -> Result<impl Responder> {
 
+    let user = caller.user.clone();
+    let thread_id = params.thread_id;
+
+    let pool = storage.main_db_pool.clone();
+    let res: Result<u32, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let mut conn = pool.get()?;
+        save_message(&mut conn, &params, &caller.user, caller.admin)
+    })
+    .await?;
+
+    let message_id = res.map_err(error::ErrorInternalServerError)?;
+    publish_live_event(&storage, &live_events, move |conn| thread_event(conn, LiveEventKind::Message, thread_id, Some(message_id), &user)).await;
+
+    return Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[post("/api/comment")]
+async fn post_comment(params: web::Json<PostComment>,
+                      storage: web::Data<DbStorage>,
+                      live_events: web::Data<LiveEvents>,
+                      caller: CallerPrincipal) -> Result<impl Responder> {
+
+    let user = caller.user.clone();
+    let article_id = params.article_id;
+
+    let pool = storage.main_db_pool.clone();
+    let res: Result<u32, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let mut conn = pool.get()?;
+        save_comment(&mut conn, &params, &caller.user)
+    })
+        .await?;
+
+    let comment_id = res.map_err(error::ErrorInternalServerError)?;
+    publish_live_event(&storage, &live_events, move |conn| article_event(conn, LiveEventKind::Comment, article_id, Some(comment_id), &user)).await;
+
+    return Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[post("/api/message/update")]
+async fn post_update_message(params: web::Json<UpdateMessage>,
+                             storage: web::Data<DbStorage>,
+                             live_events: web::Data<LiveEvents>,
+                             caller: CallerPrincipal) -> Result<impl Responder> {
+    let user = caller.user.clone();
+    let (thread_id, message_id) = (params.thread_id, params.message_id);
+
+    let pool = storage.main_db_pool.clone();
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let mut conn = pool.get()?;
+        update_message(&mut conn, &params, &caller.user)?;
+        Ok(())
+    })
+    .await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+    publish_live_event(&storage, &live_events, move |conn| thread_event(conn, LiveEventKind::MessageEdited, thread_id, Some(message_id), &user)).await;
+
+    return Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[post("/api/comment/update")]
+async fn post_update_article_comment(params: web::Json<UpdateComment>,
+                             storage: web::Data<DbStorage>,
+                             live_events: web::Data<LiveEvents>,
+                             caller: CallerPrincipal) -> Result<impl Responder> {
+    let user = caller.user.clone();
+    let (article_id, comment_id) = (params.article_id, params.comment_id);
+
+    let pool = storage.main_db_pool.clone();
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let mut conn = pool.get()?;
+        update_comment(&mut conn, &params, &caller.user)?;
+        Ok(())
+    })
+        .await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+    publish_live_event(&storage, &live_events, move |conn| article_event(conn, LiveEventKind::CommentEdited, article_id, Some(comment_id), &user)).await;
+
+    return Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[post("/api/section")]
+async fn post_new_section(params: web::Json<PostNewSection>, storage: web::Data<DbStorage>) -> Result<impl Responder>  {
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let pool = storage.main_db_pool.clone();
+        create_section(&mut pool.get()?, &params.name)?;
+        Ok(())
+    })
+    .await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+
+    return Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[post("/api/subsection")]
+async fn post_new_subsection(params: web::Json<PostNewSubsection>, storage: web::Data<DbStorage>) -> Result<impl Responder>  {
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let pool = storage.main_db_pool.clone();
+        create_sub_section(&mut pool.get()?, params.section_id, &params.name, params.description.as_deref().unwrap_or(""))?;
+        Ok(())
+    })
+    .await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+
+    return Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[post("/api/section/rename")]
+async fn post_rename_section(params: web::Json<RenameSection>, storage: web::Data<DbStorage>) -> Result<impl Responder>  {
     let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
         let pool = storage.main_db_pool.clone();
-        save_message(&mut pool.get()?, &params, &caller.user)?;
+        rename_section(&mut pool.get()?, params.section_id, &params.name)?;
         Ok(())
     })
     .await?;
 
     res.map_err(error::ErrorInternalServerError)?;
 
-    return Ok(HttpResponse::Ok().body("\"OK\""))
+    return Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[post("/api/subsection/rename")]
+async fn post_rename_subsection(params: web::Json<RenameSubsection>, storage: web::Data<DbStorage>) -> Result<impl Responder>  {
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let pool = storage.main_db_pool.clone();
+        rename_subsection(&mut pool.get()?, params.subsection_id, &params.name)?;
+        Ok(())
+    })
+    .await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+
+    return Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[post("/api/subsection/move")]
+async fn post_move_subsection(params: web::Json<MoveSubsection>, storage: web::Data<DbStorage>) -> Result<impl Responder>  {
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let pool = storage.main_db_pool.clone();
+        move_subsection(&mut pool.get()?, params.subsection_id, params.new_section_id)?;
+        Ok(())
+    })
+    .await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+
+    return Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[post("/api/subsection/description")]
+async fn post_update_subsection_description(params: web::Json<UpdateSubsectionDescription>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder>  {
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        if !caller.admin {
+            return Err(LogicError::AccessDenied.into());
+        }
+
+        let pool = storage.main_db_pool.clone();
+        update_subsection_description(&pool.get()?, params.subsection_id, &params.description)?;
+        Ok(())
+    })
+    .await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[post("/api/section/delete")]
+async fn post_delete_section(params: web::Json<DeleteSection>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder>  {
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        if !caller.admin {
+            return Err(LogicError::AccessDenied.into());
+        }
+
+        let pool = storage.main_db_pool.clone();
+        delete_section(&mut pool.get()?, &params)?;
+        Ok(())
+    })
+    .await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[post("/api/subsection/delete")]
+async fn post_delete_subsection(params: web::Json<DeleteSubsection>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder>  {
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        if !caller.admin {
+            return Err(LogicError::AccessDenied.into());
+        }
+
+        let pool = storage.main_db_pool.clone();
+        delete_subsection(&mut pool.get()?, &params)?;
+        Ok(())
+    })
+    .await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[post("/api/section/restore")]
+async fn post_restore_section(params: web::Json<RestoreSection>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder>  {
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        if !caller.admin {
+            return Err(LogicError::AccessDenied.into());
+        }
+
+        let pool = storage.main_db_pool.clone();
+        restore_section(&mut pool.get()?, params.section_id)?;
+        Ok(())
+    })
+    .await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[post("/api/subsection/restore")]
+async fn post_restore_subsection(params: web::Json<RestoreSubsection>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder>  {
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        if !caller.admin {
+            return Err(LogicError::AccessDenied.into());
+        }
+
+        let pool = storage.main_db_pool.clone();
+        restore_subsection(&mut pool.get()?, params.subsection_id)?;
+        Ok(())
+    })
+    .await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[post("/api/section/order")]
+async fn post_reorder_sections(params: web::Json<ReorderSections>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder>  {
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        if !caller.admin {
+            return Err(LogicError::AccessDenied.into());
+        }
+
+        let pool = storage.main_db_pool.clone();
+        reorder_sections(&mut pool.get()?, &params.section_ids)?;
+        Ok(())
+    })
+    .await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[post("/api/subsection/order")]
+async fn post_reorder_subsections(params: web::Json<ReorderSubsections>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder>  {
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        if !caller.admin {
+            return Err(LogicError::AccessDenied.into());
+        }
+
+        let pool = storage.main_db_pool.clone();
+        reorder_subsections(&mut pool.get()?, &params.subsection_ids)?;
+        Ok(())
+    })
+    .await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[get("/api/sections/deleted")]
+async fn get_deleted_sections(storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
+    let res: Result<DeletedSections, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        if !caller.admin {
+            return Err(LogicError::AccessDenied.into());
+        }
+
+        let pool = storage.main_db_pool.clone();
+        let result = query_deleted_sections(&pool.get()?)?;
+        Ok(result)
+    })
+    .await?;
+
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
+}
+
+#[post("/api/thread/rename")]
+async fn post_rename_thread(params: web::Json<RenameThread>, storage: web::Data<DbStorage>) -> Result<impl Responder>  {
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let pool = storage.main_db_pool.clone();
+        rename_thread(&mut pool.get()?, params.thread_id, &params.name)?;
+        Ok(())
+    })
+    .await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+
+    return Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[post("/api/thread/move")]
+async fn post_move_thread(params: web::Json<MoveThread>, storage: web::Data<DbStorage>) -> Result<impl Responder>  {
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let pool = storage.main_db_pool.clone();
+        move_thread(&mut pool.get()?, params.thread_id, params.new_subsection_id)?;
+        Ok(())
+    })
+        .await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+
+    return Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[post("/api/thread/delete")]
+async fn post_delete_thread(params: web::Json<DeleteThread>, storage: web::Data<DbStorage>) -> Result<impl Responder>  {
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let pool = storage.main_db_pool.clone();
+        delete_thread(&mut pool.get()?, params.thread_id)?;
+        Ok(())
+    })
+    .await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+
+    return Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[post("/api/thread/flags")]
+async fn post_update_thread_flags(params: web::Json<UpdateThreadFlags>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder>  {
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        if !caller.admin {
+            return Err(LogicError::AccessDenied.into());
+        }
+
+        let pool = storage.main_db_pool.clone();
+        update_thread_flags(&pool.get()?, &params)?;
+        Ok(())
+    })
+    .await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+// Thread tags can be changed by the author and admins
+#[post("/api/thread/tags")]
+async fn post_thread_tags(params: web::Json<SetThreadTags>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder>  {
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let pool = storage.main_db_pool.clone();
+        let mut conn = pool.get()?;
+
+        check_thread_visible(&conn, params.thread_id, &Some(caller.user.clone()))?;
+        let thread = query_thread(&conn, params.thread_id)?.ok_or(LogicError::ThreadNotFound)?;
+        if thread.author.id != caller.user.id && !caller.admin {
+            return Err(LogicError::AccessDenied.into());
+        }
+
+        let transaction = conn.transaction()?;
+        set_thread_tags(&transaction, params.thread_id, &params.tags)?;
+        transaction.commit()?;
+        Ok(())
+    })
+    .await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[get("/api/tags")]
+async fn get_tags(storage: web::Data<DbStorage>, principal: Option<CallerPrincipal>) -> Result<impl Responder> {
+    let pool = storage.main_db_pool.clone();
+
+    let res: Result<Tags, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let result = query_tag_cloud(&*pool.get()?, &principal.map(|p| p.user))?;
+        Ok(result)
+    })
+    .await?;
+
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
+}
+
+#[get("/api/tags/search")]
+async fn get_tags_search(params: web::Query<TagSearchQuery>, storage: web::Data<DbStorage>, principal: Option<CallerPrincipal>) -> Result<impl Responder> {
+    let pool = storage.main_db_pool.clone();
+
+    let res: Result<Tags, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let result = search_tags(&*pool.get()?, &params.prefix, &principal.map(|p| p.user))?;
+        Ok(result)
+    })
+    .await?;
+
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
+}
+
+#[post("/api/tag/rename")]
+async fn post_rename_tag(params: web::Json<RenameTag>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder>  {
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        if !caller.admin {
+            return Err(LogicError::AccessDenied.into());
+        }
+
+        let pool = storage.main_db_pool.clone();
+        rename_tag(&*pool.get()?, &params.name, &params.new_name)?;
+        Ok(())
+    })
+    .await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[post("/api/tag/merge")]
+async fn post_merge_tags(params: web::Json<MergeTags>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder>  {
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        if !caller.admin {
+            return Err(LogicError::AccessDenied.into());
+        }
+
+        let pool = storage.main_db_pool.clone();
+        let mut conn = pool.get()?;
+        let transaction = conn.transaction()?;
+        merge_tags(&transaction, &params.tags, &params.into)?;
+        transaction.commit()?;
+        Ok(())
+    })
+    .await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[post("/api/thread/merge")]
+async fn post_merge_threads(params: web::Json<MergeThreads>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder>  {
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        if !caller.admin {
+            return Err(LogicError::AccessDenied.into());
+        }
+
+        let pool = storage.main_db_pool.clone();
+        merge_threads(&mut pool.get()?, &params)?;
+        Ok(())
+    })
+    .await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[post("/api/thread/split")]
+async fn post_split_thread(params: web::Json<SplitThread>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder>  {
+    let res: Result<SplitThreadResult, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        if !caller.admin {
+            return Err(LogicError::AccessDenied.into());
+        }
+
+        let pool = storage.main_db_pool.clone();
+        let thread_id = split_thread(&mut pool.get()?, &params)?;
+        Ok(SplitThreadResult{thread_id})
+    })
+    .await?;
+
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
+}
+
+#[get("/api/message/replies")]
+async fn get_message_replies(params: web::Query<MessageRepliesQuery>, storage: web::Data<DbStorage>, principal: Option<CallerPrincipal>) -> Result<impl Responder> {
+    let res: Result<MessageReplies, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let pool = storage.main_db_pool.clone();
+        let result = query_message_replies(&pool.get()?, params.thread_id, params.message_id, &principal.map(|p| p.user))?;
+        Ok(result)
+    })
+    .await?;
+
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
+}
+
+#[get("/api/message/locate")]
+async fn get_message_location(params: web::Query<MessageLocateQuery>, storage: web::Data<DbStorage>, principal: Option<CallerPrincipal>) -> Result<impl Responder> {
+    let res: Result<MessageLocation, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let pool = storage.main_db_pool.clone();
+        let result = query_message_location(&pool.get()?, params.thread_id, params.message_id, &principal.map(|p| p.user))?;
+        Ok(result)
+    })
+    .await?;
+
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
+}
+
+#[get("/api/comment/locate")]
+async fn get_comment_location(params: web::Query<CommentLocateQuery>, storage: web::Data<DbStorage>, principal: Option<CallerPrincipal>) -> Result<impl Responder> {
+    let res: Result<CommentLocation, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let pool = storage.main_db_pool.clone();
+        let result = query_comment_location(&pool.get()?, params.article_id, params.comment_id, &principal.map(|p| p.user))?;
+        Ok(result)
+    })
+    .await?;
+
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
+}
+
+// Permalink of a message, stays valid when the message is moved or the page size changes
+async fn message_permalink(path: web::Path<(u32, u32)>, storage: web::Data<DbStorage>, principal: Option<CallerPrincipal>) -> Result<HttpResponse> {
+    let pool = storage.main_db_pool.clone();
+    let (thread_id, message_id) = path.into_inner();
+
+    let location: Result<MessageLocation, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        query_message_location(&pool.get()?, thread_id, Some(message_id), &principal.map(|p| p.user))
+    })
+    .await?;
+
+    match location {
+        Ok(location) => Ok(HttpResponse::Found()
+            .insert_header((header::LOCATION, message_location_path(&location)))
+            .finish()),
+        Err(_) => Ok(HttpResponse::NotFound().finish())
+    }
+}
+
+async fn comment_permalink(path: web::Path<(u32, u32)>, storage: web::Data<DbStorage>, principal: Option<CallerPrincipal>) -> Result<HttpResponse> {
+    let pool = storage.main_db_pool.clone();
+    let (article_id, comment_id) = path.into_inner();
+
+    let location: Result<CommentLocation, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        query_comment_location(&pool.get()?, article_id, comment_id, &principal.map(|p| p.user))
+    })
+    .await?;
+
+    match location {
+        Ok(location) => Ok(HttpResponse::Found()
+            .insert_header((header::LOCATION, comment_location_path(&location)))
+            .finish()),
+        Err(_) => Ok(HttpResponse::NotFound().finish())
+    }
+}
+
+#[derive(MultipartForm)]
+struct Upload {
+    #[multipart(rename="file_set[]")]
+    file_set: Vec<Tempfile>,
+}
+
+fn sha256_digest(file: &File) -> QueryResult<Digest> {
+    let mut reader = BufReader::new(file);
+
+    let mut context = Context::new(&SHA256);
+    let mut buffer = [0; 1024];
+
+    loop {
+        let count = reader.read(&mut buffer)?;
+        if count == 0 {
+            break;
+        }
+        context.update(&buffer[..count]);
+    }
+
+    Ok(context.finish())
+}
+
+fn store_uploaded_files(conn: &DbConnection, file_set: &[Tempfile], user: &User) -> QueryResult<Vec<UploadedFile>> {
+    let mut files = Vec::new();
+
+    for (i, file) in file_set.iter().enumerate() {
+        let input = File::open(file.file.path())?;
+        let digest = sha256_digest(&input)?;
+        let hex = HEXLOWER.encode(digest.as_ref());
+        let result_file_path = "files/".to_owned() + &hex;
+        std::fs::create_dir_all("files")?;
+        std::fs::copy(file.file.path(), &result_file_path)?;
+
+        let mime = if let Some(content_type) = &file.content_type {
+            content_type.as_ref().to_string()
+        }
+        else {
+            "application/octet-stream".to_string()
+        };
+
+        let original_file_name = if let Some(file_name) = &file.file_name {
+            file_name.to_string()
+        }
+        else {
+            "file".to_string()
+        };
+
+        let id = save_file_mapping(conn, &hex, &mime, &original_file_name, file.size as u64, user, i as u32)?;
+        files.push(UploadedFile {
+            id, file_name: original_file_name, mime
+        });
+    }
+
+    Ok(files)
+}
+
+#[post("/api/upload-files")]
+async fn post_upload(form: MultipartForm<Upload>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
+    let res: Result<UploadedFiles, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let pool = storage.main_db_pool.clone();
+        let files = store_uploaded_files(&pool.get()?, &form.file_set, &caller.user)?;
+
+        Ok(UploadedFiles{files})
+    })
+    .await?;
+
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
+}
+
+#[get("/api/article/attachments")]
+async fn get_article_attachments(params: web::Query<ArticleAttachmentsQuery>, storage: web::Data<DbStorage>, principal: Option<CallerPrincipal>) -> Result<impl Responder> {
+    let pool = storage.main_db_pool.clone();
+
+    let res: Result<ArticleAttachments, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let conn = pool.get()?;
+        let mut result = query_article_attachments(&conn, params.article_id, &principal.map(|p| p.user))?;
+
+        // Files uploaded before sizes were recorded
+        for attachment in result.attachments.iter_mut().filter(|a| a.size.is_none()) {
+            attachment.size = std::fs::metadata("files/".to_owned() + &attachment.file_path).ok().map(|m| m.len());
+        }
+
+        Ok(result)
+    })
+    .await?;
+
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
+}
+
+#[post("/api/article/attachments/upload")]
+async fn post_upload_article_attachments(params: web::Query<ArticleAttachmentsQuery>, form: MultipartForm<Upload>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
+    let res: Result<UploadedFiles, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let pool = storage.main_db_pool.clone();
+        let conn = pool.get()?;
+
+        query_article(&conn, params.article_id, None, &Some(caller.user.clone()))?;
+
+        let files = store_uploaded_files(&conn, &form.file_set, &caller.user)?;
+        for file in &files {
+            attach_article_file(&conn, params.article_id, &file.id, &caller.user)?;
+        }
+
+        Ok(UploadedFiles{files})
+    })
+    .await?;
+
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
+}
+
+#[post("/api/article/attachments/remove")]
+async fn post_remove_article_attachment(params: web::Json<RemoveArticleAttachment>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let pool = storage.main_db_pool.clone();
+        remove_article_attachment(&pool.get()?, params.article_id, &params.file_id, &caller.user)?;
+        Ok(())
+    }).await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[post("/api/article")]
+async fn post_create_article(params: web::Json<CreateArticle>, storage: web::Data<DbStorage>, live_events: web::Data<LiveEvents>, caller: CallerPrincipal) -> Result<impl Responder> {
+    let user = caller.user.clone();
+
+    let pool = storage.main_db_pool.clone();
+    let res: Result<u32, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let mut conn = pool.get()?;
+
+        let template = match params.template_id {
+            Some(id) => Some(query_article_template(&conn, id)?),
+            None => None
+        };
+
+        let article = apply_article_template(&params, template.as_ref(), &caller.user);
+        create_article(&mut conn, &article, &caller.user)
+    }).await?;
+
+    let id = res.map_err(error::ErrorInternalServerError)?;
+    publish_live_event(&storage, &live_events, move |conn| article_event(conn, LiveEventKind::Article, id, None, &user)).await;
+
+    return Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[get("/api/webhooks")]
+async fn get_webhooks(storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
+    let pool = storage.main_db_pool.clone();
+
+    let res: Result<Webhooks, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        if !caller.admin {
+            return Err(LogicError::AccessDenied.into());
+        }
+
+        let result = query_webhooks(&pool.get()?)?;
+        Ok(result)
+    })
+    .await?;
+
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
+}
+
+#[post("/api/webhook")]
+async fn post_save_webhook(params: web::Json<SaveWebhook>, storage: web::Data<DbStorage>, rng: web::Data<RandomGenerator>, caller: CallerPrincipal) -> Result<impl Responder> {
+    let generated_secret: String = {
+        let mut rng = rng.rng.lock().unwrap();
+        (&mut *rng).sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect()
+    };
+
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        if !caller.admin {
+            return Err(LogicError::AccessDenied.into());
+        }
+
+        let pool = storage.main_db_pool.clone();
+        save_webhook(&mut pool.get()?, &params, &generated_secret)?;
+        Ok(())
+    }).await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[post("/api/webhook/delete")]
+async fn post_delete_webhook(params: web::Json<DeleteWebhook>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
+    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        if !caller.admin {
+            return Err(LogicError::AccessDenied.into());
+        }
+
+        let pool = storage.main_db_pool.clone();
+        delete_webhook(&mut pool.get()?, params.id)?;
+        Ok(())
+    }).await?;
+
+    res.map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().body("\"OK\""))
+}
+
+#[get("/api/webhook/deliveries")]
+async fn get_webhook_deliveries(params: web::Query<WebhookDeliveriesQuery>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
+    let pool = storage.main_db_pool.clone();
+
+    let res: Result<WebhookDeliveries, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        if !caller.admin {
+            return Err(LogicError::AccessDenied.into());
+        }
+
+        let result = query_webhook_deliveries(&pool.get()?, params.webhook_id, params.page)?;
+        Ok(result)
+    })
+    .await?;
+
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
+}
+
+async fn run_webhook_delivery(pool: Pool<SqliteConnectionManager>, max_attempts: u32) {
+    let mut interval = actix_web::rt::time::interval(Duration::from_secs(15));
+
+    loop {
+        interval.tick().await;
+
+        let pool = pool.clone();
+        let res: Result<QueryResult<(usize, usize)>, _> = web::block(move || {
+            deliver_pending_webhooks(&pool.get()?, max_attempts)
+        }).await;
+
+        match res {
+            Ok(Ok((delivered, failed))) if delivered + failed > 0 => println!("Webhooks delivered: {}, failed: {}", delivered, failed),
+            Ok(Err(e)) => eprintln!("Webhook delivery error: {}", e),
+            _ => {}
+        }
+    }
+}
+
+#[get("/api/article/templates")]
+async fn get_article_templates(storage: web::Data<DbStorage>) -> Result<impl Responder> {
+    let pool = storage.main_db_pool.clone();
+
+    let templates: Result<ArticleTemplates, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let conn = pool.get()?;
+        let result = query_article_templates(&conn)?;
+        Ok(result)
+    })
+    .await?;
+
+    Ok(web::Json(templates.map_err(error::ErrorInternalServerError)?))
 }
 
-#[post("/api/comment")]
-async fn post_comment(params: web::Json<PostComment>,
-                      storage: web::Data<DbStorage>,
-                      caller: CallerPrincipal) -> Result<impl Responder> {
-
+#[post("/api/article/template")]
+async fn post_save_article_template(params: web::Json<SaveArticleTemplate>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
     let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        if !caller.admin {
+            return Err(LogicError::AccessDenied.into());
+        }
+
         let pool = storage.main_db_pool.clone();
-        save_comment(&mut pool.get()?, &params, &caller.user)?;
+        save_article_template(&pool.get()?, &params)?;
         Ok(())
-    })
-        .await?;
+    }).await?;
 
     res.map_err(error::ErrorInternalServerError)?;
 
-    return Ok(HttpResponse::Ok().body("\"OK\""))
+    Ok(HttpResponse::Ok().body("\"OK\""))
 }
 
-#[post("/api/message/update")]
-async fn post_update_message(params: web::Json<UpdateMessage>,
-                             storage: web::Data<DbStorage>,
-                             caller: CallerPrincipal) -> Result<impl Responder> {
+#[post("/api/article/template/delete")]
+async fn post_delete_article_template(params: web::Json<DeleteArticleTemplate>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
     let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        if !caller.admin {
+            return Err(LogicError::AccessDenied.into());
+        }
+
         let pool = storage.main_db_pool.clone();
-        update_message(&mut pool.get()?, &params, &caller.user)?;
+        delete_article_template(&pool.get()?, params.id)?;
         Ok(())
-    })
-    .await?;
+    }).await?;
 
     res.map_err(error::ErrorInternalServerError)?;
 
-    return Ok(HttpResponse::Ok().body("\"OK\""))
+    Ok(HttpResponse::Ok().body("\"OK\""))
 }
 
-#[post("/api/comment/update")]
-async fn post_update_article_comment(params: web::Json<UpdateComment>,
-                             storage: web::Data<DbStorage>,
-                             caller: CallerPrincipal) -> Result<impl Responder> {
+#[post("/api/article/update")]
+async fn post_update_article(params: web::Json<UpdateArticle>, storage: web::Data<DbStorage>, live_events: web::Data<LiveEvents>, caller: CallerPrincipal) -> Result<impl Responder> {
+    let user = caller.user.clone();
+    let id = params.id;
+
+    let pool = storage.main_db_pool.clone();
     let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
-        let pool = storage.main_db_pool.clone();
-        update_comment(&mut pool.get()?, &params, &caller.user)?;
+        let mut conn = pool.get()?;
+        update_article(&mut conn, &params, caller.user.clone())?;
         Ok(())
-    })
-        .await?;
+    }).await?;
 
     res.map_err(error::ErrorInternalServerError)?;
+    publish_live_event(&storage, &live_events, move |conn| article_event(conn, LiveEventKind::ArticleUpdate, id, None, &user)).await;
 
     return Ok(HttpResponse::Ok().body("\"OK\""))
 }
 
-#[post("/api/section")]
-async fn post_new_section(params: web::Json<PostNewSection>, storage: web::Data<DbStorage>) -> Result<impl Responder>  {
+#[post("/api/article/delete")]
+async fn post_delete_article(params: web::Json<DeleteArticle>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
     let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
         let pool = storage.main_db_pool.clone();
-        create_section(&mut pool.get()?, &params.name)?;
+        delete_article(&mut pool.get()?, params.id, &Some(caller.user))?;
         Ok(())
-    })
-    .await?;
+    }).await?;
 
     res.map_err(error::ErrorInternalServerError)?;
 
     return Ok(HttpResponse::Ok().body("\"OK\""))
 }
 
-#[post("/api/subsection")]
-async fn post_new_subsection(params: web::Json<PostNewSubsection>, storage: web::Data<DbStorage>) -> Result<impl Responder>  {
-    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
-        let pool = storage.main_db_pool.clone();
-        create_sub_section(&mut pool.get()?, params.section_id, &params.name)?;
-        Ok(())
+#[get("/api/article/trash")]
+async fn get_article_trash(params: web::Query<TrashQuery>, storage: web::Data<DbStorage>, settings: web::Data<Settings>, caller: CallerPrincipal) -> Result<impl Responder> {
+    let pool = storage.main_db_pool.clone();
+    let purge_days = settings.trash_purge_days;
+
+    let res: Result<DeletedArticles, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let all = params.all.unwrap_or(false);
+        if all && !caller.admin {
+            return Err(LogicError::AccessDenied.into());
+        }
+
+        let articles = query_deleted_articles(&pool.get()?, if all {None} else {Some(&caller.user)})?;
+        Ok(DeletedArticles{articles, purge_days})
     })
     .await?;
 
-    res.map_err(error::ErrorInternalServerError)?;
-
-    return Ok(HttpResponse::Ok().body("\"OK\""))
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
 }
 
-#[post("/api/section/rename")]
-async fn post_rename_section(params: web::Json<RenameSection>, storage: web::Data<DbStorage>) -> Result<impl Responder>  {
+#[post("/api/article/restore")]
+async fn post_restore_article(params: web::Json<RestoreArticle>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
     let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
         let pool = storage.main_db_pool.clone();
-        rename_section(&mut pool.get()?, params.section_id, &params.name)?;
+        restore_article(&mut pool.get()?, params.id, &caller.user, caller.admin)?;
         Ok(())
-    })
-    .await?;
+    }).await?;
 
     res.map_err(error::ErrorInternalServerError)?;
 
-    return Ok(HttpResponse::Ok().body("\"OK\""))
+    Ok(HttpResponse::Ok().body("\"OK\""))
 }
 
-#[post("/api/subsection/rename")]
-async fn post_rename_subsection(params: web::Json<RenameSubsection>, storage: web::Data<DbStorage>) -> Result<impl Responder>  {
-    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
-        let pool = storage.main_db_pool.clone();
-        rename_subsection(&mut pool.get()?, params.subsection_id, &params.name)?;
-        Ok(())
-    })
-    .await?;
-
-    res.map_err(error::ErrorInternalServerError)?;
+async fn run_trash_purge(pool: Pool<SqliteConnectionManager>, purge_days: u32) {
+    let mut interval = actix_web::rt::time::interval(Duration::from_secs(60 * 60));
 
-    return Ok(HttpResponse::Ok().body("\"OK\""))
+    loop {
+        interval.tick().await;
+
+        let pool = pool.clone();
+        let res: Result<QueryResult<usize>, _> = web::block(move || {
+            let deleted_before = chrono::offset::Utc::now() - chrono::Duration::days(purge_days as i64);
+            purge_deleted_articles(&mut pool.get()?, deleted_before.timestamp_millis())
+        }).await;
+
+        match res {
+            Ok(Ok(count)) if count > 0 => println!("Purged {} deleted articles", count),
+            Ok(Err(e)) => eprintln!("Trash purge error: {}", e),
+            _ => {}
+        }
+    }
 }
 
-#[post("/api/subsection/move")]
-async fn post_move_subsection(params: web::Json<MoveSubsection>, storage: web::Data<DbStorage>) -> Result<impl Responder>  {
-    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
-        let pool = storage.main_db_pool.clone();
-        move_subsection(&mut pool.get()?, params.subsection_id, params.new_section_id)?;
-        Ok(())
-    })
-    .await?;
+async fn run_scheduled_publishing(pool: Pool<SqliteConnectionManager>, live_events: web::Data<LiveEvents>) {
+    let mut interval = actix_web::rt::time::interval(SCHEDULER_INTERVAL);
 
-    res.map_err(error::ErrorInternalServerError)?;
+    loop {
+        interval.tick().await;
+
+        let pool = pool.clone();
+        let res: Result<QueryResult<Vec<LiveEvent>>, _> = web::block(move || {
+            let mut conn = pool.get()?;
+            let mut events = publish_due_threads(&mut conn)?;
+            events.extend(publish_due_articles(&mut conn)?);
+            Ok(events)
+        }).await;
+
+        match res {
+            Ok(Ok(events)) => {
+                if !events.is_empty() {
+                    println!("Published {} scheduled threads and articles", events.len());
+                }
 
-    return Ok(HttpResponse::Ok().body("\"OK\""))
+                for event in events {
+                    live_events.publish(event);
+                }
+            }
+            Ok(Err(e)) => eprintln!("Scheduled publishing error: {}", e),
+            _ => {}
+        }
+    }
 }
 
-#[post("/api/thread/rename")]
-async fn post_rename_thread(params: web::Json<RenameThread>, storage: web::Data<DbStorage>) -> Result<impl Responder>  {
-    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
-        let pool = storage.main_db_pool.clone();
-        rename_thread(&mut pool.get()?, params.thread_id, &params.name)?;
-        Ok(())
+#[get("/api/profile")]
+async fn get_profile(params: web::Query<ProfileQuery>, storage: web::Data<DbStorage>) -> Result<impl Responder> {
+    let pool = storage.main_db_pool.clone();
+
+    let res: Result<UserProfile, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let result = query_profile(&*pool.get()?, params.user_id)?;
+        Ok(result)
     })
     .await?;
 
-    res.map_err(error::ErrorInternalServerError)?;
-
-    return Ok(HttpResponse::Ok().body("\"OK\""))
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
 }
 
-#[post("/api/thread/move")]
-async fn post_move_thread(params: web::Json<MoveThread>, storage: web::Data<DbStorage>) -> Result<impl Responder>  {
+#[post("/api/profile")]
+async fn post_update_profile(params: web::Json<UpdateProfile>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
     let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
         let pool = storage.main_db_pool.clone();
-        move_thread(&mut pool.get()?, params.thread_id, params.new_subsection_id)?;
+        update_profile(&*pool.get()?, &params, &caller.user)?;
         Ok(())
     })
-        .await?;
+    .await?;
 
     res.map_err(error::ErrorInternalServerError)?;
 
-    return Ok(HttpResponse::Ok().body("\"OK\""))
+    Ok(HttpResponse::Ok().body("\"OK\""))
 }
 
-#[post("/api/thread/delete")]
-async fn post_delete_thread(params: web::Json<DeleteThread>, storage: web::Data<DbStorage>) -> Result<impl Responder>  {
-    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
-        let pool = storage.main_db_pool.clone();
-        delete_thread(&mut pool.get()?, params.thread_id)?;
-        Ok(())
+#[get("/api/profile/threads")]
+async fn get_profile_threads(params: web::Query<ProfileActivityQuery>, storage: web::Data<DbStorage>) -> Result<impl Responder> {
+    let pool = storage.main_db_pool.clone();
+
+    let res: Result<Vec<Thread>, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let result = query_user_threads(&*pool.get()?, params.user_id, params.page)?;
+        Ok(result)
     })
     .await?;
 
-    res.map_err(error::ErrorInternalServerError)?;
-
-    return Ok(HttpResponse::Ok().body("\"OK\""))
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
 }
 
-#[derive(MultipartForm)]
-struct Upload {
-    #[multipart(rename="file_set[]")]
-    file_set: Vec<Tempfile>,
-}
+#[get("/api/profile/messages")]
+async fn get_profile_messages(params: web::Query<ProfileActivityQuery>, storage: web::Data<DbStorage>) -> Result<impl Responder> {
+    let pool = storage.main_db_pool.clone();
 
-fn sha256_digest(file: &File) -> QueryResult<Digest> {
-    let mut reader = BufReader::new(file);
+    let res: Result<MessageSearchResults, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let result = query_user_messages(&*pool.get()?, params.user_id, params.page)?;
+        Ok(result)
+    })
+    .await?;
 
-    let mut context = Context::new(&SHA256);
-    let mut buffer = [0; 1024];
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
+}
 
-    loop {
-        let count = reader.read(&mut buffer)?;
-        if count == 0 {
-            break;
-        }
-        context.update(&buffer[..count]);
-    }
+#[get("/api/profile/articles")]
+async fn get_profile_articles(params: web::Query<ProfileActivityQuery>, storage: web::Data<DbStorage>) -> Result<impl Responder> {
+    let pool = storage.main_db_pool.clone();
 
-    Ok(context.finish())
+    let res: Result<Articles, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let result = query_user_articles(&*pool.get()?, params.user_id, params.page)?;
+        Ok(result)
+    })
+    .await?;
+
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
 }
 
-#[post("/api/upload-files")]
-async fn post_upload(form: MultipartForm<Upload>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
-    let res: Result<UploadedFiles, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
-        let file_set: &Vec<Tempfile> = &form.file_set;
+#[get("/api/profile/comments")]
+async fn get_profile_comments(params: web::Query<ProfileActivityQuery>, storage: web::Data<DbStorage>) -> Result<impl Responder> {
+    let pool = storage.main_db_pool.clone();
 
-        let pool = storage.main_db_pool.clone();
+    let res: Result<ProfileComments, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let result = query_user_comments(&*pool.get()?, params.user_id, params.page)?;
+        Ok(result)
+    })
+    .await?;
 
-        let mut files = Vec::new();
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
+}
 
-        let mut i = 0;
-        for file in file_set.iter() {
-            let input = File::open(file.file.path())?;
-            let digest = sha256_digest(&input)?;
-            let hex = HEXLOWER.encode(digest.as_ref());
-            let result_file_path = "files/".to_owned() + &hex;
-            std::fs::create_dir_all("files")?;
-            std::fs::copy(file.file.path(), &result_file_path)?;
+#[get("/api/scheduled")]
+async fn get_scheduled_publications(storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
+    let pool = storage.main_db_pool.clone();
 
-            let mime = if let Some(content_type) = &file.content_type {
-                content_type.as_ref().to_string()
-            }
-            else {
-                "application/octet-stream".to_string()
-            };
+    let res: Result<ScheduledPublications, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let result = query_scheduled_publications(&*pool.get()?, &caller.user)?;
+        Ok(result)
+    })
+    .await?;
 
-            let original_file_name = if let Some(file_name) = &file.file_name {
-                file_name.to_string()
-            }
-            else {
-                "file".to_string()
-            };
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
+}
 
-            let id = save_file_mapping(&mut pool.get()?, &hex, &mime, &original_file_name, &caller.user, i)?;
-            files.push(UploadedFile {
-                id, file_name: original_file_name, mime: mime
-            });
+async fn run_draft_purge(pool: Pool<SqliteConnectionManager>, expire_days: u32) {
+    let mut interval = actix_web::rt::time::interval(Duration::from_secs(60 * 60));
 
-            i = i + 1;
+    loop {
+        interval.tick().await;
+
+        let pool = pool.clone();
+        let res: Result<QueryResult<usize>, _> = web::block(move || {
+            let updated_before = chrono::offset::Utc::now() - chrono::Duration::days(expire_days as i64);
+            purge_expired_drafts(&*pool.get()?, updated_before.timestamp_millis())
+        }).await;
+
+        match res {
+            Ok(Ok(count)) if count > 0 => println!("Purged {} expired drafts", count),
+            Ok(Err(e)) => eprintln!("Draft purge error: {}", e),
+            _ => {}
         }
+    }
+}
 
-        Ok(UploadedFiles{files})
+#[get("/api/drafts")]
+async fn get_drafts(storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
+    let pool = storage.main_db_pool.clone();
+
+    let res: Result<Drafts, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let result = query_drafts(&*pool.get()?, &caller.user)?;
+        Ok(result)
     })
     .await?;
 
     Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
 }
 
-#[post("/api/article")]
-async fn post_create_article(params: web::Json<CreateArticle>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
-    let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
-        let pool = storage.main_db_pool.clone();
-        create_article(&mut pool.get()?, &params.path, &params.name, &params.content, params.visibility, &caller.user)?;
-        Ok(())
-    }).await?;
+#[get("/api/draft")]
+async fn get_draft(params: web::Query<DraftQuery>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
+    let pool = storage.main_db_pool.clone();
 
-    res.map_err(error::ErrorInternalServerError)?;
+    let res: Result<Option<Draft>, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let result = query_draft(&*pool.get()?, &params.context, &caller.user)?;
+        Ok(result)
+    })
+    .await?;
 
-    return Ok(HttpResponse::Ok().body("\"OK\""))
+    Ok(web::Json(res.map_err(error::ErrorInternalServerError)?))
 }
 
-#[post("/api/article/update")]
-async fn post_update_article(params: web::Json<UpdateArticle>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
+#[post("/api/draft")]
+async fn post_save_draft(params: web::Json<SaveDraft>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
     let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
         let pool = storage.main_db_pool.clone();
-        update_article(&mut pool.get()?, params.id, &params.path, &params.name, &params.content, params.visibility, caller.user)?;
+        save_draft(&*pool.get()?, &params, &caller.user)?;
         Ok(())
-    }).await?;
+    })
+    .await?;
 
     res.map_err(error::ErrorInternalServerError)?;
 
-    return Ok(HttpResponse::Ok().body("\"OK\""))
+    Ok(HttpResponse::Ok().body("\"OK\""))
 }
 
-#[post("/api/article/delete")]
-async fn post_delete_article(params: web::Json<DeleteArticle>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
+#[post("/api/draft/delete")]
+async fn post_delete_draft(params: web::Json<DeleteDraft>, storage: web::Data<DbStorage>, caller: CallerPrincipal) -> Result<impl Responder> {
     let res: Result<(), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
         let pool = storage.main_db_pool.clone();
-        delete_article(&mut pool.get()?, params.id, &Some(caller.user))?;
+        delete_draft(&*pool.get()?, &DraftContext::parse(&params.context)?, &caller.user)?;
         Ok(())
-    }).await?;
+    })
+    .await?;
 
     res.map_err(error::ErrorInternalServerError)?;
 
-    return Ok(HttpResponse::Ok().body("\"OK\""))
+    Ok(HttpResponse::Ok().body("\"OK\""))
 }
 
 #[post("/api/article/favorite/add")]
@@ -692,12 +2175,12 @@ async fn post_remove_favorite_article(params: web::Json<RemoveFavoriteArticle>,
 }
 
 #[get("/api/article/list")]
-async fn get_articles(storage: web::Data<DbStorage>, principal: Option<CallerPrincipal>) -> Result<impl Responder> {
+async fn get_articles(params: web::Query<ArticlesQuery>, storage: web::Data<DbStorage>, principal: Option<CallerPrincipal>) -> Result<impl Responder> {
     let pool = storage.main_db_pool.clone();
 
     let messages: Result<Articles, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
         let conn = pool.get()?;
-        let result = query_articles(&conn, &principal.map(|p| p.user))?;
+        let result = query_articles(&conn, &parse_tags(&params.tags), &principal.map(|p| p.user))?;
         Ok(result)
     })
     .await?;
@@ -733,6 +2216,60 @@ async fn get_article(params: web::Query<GetArticle>, storage: web::Data<DbStorag
     Ok(web::Json(article.map_err(error::ErrorInternalServerError)?))
 }
 
+#[get("/api/article/by-path")]
+async fn get_article_by_path(params: web::Query<GetArticleByPath>, storage: web::Data<DbStorage>, principal: Option<CallerPrincipal>) -> Result<impl Responder> {
+    let pool = storage.main_db_pool.clone();
+
+    let article: Result<Article, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let conn = pool.get()?;
+        let id = resolve_article_slug(&conn, &params.path)?.ok_or(LogicError::ArticleNotFound)?;
+        let result = query_article(&conn, id, None, &principal.map(|p| p.user))?;
+        Ok(result)
+    })
+    .await?;
+
+    Ok(web::Json(article.map_err(error::ErrorInternalServerError)?))
+}
+
+#[get("/api/article/broken-links")]
+async fn get_broken_links(storage: web::Data<DbStorage>, principal: Option<CallerPrincipal>) -> Result<impl Responder> {
+    let pool = storage.main_db_pool.clone();
+
+    let links: Result<BrokenLinks, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let conn = pool.get()?;
+        let result = query_broken_links(&conn, &principal.map(|p| p.user))?;
+        Ok(result)
+    })
+    .await?;
+
+    Ok(web::Json(links.map_err(error::ErrorInternalServerError)?))
+}
+
+async fn wiki_redirect(slug: web::Path<String>, storage: web::Data<DbStorage>, principal: Option<CallerPrincipal>) -> Result<HttpResponse> {
+    let pool = storage.main_db_pool.clone();
+
+    let id: Result<Option<u32>, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+        let conn = pool.get()?;
+        let id = resolve_article_slug(&conn, &slug)?;
+
+        if let Some(id) = id {
+            if query_article(&conn, id, None, &principal.map(|p| p.user)).is_ok() {
+                return Ok(Some(id));
+            }
+        }
+
+        Ok(None)
+    })
+    .await?;
+
+    match id.map_err(error::ErrorInternalServerError)? {
+        Some(id) => Ok(HttpResponse::Found()
+            .insert_header((header::LOCATION, format!("/article/a/{}", id)))
+            .finish()),
+        None => Ok(HttpResponse::NotFound().finish())
+    }
+}
+
 #[get("/api/search-articles")]
 async fn get_search_articles(params: web::Query<ArticleSearchParams>, storage: web::Data<DbStorage>, principal: Option<CallerPrincipal>) -> Result<impl Responder> {
     let pool = storage.main_db_pool.clone();
@@ -792,6 +2329,7 @@ struct RandomGenerator {
 
 struct CallerPrincipal {
     user: User,
+    admin: bool,
 }
 
 impl FromRequest for CallerPrincipal {
@@ -817,18 +2355,20 @@ impl FromRequest for CallerPrincipal {
         let user_sessions_pool = storage.user_sessions_db_pool.clone();
 
         Box::pin(async move {
-            let user: Result<User, Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
-                let user = query_user_by_session(&pool.get()?, &user_sessions_pool.get()?, &session.unwrap())?;
+            let user: Result<(User, bool), Box<dyn std::error::Error + Send + Sync>> = web::block(move || {
+                let conn = pool.get()?;
+                let user = query_user_by_session(&conn, &user_sessions_pool.get()?, &session.unwrap())?;
 
                 if let Some(user) = user {
-                    return Ok(user);
+                    let admin = query_user_is_admin(&conn, user.id)?;
+                    return Ok((user, admin));
                 }
                 return Err(LogicError::UserNotLoggedIn.into());
             })
             .await?;
 
             match user {
-                Ok(user) => Ok(CallerPrincipal{user}),
+                Ok((user, admin)) => Ok(CallerPrincipal{user, admin}),
                 Err(err) => Err(err).map_err(error::ErrorInternalServerError)
             }
         })
@@ -860,29 +2400,60 @@ async fn main() -> std::io::Result<()> {
     let users_passwords_db_manager = SqliteConnectionManager::file("data/user_passwords.s3db");
     let user_passwords_db_pool = Pool::new(users_passwords_db_manager).unwrap();
 
+    backfill_article_slugs(&mut main_db_pool.get().unwrap()).expect("Article slugs backfill error");
+
     let user_sessions_db_manager = SqliteConnectionManager::file("data/user_sessions.s3db");
     let user_sessions_db_pool = Pool::new(user_sessions_db_manager).unwrap();
 
     let db_storage = DbStorage {
-        main_db_pool,
+        main_db_pool: main_db_pool.clone(),
         user_passwords_db_pool,
         user_sessions_db_pool
     };
 
+    let settings = Settings::from_env();
+
+    if settings.trash_purge_days > 0 {
+        actix_web::rt::spawn(run_trash_purge(main_db_pool.clone(), settings.trash_purge_days));
+    }
+
+    if settings.draft_expire_days > 0 {
+        actix_web::rt::spawn(run_draft_purge(main_db_pool.clone(), settings.draft_expire_days));
+    }
+
+    actix_web::rt::spawn(run_webhook_delivery(main_db_pool.clone(), settings.webhook_max_attempts));
+
+    if let Some(transport) = build_smtp_transport(&settings).expect("Invalid SMTP settings") {
+        actix_web::rt::spawn(run_email_delivery(main_db_pool.clone(), settings.clone(), transport));
+    }
+
     let rng = web::Data::new(RandomGenerator{
         rng: Mutex::new(ChaCha20Rng::from_entropy())
     });
 
+    let live_events = web::Data::new(LiveEvents::new());
+
+    actix_web::rt::spawn(run_scheduled_publishing(main_db_pool.clone(), live_events.clone()));
+
     HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(db_storage.clone()))
             .app_data(rng.clone())
+            .app_data(live_events.clone())
+            .app_data(web::Data::new(settings.clone()))
             .route("/", web::get().to(index))
             .route("/forum", web::get().to(index))
             .route("/forum/{tail:.*}", web::get().to(index))
             .route("/articles", web::get().to(index))
             .route("/article/{tail:.*}", web::get().to(index))
+            .route("/wiki/{slug:.*}", web::get().to(wiki_redirect))
+            .route("/m/{thread_id}/{message_id}", web::get().to(message_permalink))
+            .route("/c/{article_id}/{comment_id}", web::get().to(comment_permalink))
             .route("/signup", web::get().to(index))
+            .route("/unsubscribe/{token}", web::get().to(unsubscribe_page))
+            .route("/unsubscribe/{token}", web::post().to(unsubscribe_email))
+            .route("/reset-password/{token}", web::get().to(password_reset_page))
+            .route("/reset-password/{token}", web::post().to(reset_password))
             .route("/js/bundle.min.js", web::get().to(js_bundle))
             .route("/css/style.css", web::get().to(style))
             .route("/css/icons.css", web::get().to(icons_css))
@@ -892,8 +2463,36 @@ async fn main() -> std::io::Result<()> {
             .service(get_threads)
             .service(get_forum_structure)
             .service(get_messages)
+            .service(post_mark_thread_read)
+            .service(post_mark_threads_read)
+            .service(post_subscribe)
+            .service(post_unsubscribe)
+            .service(get_subscriptions)
+            .service(get_notifications)
+            .service(get_notification_count)
+            .service(post_mark_notifications_read)
+            .service(get_live_events)
+            .service(get_email_settings)
+            .service(get_threads_feed)
+            .service(get_section_feed)
+            .service(get_subsection_feed)
+            .service(get_thread_feed)
+            .service(get_articles_feed)
+            .service(get_feed_token)
+            .service(post_create_feed_token)
+            .service(post_reset_feed_token)
+            .service(post_email_settings)
             .service(get_search_messages)
             .service(get_current_user)
+            .service(get_password_policy)
+            .service(post_change_password)
+            .service(post_create_password_reset)
+            .service(get_password_hash_report)
+            .service(get_search_users)
+            .service(get_allowed_reactions)
+            .service(post_add_reaction)
+            .service(post_remove_reaction)
+            .service(get_reaction_users)
             .service(get_user_sessions)
             .service(get_uploaded_file)
             .service(post_thread)
@@ -904,26 +2503,73 @@ async fn main() -> std::io::Result<()> {
             .service(post_new_section)
             .service(post_new_subsection)
             .service(post_upload)
+            .service(get_article_attachments)
+            .service(post_upload_article_attachments)
+            .service(post_remove_article_attachment)
             .service(post_rename_section)
             .service(post_rename_subsection)
             .service(post_rename_thread)
             .service(post_move_thread)
+            .service(post_update_thread_flags)
+            .service(post_merge_threads)
+            .service(post_split_thread)
+            .service(get_message_location)
+            .service(get_comment_location)
+            .service(get_message_replies)
+            .service(get_poll)
+            .service(post_poll_vote)
             .service(post_delete_thread)
             .service(post_move_subsection)
+            .service(post_update_subsection_description)
+            .service(post_delete_section)
+            .service(post_delete_subsection)
+            .service(post_restore_section)
+            .service(post_restore_subsection)
+            .service(post_reorder_sections)
+            .service(post_reorder_subsections)
+            .service(get_deleted_sections)
             .service(post_create_article)
             .service(post_update_article)
+            .service(get_article_templates)
+            .service(post_save_article_template)
+            .service(post_delete_article_template)
+            .service(get_webhooks)
+            .service(post_save_webhook)
+            .service(post_delete_webhook)
+            .service(get_webhook_deliveries)
             .service(post_delete_article)
+            .service(get_article_trash)
+            .service(post_restore_article)
             .service(post_add_favorite_article)
             .service(post_remove_favorite_article)
             .service(get_articles)
+            .service(get_tags)
+            .service(get_tags_search)
+            .service(post_thread_tags)
+            .service(post_rename_tag)
+            .service(post_merge_tags)
+            .service(get_drafts)
+            .service(get_scheduled_publications)
+            .service(get_profile)
+            .service(post_update_profile)
+            .service(get_profile_threads)
+            .service(get_profile_messages)
+            .service(get_profile_articles)
+            .service(get_profile_comments)
+            .service(get_draft)
+            .service(post_save_draft)
+            .service(post_delete_draft)
             .service(get_favorite_articles)
             .service(get_article)
+            .service(get_article_by_path)
+            .service(get_broken_links)
             .service(get_search_articles)
             .service(get_article_comments)
             .service(post_remove_user_session)
             .service(post_logout)
             .service(signon)
             .service(signup)
+            .service(post_request_password_reset)
     })
         .bind((bind_address, port))?
         .run()
@@ -0,0 +1,150 @@
+use rusqlite::{Connection, OptionalExtension, params};
+use crate::notifications::add_notification;
+use crate::queries::{extract_user, QueryResult};
+use crate::structs::{MentionSpan, NotificationKind, User, Users};
+
+pub const USER_SEARCH_LIMIT: u32 = 20;
+
+pub enum MentionSource {
+    Message { thread_id: u32, message_id: u32 },
+    Comment { article_id: u32, comment_id: u32 },
+    Article { article_id: u32 },
+}
+
+impl MentionSource {
+    // (thread_id, article_id, message_id) as stored in MENTION
+    fn key(&self) -> (u32, u32, u32) {
+        match self {
+            MentionSource::Message { thread_id, message_id } => (*thread_id, 0, *message_id),
+            MentionSource::Comment { article_id, comment_id } => (0, *article_id, *comment_id),
+            MentionSource::Article { article_id } => (0, *article_id, 0),
+        }
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-' || c == '.'
+}
+
+// Finds @name mentions, returns byte ranges of the whole mention (including @) and the name.
+// @ inside words (e-mail addresses) is ignored.
+pub fn extract_mentions(content: &str) -> Vec<(usize, usize, &str)> {
+    let mut mentions = Vec::new();
+    let mut prev: Option<char> = None;
+
+    for (index, c) in content.char_indices() {
+        if c == '@' && !prev.is_some_and(|p| is_name_char(p) || p == '@') {
+            let name_start = index + 1;
+            let name_len: usize = content[name_start..].chars()
+                .take_while(|c| is_name_char(*c))
+                .map(char::len_utf8)
+                .sum();
+
+            let name = content[name_start..name_start + name_len].trim_end_matches(['.', '-']);
+            if !name.is_empty() {
+                mentions.push((index, name_start + name.len(), name));
+            }
+        }
+
+        prev = Some(c);
+    }
+
+    mentions
+}
+
+fn find_user_by_name(conn: &Connection, name: &str) -> QueryResult<Option<User>> {
+    let user = conn.query_row("SELECT id, name, display_name, avatar_file_id FROM USER WHERE name = ?", [name],
+                              |row| extract_user(row, 0)).optional()?;
+    Ok(user)
+}
+
+pub fn resolve_mentions(conn: &Connection, content: &str) -> QueryResult<Vec<MentionSpan>> {
+    let mut spans = Vec::new();
+
+    for (start, end, name) in extract_mentions(content) {
+        if let Some(user) = find_user_by_name(conn, name)? {
+            let start16 = content[..start].encode_utf16().count();
+            let len16 = content[start..end].encode_utf16().count();
+
+            spans.push(MentionSpan {
+                start: start16 as u32,
+                end: (start16 + len16) as u32,
+                user,
+            });
+        }
+    }
+
+    Ok(spans)
+}
+
+// Sends a mention notification to every user mentioned in the content for the first time,
+// returns notified user ids
+pub fn notify_mentions(conn: &Connection, source: MentionSource, content: &str, author: &User) -> QueryResult<Vec<u32>> {
+    let (thread_id, article_id, message_id) = source.key();
+
+    let t = chrono::offset::Utc::now();
+    let t = t.timestamp_millis();
+
+    let mut notified = Vec::new();
+
+    for span in resolve_mentions(conn, content)? {
+        let user_id = span.user.id;
+        if user_id == author.id || notified.contains(&user_id) {
+            continue;
+        }
+
+        if article_id != 0 {
+            let visible: bool = conn.query_row("SELECT EXISTS(SELECT 1 FROM ARTICLE WHERE id = ? AND active = TRUE AND (visibility = 'public' OR user_id = ?))",
+                                               params![article_id, user_id], |row| row.get(0))?;
+            if !visible {
+                continue;
+            }
+        }
+
+        let inserted = conn.execute("INSERT OR IGNORE INTO MENTION(user_id, thread_id, article_id, message_id, create_time) VALUES(?, ?, ?, ?, ?)",
+                                    params![user_id, thread_id, article_id, message_id, t])?;
+        if inserted == 0 {
+            continue;
+        }
+
+        add_notification(conn, user_id, NotificationKind::Mention,
+                         if thread_id != 0 {Some(thread_id)} else {None},
+                         if article_id != 0 {Some(article_id)} else {None},
+                         if message_id != 0 {Some(message_id)} else {None},
+                         author)?;
+
+        notified.push(user_id);
+    }
+
+    Ok(notified)
+}
+
+pub fn search_users(conn: &Connection, prefix: &str) -> QueryResult<Users> {
+    // Range condition keeps the lookup on USER_NAME_INX
+    let upper_bound = format!("{}{}", prefix, char::MAX);
+
+    let mut stmt = conn.prepare(
+        &format!("SELECT id, name, display_name, avatar_file_id FROM USER WHERE name >= ? AND name < ? ORDER BY name LIMIT {}", USER_SEARCH_LIMIT),
+    )?;
+
+    let users = stmt.query_map(params![prefix, upper_bound], |row| extract_user(row, 0))?
+        .collect::<Result<Vec<User>, _>>()?;
+
+    Ok(Users{users})
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mentions::extract_mentions;
+
+    #[test]
+    fn mentions_are_extracted() {
+        let content = "@alice, ask @bob.smith. Mail me at carol@example.com or @. (@иван)";
+        let names: Vec<&str> = extract_mentions(content).iter().map(|m| m.2).collect();
+
+        assert_eq!(vec!["alice", "bob.smith", "иван"], names);
+
+        let (start, end, _) = extract_mentions(content)[1];
+        assert_eq!("@bob.smith", &content[start..end]);
+    }
+}
@@ -0,0 +1,179 @@
+use rusqlite::{Connection, params, Row};
+use crate::queries::{DbConnection, extract_user, MESSAGES_PER_PAGE, QueryResult};
+use crate::structs::{MarkNotificationsRead, Notification, NotificationCount, NotificationKind, Notifications, Subscription, Subscriptions, SubscriptionTarget, User};
+
+pub const NOTIFICATIONS_PER_PAGE: u32 = 50;
+
+pub fn subscribe(conn: &Connection, user_id: u32, target_type: SubscriptionTarget, target_id: u32) -> QueryResult<()> {
+    conn.execute("INSERT OR IGNORE INTO SUBSCRIPTION(user_id, target_type, target_id) VALUES(?, ?, ?)",
+                 params![user_id, target_type, target_id])?;
+
+    Ok(())
+}
+
+pub fn unsubscribe(conn: &DbConnection, user: &User, target_type: SubscriptionTarget, target_id: u32) -> QueryResult<()> {
+    conn.execute("DELETE FROM SUBSCRIPTION WHERE user_id = ? AND target_type = ? AND target_id = ?",
+                 params![user.id, target_type, target_id])?;
+
+    Ok(())
+}
+
+pub fn query_subscriptions(conn: &DbConnection, user: &User) -> QueryResult<Subscriptions> {
+    let mut stmt = conn.prepare(
+        "SELECT s.target_type, s.target_id, CASE s.target_type \
+            WHEN 'thread' THEN (SELECT tn.name FROM THREAD t INNER JOIN THREAD_NAME_FTS tn ON tn.rowid = t.name_id WHERE t.id = s.target_id) \
+            WHEN 'subsection' THEN (SELECT name FROM SUBSECTION WHERE id = s.target_id) \
+            ELSE (SELECT c.name FROM ARTICLE a INNER JOIN ARTICLE_CONTENT_FTS c ON c.rowid = a.content_id WHERE a.id = s.target_id AND a.active = TRUE) \
+        END \
+        FROM SUBSCRIPTION s WHERE s.user_id = ? ORDER BY s.target_type, s.target_id",
+    )?;
+
+    let subscriptions = stmt.query_map([user.id], |row| Ok(Subscription {
+        target_type: row.get(0)?,
+        target_id: row.get(1)?,
+        title: row.get(2)?,
+    }))?.collect::<Result<Vec<Subscription>, _>>()?;
+
+    Ok(Subscriptions{subscriptions})
+}
+
+// Unread notifications are grouped per thread or article, a new event only bumps the counter.
+// Mentions are grouped separately so they are not hidden behind regular activity.
+pub fn add_notification(conn: &Connection, user_id: u32, kind: NotificationKind, thread_id: Option<u32>, article_id: Option<u32>, message_id: Option<u32>, actor: &User) -> QueryResult<()> {
+    let t = chrono::offset::Utc::now();
+    let t = t.timestamp_millis();
+
+    let count = conn.execute("UPDATE NOTIFICATION SET kind = ?, actor_id = ?, event_count = event_count + 1, update_time = ?, message_id = COALESCE(message_id, ?) \
+        WHERE user_id = ? AND read = FALSE AND thread_id IS ? AND article_id IS ? AND (kind = 'mention') = ?",
+                             params![kind, actor.id, t, message_id, user_id, thread_id, article_id, matches!(kind, NotificationKind::Mention)])?;
+
+    if count == 0 {
+        conn.execute("INSERT INTO NOTIFICATION(user_id, kind, thread_id, article_id, message_id, actor_id, event_count, create_time, update_time) \
+            VALUES(?, ?, ?, ?, ?, ?, 1, ?, ?)",
+                     params![user_id, kind, thread_id, article_id, message_id, actor.id, t, t])?;
+    }
+
+    Ok(())
+}
+
+// Notifies subscribers of the thread and of its subsection, returns notified user ids
+pub fn notify_thread_subscribers(conn: &Connection, thread_id: u32, kind: NotificationKind, message_id: u32, actor: &User) -> QueryResult<Vec<u32>> {
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT user_id FROM SUBSCRIPTION WHERE user_id != ?1 \
+        AND ((target_type = 'thread' AND target_id = ?2) \
+            OR (target_type = 'subsection' AND target_id = (SELECT subsection_id FROM THREAD WHERE id = ?2)))",
+    )?;
+
+    let users = stmt.query_map(params![actor.id, thread_id], |row| row.get(0))?
+        .collect::<Result<Vec<u32>, _>>()?;
+
+    for user_id in &users {
+        add_notification(conn, *user_id, kind, Some(thread_id), None, Some(message_id), actor)?;
+    }
+
+    Ok(users)
+}
+
+// Notifies subscribers of the article who can see it, returns notified user ids
+pub fn notify_article_subscribers(conn: &Connection, article_id: u32, kind: NotificationKind, comment_id: Option<u32>, actor: &User) -> QueryResult<Vec<u32>> {
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT s.user_id FROM SUBSCRIPTION s \
+        INNER JOIN ARTICLE a ON a.id = s.target_id AND a.active = TRUE \
+        WHERE s.target_type = 'article' AND s.target_id = ?2 AND s.user_id != ?1 \
+        AND (a.visibility = 'public' OR a.user_id = s.user_id)",
+    )?;
+
+    let users = stmt.query_map(params![actor.id, article_id], |row| row.get(0))?
+        .collect::<Result<Vec<u32>, _>>()?;
+
+    for user_id in &users {
+        add_notification(conn, *user_id, kind, None, Some(article_id), comment_id, actor)?;
+    }
+
+    Ok(users)
+}
+
+fn notifications_sql(where_clause: &str) -> String {
+    format!("SELECT n.id, n.kind, n.thread_id, n.article_id, \
+        CASE WHEN n.thread_id IS NOT NULL \
+            THEN (SELECT tn.name FROM THREAD t INNER JOIN THREAD_NAME_FTS tn ON tn.rowid = t.name_id WHERE t.id = n.thread_id) \
+            ELSE (SELECT c.name FROM ARTICLE a INNER JOIN ARTICLE_CONTENT_FTS c ON c.rowid = a.content_id WHERE a.id = n.article_id AND a.active = TRUE) \
+        END, \
+        n.message_id, \
+        CASE WHEN n.thread_id IS NOT NULL AND n.message_id IS NOT NULL \
+            THEN (SELECT count(1) FROM MESSAGE m WHERE m.thread_id = n.thread_id AND m.id < n.message_id) / {} + 1 \
+        END, \
+        u.id, u.name, u.display_name, u.avatar_file_id, n.event_count, n.create_time, n.update_time, n.read \
+        FROM NOTIFICATION n \
+        INNER JOIN USER u ON u.id = n.actor_id \
+        WHERE {} \
+        ORDER BY n.update_time DESC", MESSAGES_PER_PAGE, where_clause)
+}
+
+fn notification_from_row(row: &Row) -> rusqlite::Result<Notification> {
+    Ok(Notification {
+        id: row.get(0)?,
+        kind: row.get(1)?,
+        thread_id: row.get(2)?,
+        article_id: row.get(3)?,
+        title: row.get(4)?,
+        message_id: row.get(5)?,
+        page: row.get(6)?,
+        actor: extract_user(row, 7)?,
+        count: row.get(11)?,
+        create_time: row.get(12)?,
+        update_time: row.get(13)?,
+        read: row.get(14)?,
+    })
+}
+
+pub fn query_notifications(conn: &DbConnection, user: &User, page: u32) -> QueryResult<Notifications> {
+    let limit_clause = format!(" LIMIT {}", NOTIFICATIONS_PER_PAGE);
+    let offset_clause = if page > 1 {format!(" OFFSET {}", (page - 1) * NOTIFICATIONS_PER_PAGE)} else { "".to_string() };
+
+    let sql = format!("{} {} {}", notifications_sql("n.user_id = ?"), limit_clause, offset_clause);
+
+    let mut stmt = conn.prepare(&sql)?;
+
+    let notifications = stmt.query_map([user.id], notification_from_row)?
+        .collect::<Result<Vec<Notification>, _>>()?;
+
+    let unread_count = query_unread_notification_count(conn, user)?.unread;
+
+    Ok(Notifications{notifications, unread_count})
+}
+
+// Unread notifications which got new events after the given time, oldest first
+pub fn query_unread_notifications_since(conn: &Connection, user_id: u32, since: i64) -> QueryResult<Vec<Notification>> {
+    let mut stmt = conn.prepare(&notifications_sql("n.user_id = ? AND n.read = FALSE AND n.update_time > ?"))?;
+
+    let mut notifications = stmt.query_map(params![user_id, since], notification_from_row)?
+        .collect::<Result<Vec<Notification>, _>>()?;
+    notifications.reverse();
+
+    Ok(notifications)
+}
+
+pub fn query_unread_notification_count(conn: &DbConnection, user: &User) -> QueryResult<NotificationCount> {
+    let unread: u32 = conn.query_row("SELECT count(1) FROM NOTIFICATION WHERE user_id = ? AND read = FALSE", [user.id], |row| row.get(0))?;
+
+    Ok(NotificationCount{unread})
+}
+
+pub fn mark_notifications_read(conn: &mut DbConnection, user: &User, params: &MarkNotificationsRead) -> QueryResult<()> {
+    let transaction = conn.transaction()?;
+
+    match &params.ids {
+        Some(ids) => {
+            for id in ids {
+                transaction.execute("UPDATE NOTIFICATION SET read = TRUE WHERE user_id = ? AND id = ?", params![user.id, id])?;
+            }
+        }
+        None => {
+            transaction.execute("UPDATE NOTIFICATION SET read = TRUE WHERE user_id = ?", [user.id])?;
+        }
+    }
+
+    transaction.commit()?;
+    Ok(())
+}
@@ -0,0 +1,210 @@
+use std::collections::BTreeMap;
+use argon2::{Algorithm, Params, password_hash::PasswordHash};
+use rusqlite::{Connection, OptionalExtension, params};
+use ring::{digest, hmac};
+use crate::email::escape_html;
+use crate::queries::{extract_user, QueryResult};
+use crate::settings::Settings;
+use crate::structs::{LogicError, PasswordHashReport, PasswordPolicy, PasswordSchemeCount, User};
+
+// Same key as the sign on form uses to hash the password before sending it
+const CLIENT_HASH_KEY: &[u8] = b"[the-ark]";
+const MAX_PASSWORD_LENGTH: usize = 1024;
+
+// Hex of HMAC-SHA256 of the password, what the sign on form sends as the password
+pub fn client_password_digest(password: &str) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, CLIENT_HASH_KEY);
+    hex::encode(hmac::sign(&key, password.as_bytes()).as_ref())
+}
+
+// Counts stored hashes by algorithm, pass_conn is the user passwords database
+pub fn query_password_hash_report(pass_conn: &Connection, hash_params: &Params) -> QueryResult<PasswordHashReport> {
+    let mut stmt = pass_conn.prepare("SELECT password FROM USER_PASS")?;
+    let mut rows = stmt.query([])?;
+
+    let mut schemes = BTreeMap::new();
+    let mut total = 0;
+    let mut legacy = 0;
+    let mut outdated = 0;
+
+    while let Some(row) = rows.next()? {
+        let password: String = row.get(0)?;
+        total += 1;
+
+        let parsed_hash = match PasswordHash::new(&password) {
+            Ok(parsed_hash) => parsed_hash,
+            Err(_) => {
+                legacy += 1;
+                *schemes.entry("unknown".to_string()).or_insert(0) += 1;
+                continue;
+            }
+        };
+
+        *schemes.entry(parsed_hash.algorithm.to_string()).or_insert(0) += 1;
+
+        if parsed_hash.algorithm != Algorithm::Argon2id.ident() {
+            legacy += 1;
+        } else if !Params::try_from(&parsed_hash).is_ok_and(|params| params.m_cost() == hash_params.m_cost()
+            && params.t_cost() == hash_params.t_cost()
+            && params.p_cost() == hash_params.p_cost()) {
+            outdated += 1;
+        }
+    }
+
+    let schemes = schemes.into_iter()
+        .map(|(scheme, count)| PasswordSchemeCount{scheme, count})
+        .collect();
+
+    Ok(PasswordHashReport{total, legacy, outdated, schemes})
+}
+
+pub fn password_policy(settings: &Settings) -> PasswordPolicy {
+    PasswordPolicy {
+        min_length: settings.password_min_length,
+        min_classes: settings.password_min_classes,
+    }
+}
+
+// Character kinds are lowercase and uppercase letters, digits and everything else
+pub fn is_strong_password(password: &str, user_name: &str, policy: &PasswordPolicy) -> bool {
+    let length = password.chars().count();
+    let classes = [
+        password.chars().any(|c| c.is_lowercase()),
+        password.chars().any(|c| c.is_uppercase()),
+        password.chars().any(|c| c.is_numeric()),
+        password.chars().any(|c| !c.is_alphanumeric()),
+    ].iter().filter(|present| **present).count();
+
+    length >= policy.min_length
+        && length <= MAX_PASSWORD_LENGTH
+        && classes >= policy.min_classes
+        && !password.eq_ignore_ascii_case(user_name)
+}
+
+// Sign on sends the digest, sign up and password changes take the plain password.
+// A digest sent there by mistake would be hashed twice and never match on sign on.
+pub fn is_client_password_digest(password: &str) -> bool {
+    password.len() == 64 && password.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+pub fn check_password_strength(password: &str, user_name: &str, settings: &Settings) -> QueryResult<()> {
+    if is_client_password_digest(password) {
+        return Err(LogicError::HashedPassword.into());
+    }
+
+    if !is_strong_password(password, user_name, &password_policy(settings)) {
+        return Err(LogicError::WeakPassword.into());
+    }
+
+    Ok(())
+}
+
+pub fn password_reset_url(base_url: &str, token: &str) -> String {
+    format!("{}/reset-password/{}", base_url, token)
+}
+
+// Only the hash of a token is stored, the token itself is in the link
+fn password_reset_token_hash(token: &str) -> String {
+    hex::encode(digest::digest(&digest::SHA256, token.as_bytes()).as_ref())
+}
+
+// Admin link, replaces earlier tokens of the user and drops expired ones. Returns the expire time.
+pub fn create_password_reset(conn: &Connection, user_id: u32, token: &str, hours: u32) -> QueryResult<i64> {
+    let exists = conn.query_row("SELECT 1 FROM USER WHERE id = ?", [user_id], |_| Ok(())).optional()?;
+    if exists.is_none() {
+        return Err(LogicError::UserNotFound.into());
+    }
+
+    let t = chrono::offset::Utc::now().timestamp_millis();
+    let expire_time = t + hours as i64 * 3600 * 1000;
+
+    conn.execute("DELETE FROM PASSWORD_RESET WHERE user_id = ? OR expire_time <= ?", params![user_id, t])?;
+    conn.execute("INSERT INTO PASSWORD_RESET(token_hash, user_id, requested, create_time, expire_time) VALUES(?, ?, FALSE, ?, ?)",
+                 params![password_reset_token_hash(token), user_id, t, expire_time])?;
+
+    Ok(expire_time)
+}
+
+// Link requested by the user, anyone can ask for it so earlier tokens are kept.
+// Returns false without creating a token while a requested one from the last cooldown minutes is pending.
+pub fn request_password_reset(conn: &Connection, user_id: u32, token: &str, hours: u32, cooldown_minutes: u32) -> QueryResult<bool> {
+    let t = chrono::offset::Utc::now().timestamp_millis();
+    let expire_time = t + hours as i64 * 3600 * 1000;
+
+    conn.execute("DELETE FROM PASSWORD_RESET WHERE expire_time <= ?", [t])?;
+
+    let pending: bool = conn.query_row("SELECT EXISTS(SELECT 1 FROM PASSWORD_RESET WHERE user_id = ? AND requested = TRUE AND create_time > ?)",
+                                       params![user_id, t - cooldown_minutes as i64 * 60 * 1000], |row| row.get(0))?;
+    if pending {
+        return Ok(false);
+    }
+
+    conn.execute("INSERT INTO PASSWORD_RESET(token_hash, user_id, requested, create_time, expire_time) VALUES(?, ?, TRUE, ?, ?)",
+                 params![password_reset_token_hash(token), user_id, t, expire_time])?;
+
+    Ok(true)
+}
+
+pub fn delete_password_reset(conn: &Connection, token: &str) -> QueryResult<()> {
+    conn.execute("DELETE FROM PASSWORD_RESET WHERE token_hash = ?", [password_reset_token_hash(token)])?;
+    Ok(())
+}
+
+// The user of a token that is not expired yet
+pub fn query_password_reset_user(conn: &Connection, token: &str) -> QueryResult<Option<User>> {
+    let t = chrono::offset::Utc::now().timestamp_millis();
+
+    let user = conn.query_row("SELECT u.id, u.name, u.display_name, u.avatar_file_id FROM PASSWORD_RESET r \
+        INNER JOIN USER u ON u.id = r.user_id WHERE r.token_hash = ? AND r.expire_time > ?",
+                              params![password_reset_token_hash(token), t], |row| extract_user(row, 0)).optional()?;
+
+    Ok(user)
+}
+
+// Tokens are single-use: returns the user only for the first caller
+pub fn consume_password_reset(conn: &Connection, token: &str) -> QueryResult<Option<User>> {
+    let user = query_password_reset_user(conn, token)?;
+    if user.is_none() {
+        return Ok(None);
+    }
+
+    let count = conn.execute("DELETE FROM PASSWORD_RESET WHERE token_hash = ?", [password_reset_token_hash(token)])?;
+    if count == 0 {
+        return Ok(None);
+    }
+
+    Ok(user)
+}
+
+pub fn render_password_reset_page(user: &User, error: Option<&str>) -> String {
+    let error = error
+        .map(|error| format!("<p style=\"color: #c00\">{}</p>", escape_html(error)))
+        .unwrap_or_default();
+
+    format!("<!DOCTYPE html><html><body>\
+        <p>New password for {}</p>{}\
+        <form method=\"post\">\
+        <p><input type=\"password\" name=\"password\" placeholder=\"Password\"/></p>\
+        <p><input type=\"password\" name=\"confirmation\" placeholder=\"Password confirmation\"/></p>\
+        <p><button type=\"submit\">Set password</button></p>\
+        </form></body></html>", escape_html(&user.name), error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_password_policy() {
+        let policy = PasswordPolicy{min_length: 8, min_classes: 2};
+
+        assert!(is_strong_password("correct horse", "bob", &policy));
+        assert!(is_strong_password("secret123", "bob", &policy));
+        assert!(!is_strong_password("secret1", "bob", &policy));
+        assert!(!is_strong_password("secretsecret", "bob", &policy));
+        assert!(!is_strong_password("Password1", "password1", &policy));
+        assert_eq!(client_password_digest("secret123"), "4f403f23adb900aafac031f9c733bea318f68b28490110aa0b0ff4008580881d");
+        assert!(is_client_password_digest(&client_password_digest("secret123")));
+        assert!(!is_client_password_digest("secret123"));
+    }
+}
@@ -0,0 +1,123 @@
+use rusqlite::{Connection, OptionalExtension, params, TransactionBehavior};
+use crate::queries::{check_thread_visible, DbConnection, extract_user, QueryResult};
+use crate::structs::{CreatePoll, LogicError, Poll, PollOption, User};
+
+pub const MAX_POLL_OPTIONS: usize = 20;
+
+pub fn create_poll(conn: &Connection, thread_id: u32, poll: &CreatePoll) -> QueryResult<()> {
+    let options: Vec<&str> = poll.options.iter().map(|o| o.trim()).filter(|o| !o.is_empty()).collect();
+    if poll.question.trim().is_empty() || options.len() < 2 || options.len() > MAX_POLL_OPTIONS {
+        return Err(LogicError::InvalidPoll.into());
+    }
+
+    let t = chrono::offset::Utc::now();
+    let t = t.timestamp_millis();
+
+    conn.execute("INSERT INTO POLL(thread_id, question, multiple, anonymous, hide_results, close_time, create_time) VALUES(?, ?, ?, ?, ?, ?, ?)",
+                 params![thread_id, poll.question.trim(), poll.multiple, poll.anonymous, poll.hide_results, poll.close_time, t])?;
+
+    for (i, option) in options.iter().enumerate() {
+        conn.execute("INSERT INTO POLL_OPTION(thread_id, id, text) VALUES(?, ?, ?)", params![thread_id, i as u32 + 1, option])?;
+    }
+
+    Ok(())
+}
+
+// Poll of the thread with the results if they are visible to the user:
+// always for open results, otherwise after the user voted or the poll was closed
+pub fn query_poll(conn: &Connection, thread_id: u32, user: &Option<User>) -> QueryResult<Option<Poll>> {
+    let t = chrono::offset::Utc::now();
+    let t = t.timestamp_millis();
+
+    let poll = conn.query_row("SELECT question, multiple, anonymous, hide_results, close_time FROM POLL WHERE thread_id = ?", [thread_id],
+                              |row| {
+                                  let close_time: Option<i64> = row.get(4)?;
+                                  Ok(Poll {
+                                      question: row.get(0)?,
+                                      multiple: row.get(1)?,
+                                      anonymous: row.get(2)?,
+                                      hide_results: row.get(3)?,
+                                      close_time,
+                                      closed: close_time.is_some_and(|close_time| close_time <= t),
+                                      options: Vec::new(),
+                                      total_voters: None,
+                                      my_votes: Vec::new(),
+                                  })
+                              }).optional()?;
+
+    let mut poll = match poll {
+        Some(poll) => poll,
+        None => return Ok(None)
+    };
+
+    if let Some(user) = user {
+        let mut stmt = conn.prepare("SELECT option_id FROM POLL_VOTE WHERE thread_id = ? AND user_id = ? ORDER BY option_id")?;
+        poll.my_votes = stmt.query_map(params![thread_id, user.id], |row| row.get(0))?.collect::<Result<Vec<u32>, _>>()?;
+    }
+
+    let results_visible = !poll.hide_results || poll.closed || !poll.my_votes.is_empty();
+
+    let mut stmt = conn.prepare("SELECT o.id, o.text, (SELECT count(1) FROM POLL_VOTE v WHERE v.thread_id = o.thread_id AND v.option_id = o.id) \
+        FROM POLL_OPTION o WHERE o.thread_id = ? ORDER BY o.id")?;
+    poll.options = stmt.query_map([thread_id], |row| Ok(PollOption {
+        id: row.get(0)?,
+        text: row.get(1)?,
+        votes: if results_visible { Some(row.get(2)?) } else { None },
+        voters: None,
+    }))?.collect::<Result<Vec<PollOption>, _>>()?;
+
+    if results_visible {
+        poll.total_voters = Some(conn.query_row("SELECT count(DISTINCT user_id) FROM POLL_VOTE WHERE thread_id = ?", [thread_id], |row| row.get(0))?);
+
+        if !poll.anonymous {
+            let mut stmt = conn.prepare("SELECT u.id, u.name, u.display_name, u.avatar_file_id FROM POLL_VOTE v INNER JOIN USER u ON u.id = v.user_id \
+                WHERE v.thread_id = ? AND v.option_id = ? ORDER BY v.create_time")?;
+            for option in &mut poll.options {
+                option.voters = Some(stmt.query_map(params![thread_id, option.id], |row| extract_user(row, 0))?
+                    .collect::<Result<Vec<User>, _>>()?);
+            }
+        }
+    }
+
+    Ok(Some(poll))
+}
+
+// Replaces the votes of the user, no options withdraws the vote.
+// The poll is checked in the same transaction, so no vote gets in after it closes.
+pub fn save_poll_vote(conn: &mut DbConnection, thread_id: u32, option_ids: &[u32], user: &User) -> QueryResult<()> {
+    let transaction = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+    check_thread_visible(&transaction, thread_id, &Some(user.clone()))?;
+
+    let locked: bool = transaction.query_row("SELECT locked FROM THREAD WHERE id = ?", [thread_id], |row| row.get(0))?;
+
+    if locked {
+        return Err(LogicError::ThreadLocked.into());
+    }
+
+    let poll = query_poll(&transaction, thread_id, &None)?.ok_or(LogicError::PollNotFound)?;
+
+    if poll.closed {
+        return Err(LogicError::PollClosed.into());
+    }
+
+    let mut option_ids = option_ids.to_vec();
+    option_ids.sort_unstable();
+    option_ids.dedup();
+
+    if (!poll.multiple && option_ids.len() > 1) || option_ids.iter().any(|id| !poll.options.iter().any(|o| o.id == *id)) {
+        return Err(LogicError::InvalidPollVote.into());
+    }
+
+    let t = chrono::offset::Utc::now();
+    let t = t.timestamp_millis();
+
+    transaction.execute("DELETE FROM POLL_VOTE WHERE thread_id = ? AND user_id = ?", params![thread_id, user.id])?;
+    for option_id in option_ids {
+        transaction.execute("INSERT INTO POLL_VOTE(thread_id, option_id, user_id, create_time) VALUES(?, ?, ?, ?)",
+                            params![thread_id, option_id, user.id, t])?;
+    }
+
+    transaction.commit()?;
+    Ok(())
+}
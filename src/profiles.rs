@@ -0,0 +1,185 @@
+use rusqlite::{Connection, OptionalExtension, params};
+use crate::queries::{extract_thread, extract_user, get_threads_sql, QueryResult};
+use crate::structs::{ArticleInfo, Articles, LogicError, MessageSearchResult, MessageSearchResults, ProfileComment, ProfileComments, Thread, UpdateProfile, User, UserProfile};
+
+pub const PROFILE_ITEMS_PER_PAGE: u32 = 20;
+pub const MAX_DISPLAY_NAME_LENGTH: usize = 64;
+pub const MAX_BIO_LENGTH: usize = 2000;
+
+// IANA names like Europe/Moscow or America/Argentina/Buenos_Aires, and UTC
+pub fn is_valid_timezone(timezone: &str) -> bool {
+    timezone.len() <= 64
+        && timezone.split('/').all(|part| !part.is_empty()
+            && part.starts_with(|c: char| c.is_ascii_alphabetic())
+            && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '+'))
+}
+
+// BCP 47 tags like en, ru-RU or zh-Hant-TW
+pub fn is_valid_locale(locale: &str) -> bool {
+    let mut parts = locale.split('-');
+    let language = parts.next().unwrap_or("");
+
+    (2..=3).contains(&language.len())
+        && language.chars().all(|c| c.is_ascii_alphabetic())
+        && parts.all(|part| (2..=8).contains(&part.len()) && part.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+fn trimmed(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim)
+}
+
+// Value of the field being set, empty strings clear the field
+fn non_empty(value: Option<&str>) -> Option<&str> {
+    value.filter(|value| !value.is_empty())
+}
+
+fn offset_clause(page: u32) -> String {
+    let offset = if page > 1 { (page - 1) * PROFILE_ITEMS_PER_PAGE } else { 0 };
+    format!("LIMIT {} OFFSET {}", PROFILE_ITEMS_PER_PAGE, offset)
+}
+
+pub fn query_profile(conn: &Connection, user_id: u32) -> QueryResult<UserProfile> {
+    let profile = conn.query_row("SELECT id, name, display_name, avatar_file_id, bio, timezone, locale FROM USER WHERE id = ?", [user_id],
+                                 |row| Ok(UserProfile {
+                                     user: extract_user(row, 0)?,
+                                     bio: row.get(4)?,
+                                     timezone: row.get(5)?,
+                                     locale: row.get(6)?,
+                                 })).optional()?;
+
+    Ok(profile.ok_or(LogicError::UserNotFound)?)
+}
+
+pub fn update_profile(conn: &Connection, profile: &UpdateProfile, user: &User) -> QueryResult<()> {
+    let display_name = trimmed(&profile.display_name);
+    let avatar_file_id = trimmed(&profile.avatar_file_id);
+    let bio = trimmed(&profile.bio);
+    let timezone = trimmed(&profile.timezone);
+    let locale = trimmed(&profile.locale);
+
+    if non_empty(display_name).is_some_and(|name| name.chars().count() > MAX_DISPLAY_NAME_LENGTH)
+        || non_empty(bio).is_some_and(|bio| bio.chars().count() > MAX_BIO_LENGTH)
+        || non_empty(timezone).is_some_and(|timezone| !is_valid_timezone(timezone))
+        || non_empty(locale).is_some_and(|locale| !is_valid_locale(locale)) {
+        return Err(LogicError::InvalidProfile.into());
+    }
+
+    // the avatar has to be an image uploaded by the same user
+    if let Some(file_id) = non_empty(avatar_file_id) {
+        let image: bool = conn.query_row("SELECT EXISTS(SELECT 1 FROM FILES WHERE id = ? AND user_id = ? AND mime LIKE 'image/%')",
+                                         params![file_id, user.id], |row| row.get(0))?;
+        if !image {
+            return Err(LogicError::InvalidProfile.into());
+        }
+    }
+
+    // fields missing in the request are kept
+    conn.execute("UPDATE USER SET \
+        display_name = CASE WHEN ?1 IS NULL THEN display_name ELSE NULLIF(?1, '') END, \
+        avatar_file_id = CASE WHEN ?2 IS NULL THEN avatar_file_id ELSE NULLIF(?2, '') END, \
+        bio = CASE WHEN ?3 IS NULL THEN bio ELSE NULLIF(?3, '') END, \
+        timezone = CASE WHEN ?4 IS NULL THEN timezone ELSE NULLIF(?4, '') END, \
+        locale = CASE WHEN ?5 IS NULL THEN locale ELSE NULLIF(?5, '') END \
+        WHERE id = ?6",
+                 params![display_name, avatar_file_id, bio, timezone, locale, user.id])?;
+
+    Ok(())
+}
+
+// Threads started by the user, recently active first
+pub fn query_user_threads(conn: &Connection, user_id: u32, page: u32) -> QueryResult<Vec<Thread>> {
+    let sql = get_threads_sql("WHERE t.deleted = FALSE AND t.publish_time IS NULL AND t.author_id = ?", "FALSE", &offset_clause(page), "");
+    let mut stmt = conn.prepare(&sql)?;
+
+    let mut rows = stmt.query([user_id])?;
+    let mut threads = Vec::new();
+    while let Some(row) = rows.next()? {
+        threads.push(extract_thread(row)?);
+    }
+
+    Ok(threads)
+}
+
+pub fn query_user_messages(conn: &Connection, user_id: u32, page: u32) -> QueryResult<MessageSearchResults> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT m.id, m.thread_id, tn.name, m.create_time, u.id, u.name, u.display_name, u.avatar_file_id, mc.content \
+        FROM MESSAGE m INDEXED BY MESSAGE_USER_INX \
+        INNER JOIN USER u ON u.id = m.user_id \
+        INNER JOIN MESSAGE_CONTENT_FTS mc ON mc.rowid = m.content_id \
+        INNER JOIN THREAD t ON t.id = m.thread_id AND t.deleted = FALSE AND t.publish_time IS NULL \
+        INNER JOIN THREAD_NAME_FTS tn ON tn.rowid = t.name_id \
+        WHERE m.user_id = ? ORDER BY m.create_time DESC {}", offset_clause(page)),
+    )?;
+
+    let messages = stmt.query_map([user_id], |row| Ok(MessageSearchResult {
+        id: row.get(0)?,
+        thread_id: row.get(1)?,
+        thread_name: row.get(2)?,
+        create_time: row.get(3)?,
+        user: extract_user(row, 4)?,
+        content: row.get(8)?,
+    }))?.collect::<Result<Vec<MessageSearchResult>, _>>()?;
+
+    Ok(MessageSearchResults{messages})
+}
+
+// Public articles created by the user, newest first
+pub fn query_user_articles(conn: &Connection, user_id: u32, page: u32) -> QueryResult<Articles> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT a.id, a.path, c.name FROM ARTICLE a \
+        INNER JOIN ARTICLE_CONTENT_FTS c ON c.rowid = a.content_id \
+        INNER JOIN ARTICLE f ON f.id = a.id AND f.version = 1 \
+        WHERE a.active = TRUE AND a.visibility = 'public' AND f.user_id = ? \
+        ORDER BY f.create_time DESC {}", offset_clause(page)),
+    )?;
+
+    let articles = stmt.query_map([user_id], |row| Ok(ArticleInfo {
+        id: row.get(0)?,
+        path: row.get(1)?,
+        name: row.get(2)?,
+    }))?.collect::<Result<Vec<ArticleInfo>, _>>()?;
+
+    Ok(Articles{articles})
+}
+
+// Comments of the user on public articles, newest first
+pub fn query_user_comments(conn: &Connection, user_id: u32, page: u32) -> QueryResult<ProfileComments> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT m.id, a.id, a.path, c.name, m.create_time, mc.content FROM ARTICLE_COMMENT m \
+        INNER JOIN ARTICLE_COMMENT_CONTENT_FTS mc ON mc.rowid = m.content_id \
+        INNER JOIN ARTICLE a ON a.id = m.article_id AND a.active = TRUE AND a.visibility = 'public' \
+        INNER JOIN ARTICLE_CONTENT_FTS c ON c.rowid = a.content_id \
+        WHERE m.user_id = ? ORDER BY m.create_time DESC {}", offset_clause(page)),
+    )?;
+
+    let comments = stmt.query_map([user_id], |row| Ok(ProfileComment {
+        id: row.get(0)?,
+        article: ArticleInfo {
+            id: row.get(1)?,
+            path: row.get(2)?,
+            name: row.get(3)?,
+        },
+        create_time: row.get(4)?,
+        content: row.get(5)?,
+    }))?.collect::<Result<Vec<ProfileComment>, _>>()?;
+
+    Ok(ProfileComments{comments})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timezones_and_locales_are_validated() {
+        assert!(is_valid_timezone("UTC"));
+        assert!(is_valid_timezone("America/Argentina/Buenos_Aires"));
+        assert!(!is_valid_timezone("Europe//Moscow"));
+        assert!(!is_valid_timezone("../etc"));
+
+        assert!(is_valid_locale("en"));
+        assert!(is_valid_locale("zh-Hant-TW"));
+        assert!(!is_valid_locale("english"));
+        assert!(!is_valid_locale("ru_RU"));
+    }
+}
@@ -1,16 +1,31 @@
-use chrono::{Datelike, Timelike, Utc};
+use std::collections::{BTreeMap, HashMap};
+use chrono::{DateTime, Datelike, Timelike, Utc};
 use r2d2::PooledConnection;
 use r2d2_sqlite::SqliteConnectionManager;
-use rusqlite::{OptionalExtension, params, Row, TransactionBehavior};
+use rusqlite::{Connection, OptionalExtension, params, params_from_iter, Row, TransactionBehavior};
+use rusqlite::types::Value;
 use crate::{ForumStructure, Message, PostMessage, Section, SubSection, Thread, ThreadQuery, ThreadsQueryType, UploadedFile, User};
-use pbkdf2::{
+use argon2::{
     password_hash::{
         PasswordHash,
-        PasswordHasher, PasswordVerifier, rand_core::OsRng, SaltString
+        PasswordHasher, rand_core::OsRng, SaltString
     },
-    Pbkdf2
+    Algorithm as Argon2Algorithm,
+    Argon2,
+    Params as Argon2Params,
+    Version as Argon2Version
 };
-use crate::structs::{Article, ArticleInfo, Articles, ArticleSearchParams, ArticleSearchResult, ArticleSearchResults, ArticleVersion, ArticleVisibility, Comment, CommentsQueryResult, CreateThreadMessage, LogicError, MessageSearchParams, MessageSearchResult, MessageSearchResults, PostComment, SearchResultFragment, UpdateComment, UpdateMessage, UploadedFileWithLocation, UserSession, UserSessions};
+use pbkdf2::Pbkdf2;
+use crate::mentions::{MentionSource, notify_mentions, resolve_mentions};
+use crate::reactions::{query_reactions, ReactionTarget};
+use crate::polls::create_poll;
+use crate::drafts::{delete_draft, DraftContext};
+use crate::scheduling::scheduled_time;
+use crate::tags::{delete_unused_tags, parse_tags, query_article_tags, query_thread_tags, set_article_tags, set_thread_tags, tags_filter_clause};
+use serde_json::json;
+use crate::webhooks::enqueue_webhook_event;
+use crate::notifications::{notify_article_subscribers, notify_thread_subscribers, subscribe};
+use crate::structs::{Article, ArticleAttachment, ArticleAttachments, ArticleInfo, ArticleTemplate, ArticleTemplates, CreateArticle, SaveArticleTemplate, Articles, ArticleSearchParams, ArticleSearchResult, ArticleSearchResults, ArticleVersion, ArticleVisibility, BrokenLink, BrokenLinks, Comment, CommentsQueryResult, CreateThreadMessage, DeletedArticle, LogicError, MarkThreadsRead, NotificationKind, SubscriptionTarget, MessageSearchParams, WebhookEvent, MessageSearchResult, MessageSearchResults, PostComment, SearchResultFragment, UpdateArticle, UpdateComment, UpdateMessage, UpdateThreadFlags, UploadedFileWithLocation, MessagePreview, MessageReplies, MergeThreads, SplitThread, MessageLocation, CommentLocation, DeletedSections, DeletedThreadsPolicy, DeleteSection, DeleteSubsection, UserSession, UserSessions};
 
 pub type DbConnection = PooledConnection<SqliteConnectionManager>;
 pub type QueryResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
@@ -21,86 +36,110 @@ pub const MESSAGES_PER_PAGE: u32 = 50;
 
 pub fn query_forum_structure(conn: &DbConnection) -> QueryResult<ForumStructure> {
     let mut stmt = conn.prepare(
-        "SELECT id, name FROM SECTION WHERE deleted = FALSE",
+        "SELECT id, name, sort_order FROM SECTION WHERE deleted = FALSE ORDER BY sort_order, id",
     )?;
 
-    let mut sections = Vec::new();
-    let mut rows = stmt.query([])?;
-
-    while let Some(row) = rows.next()? {
-        sections.push(Section{
-            id: row.get(0)?,
-            name: row.get(1)?,
-        });
-    }
+    let sections = stmt.query_map([], extract_section)?.collect::<Result<Vec<Section>, _>>()?;
 
     let mut stmt = conn.prepare(
-        "SELECT id, section_id, name FROM SUBSECTION WHERE deleted = FALSE AND (SELECT deleted FROM SECTION WHERE SUBSECTION.section_id = SECTION.id) = FALSE",
+        "SELECT id, section_id, name, description, sort_order FROM SUBSECTION \
+        WHERE deleted = FALSE AND (SELECT deleted FROM SECTION WHERE SUBSECTION.section_id = SECTION.id) = FALSE ORDER BY sort_order, id",
     )?;
 
-    let mut subsections = Vec::new();
-    let mut rows = stmt.query([])?;
-
-    while let Some(row) = rows.next()? {
-        subsections.push(SubSection{
-            id: row.get(0)?,
-            section_id: row.get(1)?,
-            name: row.get(2)?,
-        });
-    }
+    let subsections = stmt.query_map([], extract_subsection)?.collect::<Result<Vec<SubSection>, _>>()?;
 
     Ok(ForumStructure{sections, subsections, threads_per_page: THREADS_PER_PAGE, messages_per_page: MESSAGES_PER_PAGE})
 }
 
-fn get_threads_sql(where_clause: &str, limit_clause: &str, offset_clause: &str) -> String {
-    return format!("SELECT c.*, m2.create_time, u2.id, u2.name FROM (
-SELECT t.id, t.subsection_id, tn.name, t.author_id, u.name, t.create_time,
+// id, name, display name and avatar file id of the user starting from the column index
+pub fn extract_user(row: &Row, index: usize) -> rusqlite::Result<User> {
+    let avatar_file_id: Option<String> = row.get(index + 3)?;
+
+    Ok(User {
+        id: row.get(index)?,
+        name: row.get(index + 1)?,
+        display_name: row.get(index + 2)?,
+        avatar_url: avatar_file_id.map(|id| format!("/files/{}", id)),
+    })
+}
+
+pub fn get_threads_sql(where_clause: &str, pin_clause: &str, limit_clause: &str, offset_clause: &str) -> String {
+    return format!("SELECT c.*, m2.create_time, u2.id, u2.name, u2.display_name, u2.avatar_file_id FROM (
+SELECT t.id, t.subsection_id, tn.name, t.author_id, u.name, u.display_name, u.avatar_file_id, t.create_time,
 (SELECT count(1) FROM MESSAGE m WHERE m.thread_id = t.id) as message_count,
 (SELECT max(id) FROM MESSAGE m WHERE m.thread_id = t.id) as last_message_id,
-t.update_time
+t.update_time, t.pinned, t.locked, t.announcement, t.publish_time
 FROM THREAD t INNER JOIN USER u on t.author_id = u.id INNER JOIN THREAD_NAME_FTS tn ON tn.rowid = t.name_id
 {}
 ) c INNER JOIN MESSAGE m2 ON m2.id = c.last_message_id AND m2.thread_id = c.id
 INNER JOIN USER u2 on m2.user_id = u2.id
-order by c.update_time DESC
- {} {}", where_clause, limit_clause, offset_clause);
+order by ({}) DESC, c.update_time DESC
+ {} {}", where_clause, pin_clause, limit_clause, offset_clause);
 }
 
-fn extract_thread(row: &Row) -> Result<Thread, Box<dyn std::error::Error + Send + Sync>> {
+pub fn extract_thread(row: &Row) -> Result<Thread, Box<dyn std::error::Error + Send + Sync>> {
     Ok(Thread {
         id: row.get(0)?,
         sub_section_id: row.get(1)?,
         name: row.get(2)?,
-        author: User {
-            id: row.get(3)?,
-            name: row.get(4)?,
-        },
-        creation_date_time: row.get(5)?,
-        total_messages: row.get(6)?,
-        last_message_id: row.get(7)?,
-        last_message_date_time: row.get(9)?,
-        last_message_user: User {
-            id: row.get(10)?,
-            name: row.get(11)?,
-        },
+        author: extract_user(row, 3)?,
+        creation_date_time: row.get(7)?,
+        total_messages: row.get(8)?,
+        last_message_id: row.get(9)?,
+        pinned: row.get(11)?,
+        locked: row.get(12)?,
+        announcement: row.get(13)?,
+        publish_at: row.get(14)?,
+        last_message_date_time: row.get(15)?,
+        last_message_user: extract_user(row, 16)?,
+        unread_count: None,
+        first_unread_message_id: None,
+        first_unread_page: None,
+        tags: Vec::new(),
     })
 }
 
-pub fn get_thread_where_clause(params: &ThreadQuery) -> &str {
-    return match params.query_type {
-        ThreadsQueryType::All => "WHERE deleted = FALSE",
-        ThreadsQueryType::Section => "WHERE deleted = FALSE AND t.subsection_id IN (SELECT ID FROM SUBSECTION WHERE section_id = ?)",
-        ThreadsQueryType::SubSection => "WHERE deleted = FALSE AND t.subsection_id = ?"
+pub fn get_thread_where_clause(query_type: &ThreadsQueryType) -> &str {
+    return match query_type {
+        ThreadsQueryType::All => "WHERE deleted = FALSE AND t.publish_time IS NULL",
+        ThreadsQueryType::Section => "WHERE deleted = FALSE AND t.publish_time IS NULL AND (t.subsection_id IN (SELECT ID FROM SUBSECTION WHERE section_id = ?) OR t.announcement = TRUE)",
+        ThreadsQueryType::SubSection => "WHERE deleted = FALSE AND t.publish_time IS NULL AND (t.subsection_id = ? OR t.announcement = TRUE)"
     };
 }
 
-pub fn query_threads(conn: &DbConnection, params: &ThreadQuery) -> QueryResult<Vec<Thread>> {
-    let where_clause = get_thread_where_clause(params);
+// Announcements are on top of every list, pinned threads on top of their subsection or of all lists
+fn get_thread_pin_clause(query_type: &ThreadsQueryType) -> &str {
+    match query_type {
+        ThreadsQueryType::All | ThreadsQueryType::Section => "c.announcement OR c.pinned = 2",
+        ThreadsQueryType::SubSection => "c.announcement OR c.pinned > 0"
+    }
+}
+
+// Where clause of the query with the tag filter and its parameters
+fn get_thread_query_filter(params: &ThreadQuery) -> (String, Vec<Value>) {
+    let mut where_clause = get_thread_where_clause(&params.query_type).to_string();
+    let mut values = Vec::new();
+
+    if !matches!(params.query_type, ThreadsQueryType::All) {
+        values.push(Value::from(params.id));
+    }
+
+    let tags = parse_tags(&params.tags);
+    if !tags.is_empty() {
+        where_clause.push_str(&format!(" AND {}", tags_filter_clause("t.id", "THREAD_TAG", "thread_id", tags.len())));
+        values.extend(tags.into_iter().map(Value::from));
+    }
+
+    (where_clause, values)
+}
+
+pub fn query_threads(conn: &DbConnection, params: &ThreadQuery, user: &Option<User>) -> QueryResult<Vec<Thread>> {
+    let (where_clause, values) = get_thread_query_filter(params);
 
     let limit_clause = format!(" LIMIT {}", THREADS_PER_PAGE);
     let offset_clause = if params.page > 1 {format!(" OFFSET {}", (params.page - 1) * THREADS_PER_PAGE)} else { "".to_string() };
 
-    let sql = get_threads_sql(where_clause, &limit_clause, &offset_clause);
+    let sql = get_threads_sql(&where_clause, get_thread_pin_clause(&params.query_type), &limit_clause, &offset_clause);
 
     let mut stmt = conn.prepare(
         &sql,
@@ -108,18 +147,78 @@ pub fn query_threads(conn: &DbConnection, params: &ThreadQuery) -> QueryResult<V
 
     let mut threads = Vec::new();
 
-    let mut rows = if let ThreadsQueryType::All = params.query_type { stmt.query([])? } else { stmt.query([params.id])? };
+    let mut rows = stmt.query(params_from_iter(values))?;
 
     while let Some(row) = rows.next()? {
-        let thread = extract_thread(row)?;
+        let mut thread = extract_thread(row)?;
+        thread.tags = query_thread_tags(conn, thread.id)?;
+        if let Some(user) = user {
+            fill_unread_info(conn, &mut thread, user)?;
+        }
         threads.push(thread);
     }
 
     Ok(threads)
 }
 
+pub fn fill_unread_info(conn: &DbConnection, thread: &mut Thread, user: &User) -> QueryResult<()> {
+    let last_read: u32 = conn.query_row("SELECT last_read_message_id FROM THREAD_READ WHERE user_id = ? AND thread_id = ?",
+                                        params![user.id, thread.id], |row| row.get(0)).optional()?.unwrap_or(0);
+
+    let (unread_count, first_unread_message_id): (u32, Option<u32>) = conn.query_row(
+        "SELECT count(1), min(id) FROM MESSAGE WHERE thread_id = ? AND id > ?",
+        params![thread.id, last_read], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+    thread.unread_count = Some(unread_count);
+    thread.first_unread_message_id = first_unread_message_id;
+    thread.first_unread_page = match first_unread_message_id {
+        Some(id) => Some(query_message_page(conn, thread.id, id)?),
+        None => None
+    };
+
+    Ok(())
+}
+
+pub fn query_message_page(conn: &Connection, thread_id: u32, message_id: u32) -> QueryResult<u32> {
+    let position: u32 = conn.query_row("SELECT count(1) FROM MESSAGE WHERE thread_id = ? AND id < ?",
+                                       params![thread_id, message_id], |row| row.get(0))?;
+
+    Ok(position / MESSAGES_PER_PAGE + 1)
+}
+
+pub fn mark_thread_read(conn: &DbConnection, user: &User, thread_id: u32, message_id: Option<u32>) -> QueryResult<()> {
+    let message_id: Option<u32> = match message_id {
+        Some(id) => Some(id),
+        None => conn.query_row("SELECT max(id) FROM MESSAGE WHERE thread_id = ?", [thread_id], |row| row.get(0))?
+    };
+
+    let message_id = message_id.ok_or(LogicError::ThreadNotFound)?;
+
+    conn.execute("INSERT INTO THREAD_READ(user_id, thread_id, last_read_message_id) VALUES(?, ?, ?) \
+        ON CONFLICT(user_id, thread_id) DO UPDATE SET last_read_message_id = max(last_read_message_id, excluded.last_read_message_id)",
+                 params![user.id, thread_id, message_id])?;
+
+    Ok(())
+}
+
+pub fn mark_threads_read(conn: &DbConnection, user: &User, params: &MarkThreadsRead) -> QueryResult<()> {
+    let sql = format!("INSERT INTO THREAD_READ(user_id, thread_id, last_read_message_id) \
+        SELECT ?, t.id, (SELECT max(id) FROM MESSAGE m WHERE m.thread_id = t.id) FROM THREAD t {} \
+        ON CONFLICT(user_id, thread_id) DO UPDATE SET last_read_message_id = max(last_read_message_id, excluded.last_read_message_id)",
+                      get_thread_where_clause(&params.query_type));
+
+    if let ThreadsQueryType::All = params.query_type {
+        conn.execute(&sql, [user.id])?;
+    }
+    else {
+        conn.execute(&sql, params![user.id, params.id])?;
+    }
+
+    Ok(())
+}
+
 pub fn query_thread_count(conn: &DbConnection, params: &ThreadQuery) -> QueryResult<u32> {
-    let where_clause = get_thread_where_clause(params);
+    let (where_clause, values) = get_thread_query_filter(params);
 
     let sql = format!("SELECT count(1) FROM THREAD t {}", where_clause);
 
@@ -127,13 +226,30 @@ pub fn query_thread_count(conn: &DbConnection, params: &ThreadQuery) -> QueryRes
         &sql,
     )?;
 
-    let count: u32 = if let ThreadsQueryType::All = params.query_type { stmt.query_row([], |rs| rs.get(0))? } else { stmt.query_row([params.id], |rs| rs.get(0))? };
+    let count: u32 = stmt.query_row(params_from_iter(values), |rs| rs.get(0))?;
 
     Ok(count)
 }
 
+// Deleted threads are hidden from everyone, scheduled ones from everyone except the author
+pub fn is_thread_visible(conn: &Connection, thread_id: u32, user: &Option<User>) -> QueryResult<bool> {
+    let user_id = user.as_ref().map_or(0, |user| user.id);
+    let visible = conn.query_row("SELECT EXISTS(SELECT 1 FROM THREAD WHERE id = ? AND deleted = FALSE AND (publish_time IS NULL OR author_id = ?))",
+                                 params![thread_id, user_id], |row| row.get(0))?;
+
+    Ok(visible)
+}
+
+pub fn check_thread_visible(conn: &Connection, thread_id: u32, user: &Option<User>) -> QueryResult<()> {
+    if !is_thread_visible(conn, thread_id, user)? {
+        return Err(LogicError::ThreadNotFound.into());
+    }
+
+    Ok(())
+}
+
 pub fn query_thread(conn: &DbConnection, thread_id: u32) -> QueryResult<Option<Thread>> {
-    let sql = get_threads_sql(" WHERE t.id = ? ", "", "");
+    let sql = get_threads_sql(" WHERE t.id = ? ", "FALSE", "", "");
 
     let mut stmt = conn.prepare(
         &sql,
@@ -142,18 +258,20 @@ pub fn query_thread(conn: &DbConnection, thread_id: u32) -> QueryResult<Option<T
     let mut rows = stmt.query([thread_id])?;
 
     if let Some(row) = rows.next()? {
-        return Ok(Some(extract_thread(row)?));
+        let mut thread = extract_thread(row)?;
+        thread.tags = query_thread_tags(conn, thread.id)?;
+        return Ok(Some(thread));
     }
 
     return Ok(None);
 }
 
-pub fn query_thread_messages(conn: &DbConnection, thread_id: u32, page: u32) -> QueryResult<Vec<Message>> {
+pub fn query_thread_messages(conn: &DbConnection, thread_id: u32, page: u32, user: &Option<User>) -> QueryResult<Vec<Message>> {
     let limit_clause = format!(" LIMIT {}", MESSAGES_PER_PAGE);
     let offset_clause = if page > 1 {format!(" OFFSET {}", (page - 1) * MESSAGES_PER_PAGE)} else { "".to_string() };
 
     let sql = format!("SELECT * FROM (
-SELECT m.id, m.user_id, u.name, m.create_time, m.update_time, mc.content
+SELECT m.id, m.user_id, u.name, u.display_name, u.avatar_file_id, m.create_time, m.update_time, mc.content, m.reply_to
 FROM MESSAGE m
 INNER JOIN USER u ON u.id = m.user_id
 INNER JOIN MESSAGE_CONTENT_FTS mc ON mc.rowid = m.content_id
@@ -171,16 +289,22 @@ ORDER BY m.id
     let mut messages = Vec::new();
 
     while let Some(row) = rows.next()? {
+        let content: String = row.get(7)?;
+        let id = row.get(0)?;
+        let reply_to: Option<u32> = row.get(8)?;
         let message = Message {
-            id: row.get(0)?,
-            user: User {
-                id: row.get(1)?,
-                name: row.get(2)?,
+            id,
+            user: extract_user(row, 1)?,
+            thread_id,
+            create_time: row.get(5)?,
+            update_time: row.get(6)?,
+            mentions: resolve_mentions(conn, &content)?,
+            reactions: query_reactions(conn, ReactionTarget::Message{thread_id, message_id: id}, user)?,
+            reply_to: match reply_to {
+                Some(reply_to) => query_message_preview(conn, thread_id, reply_to)?,
+                None => None
             },
-            thread_id: thread_id,
-            create_time: row.get(3)?,
-            update_time: row.get(4)?,
-            content: row.get(5)?,
+            content,
         };
 
         messages.push(message);
@@ -194,7 +318,7 @@ pub fn query_article_comments(conn: &DbConnection, article_id: u32, page: u32, u
     let offset_clause = if page > 1 {format!(" OFFSET {}", (page - 1) * MESSAGES_PER_PAGE)} else { "".to_string() };
 
     let sql = format!("SELECT * FROM (
-SELECT m.id, m.user_id, u.name, m.create_time, m.update_time, mc.content
+SELECT m.id, m.user_id, u.name, u.display_name, u.avatar_file_id, m.create_time, m.update_time, mc.content
 FROM ARTICLE_COMMENT m
 INNER JOIN USER u ON u.id = m.user_id
 INNER JOIN ARTICLE_COMMENT_CONTENT_FTS mc ON mc.rowid = m.content_id
@@ -212,16 +336,17 @@ ORDER BY m.id
     let mut comments = Vec::new();
 
     while let Some(row) = rows.next()? {
+        let content: String = row.get(7)?;
+        let id = row.get(0)?;
         let message = Comment {
-            id: row.get(0)?,
-            user: User {
-                id: row.get(1)?,
-                name: row.get(2)?,
-            },
+            id,
+            user: extract_user(row, 1)?,
             acticle_id: article_id,
-            create_time: row.get(3)?,
-            update_time: row.get(4)?,
-            content: row.get(5)?,
+            create_time: row.get(5)?,
+            update_time: row.get(6)?,
+            mentions: resolve_mentions(conn, &content)?,
+            reactions: query_reactions(conn, ReactionTarget::Comment{article_id, comment_id: id}, user)?,
+            content,
         };
 
         comments.push(message);
@@ -258,9 +383,27 @@ ORDER BY m.id
     Err(LogicError::ArticleNotFound.into())
 }
 
+fn extract_section(row: &Row) -> rusqlite::Result<Section> {
+    Ok(Section {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        sort_order: row.get(2)?,
+    })
+}
+
+fn extract_subsection(row: &Row) -> rusqlite::Result<SubSection> {
+    Ok(SubSection {
+        id: row.get(0)?,
+        section_id: row.get(1)?,
+        name: row.get(2)?,
+        description: row.get(3)?,
+        sort_order: row.get(4)?,
+    })
+}
+
 pub fn create_section(conn: &DbConnection, section_name: &String) -> QueryResult<()> {
     let mut stmt = conn.prepare(
-        "INSERT INTO SECTION(name) VALUES(?)",
+        "INSERT INTO SECTION(name, sort_order) VALUES(?, (SELECT COALESCE(max(sort_order), 0) + 1 FROM SECTION))",
     )?;
 
     let count = stmt.execute([section_name])?;
@@ -271,12 +414,13 @@ pub fn create_section(conn: &DbConnection, section_name: &String) -> QueryResult
     return Ok(());
 }
 
-pub fn create_sub_section(conn: &DbConnection, section_id: u32, section_name: &String) -> QueryResult<()> {
+pub fn create_sub_section(conn: &DbConnection, section_id: u32, section_name: &String, description: &str) -> QueryResult<()> {
     let mut stmt = conn.prepare(
-        "INSERT INTO SUBSECTION(section_id, name) VALUES(?, ?)",
+        "INSERT INTO SUBSECTION(section_id, name, description, sort_order) \
+        VALUES(?1, ?2, ?3, (SELECT COALESCE(max(sort_order), 0) + 1 FROM SUBSECTION WHERE section_id = ?1))",
     )?;
 
-    let count = stmt.execute(params![section_id, section_name])?;
+    let count = stmt.execute(params![section_id, section_name, description])?;
     if count == 0 {
         return Err(LogicError::CreateError.into());
     }
@@ -310,6 +454,145 @@ pub fn rename_subsection(conn: &DbConnection, subsection_id: u32, section_name:
     return Ok(());
 }
 
+pub fn update_subsection_description(conn: &DbConnection, subsection_id: u32, description: &str) -> QueryResult<()> {
+    let count = conn.execute("UPDATE SUBSECTION SET description = ? WHERE id = ?", params![description, subsection_id])?;
+    if count == 0 {
+        return Err(LogicError::SubsectionNotFound.into());
+    }
+
+    Ok(())
+}
+
+pub fn reorder_sections(conn: &mut DbConnection, section_ids: &[u32]) -> QueryResult<()> {
+    let transaction = conn.transaction()?;
+
+    for (i, id) in section_ids.iter().enumerate() {
+        transaction.execute("UPDATE SECTION SET sort_order = ? WHERE id = ?", params![i as i32, id])?;
+    }
+
+    transaction.commit()?;
+    Ok(())
+}
+
+pub fn reorder_subsections(conn: &mut DbConnection, subsection_ids: &[u32]) -> QueryResult<()> {
+    let transaction = conn.transaction()?;
+
+    for (i, id) in subsection_ids.iter().enumerate() {
+        transaction.execute("UPDATE SUBSECTION SET sort_order = ? WHERE id = ?", params![i as i32, id])?;
+    }
+
+    transaction.commit()?;
+    Ok(())
+}
+
+// Applies the policy to the live threads of the subsections that are being deleted
+fn delete_subsection_threads(conn: &Connection, subsection_ids: &[u32], policy: DeletedThreadsPolicy, target_subsection_id: Option<u32>) -> QueryResult<()> {
+    match policy {
+        DeletedThreadsPolicy::Block => {
+            for id in subsection_ids {
+                let count: u32 = conn.query_row("SELECT count(1) FROM THREAD WHERE subsection_id = ? AND deleted = FALSE", [id], |row| row.get(0))?;
+                if count > 0 {
+                    return Err(LogicError::SectionNotEmpty.into());
+                }
+            }
+        }
+        DeletedThreadsPolicy::Move => {
+            let target_id = target_subsection_id.ok_or(LogicError::SubsectionNotFound)?;
+            let target_exists: bool = conn.query_row("SELECT count(1) FROM SUBSECTION s INNER JOIN SECTION c ON c.id = s.section_id \
+                WHERE s.id = ? AND s.deleted = FALSE AND c.deleted = FALSE", [target_id], |row| row.get(0))?;
+
+            if !target_exists || subsection_ids.contains(&target_id) {
+                return Err(LogicError::SubsectionNotFound.into());
+            }
+
+            for id in subsection_ids {
+                conn.execute("UPDATE THREAD SET subsection_id = ? WHERE subsection_id = ? AND deleted = FALSE", params![target_id, id])?;
+            }
+        }
+        DeletedThreadsPolicy::Delete => {
+            for id in subsection_ids {
+                conn.execute("UPDATE THREAD SET deleted = TRUE, cascade_deleted = TRUE WHERE subsection_id = ? AND deleted = FALSE", [id])?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn delete_section(conn: &mut DbConnection, params: &DeleteSection) -> QueryResult<()> {
+    let transaction = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+
+    let count = transaction.execute("UPDATE SECTION SET deleted = TRUE WHERE id = ? AND deleted = FALSE", [params.section_id])?;
+    if count == 0 {
+        return Err(LogicError::SectionNotFound.into());
+    }
+
+    let subsection_ids = {
+        let mut stmt = transaction.prepare("SELECT id FROM SUBSECTION WHERE section_id = ?")?;
+        let ids = stmt.query_map([params.section_id], |row| row.get(0))?.collect::<Result<Vec<u32>, _>>()?;
+        ids
+    };
+
+    delete_subsection_threads(&transaction, &subsection_ids, params.threads, params.target_subsection_id)?;
+
+    transaction.commit()?;
+    Ok(())
+}
+
+pub fn delete_subsection(conn: &mut DbConnection, params: &DeleteSubsection) -> QueryResult<()> {
+    let transaction = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+
+    let count = transaction.execute("UPDATE SUBSECTION SET deleted = TRUE WHERE id = ? AND deleted = FALSE", [params.subsection_id])?;
+    if count == 0 {
+        return Err(LogicError::SubsectionNotFound.into());
+    }
+
+    delete_subsection_threads(&transaction, &[params.subsection_id], params.threads, params.target_subsection_id)?;
+
+    transaction.commit()?;
+    Ok(())
+}
+
+// Threads deleted together with the section are restored, except the ones of its deleted subsections
+pub fn restore_section(conn: &mut DbConnection, section_id: u32) -> QueryResult<()> {
+    let transaction = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+
+    let count = transaction.execute("UPDATE SECTION SET deleted = FALSE WHERE id = ? AND deleted = TRUE", [section_id])?;
+    if count == 0 {
+        return Err(LogicError::SectionNotFound.into());
+    }
+
+    transaction.execute("UPDATE THREAD SET deleted = FALSE, cascade_deleted = FALSE \
+        WHERE cascade_deleted = TRUE AND subsection_id IN (SELECT id FROM SUBSECTION WHERE section_id = ? AND deleted = FALSE)", [section_id])?;
+
+    transaction.commit()?;
+    Ok(())
+}
+
+pub fn restore_subsection(conn: &mut DbConnection, subsection_id: u32) -> QueryResult<()> {
+    let transaction = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+
+    let count = transaction.execute("UPDATE SUBSECTION SET deleted = FALSE WHERE id = ? AND deleted = TRUE", [subsection_id])?;
+    if count == 0 {
+        return Err(LogicError::SubsectionNotFound.into());
+    }
+
+    transaction.execute("UPDATE THREAD SET deleted = FALSE, cascade_deleted = FALSE WHERE cascade_deleted = TRUE AND subsection_id = ?", [subsection_id])?;
+
+    transaction.commit()?;
+    Ok(())
+}
+
+pub fn query_deleted_sections(conn: &DbConnection) -> QueryResult<DeletedSections> {
+    let mut stmt = conn.prepare("SELECT id, name, sort_order FROM SECTION WHERE deleted = TRUE ORDER BY sort_order, id")?;
+    let sections = stmt.query_map([], extract_section)?.collect::<Result<Vec<Section>, _>>()?;
+
+    let mut stmt = conn.prepare("SELECT id, section_id, name, description, sort_order FROM SUBSECTION WHERE deleted = TRUE ORDER BY sort_order, id")?;
+    let subsections = stmt.query_map([], extract_subsection)?.collect::<Result<Vec<SubSection>, _>>()?;
+
+    Ok(DeletedSections{sections, subsections})
+}
+
 pub fn rename_thread(conn: &DbConnection, thread_id: u32, thread_name: &String) -> QueryResult<()> {
     let mut stmt = conn.prepare(
         "UPDATE THREAD_NAME_FTS SET name = ? WHERE rowid = (SELECT name_id FROM THREAD WHERE id = ?)",
@@ -351,22 +634,21 @@ pub fn move_subsection(conn: &DbConnection, subsection_id: u32, new_section_id:
 
 pub fn find_user(conn: &DbConnection, user_name: &str) -> QueryResult<Option<User>> {
     let mut stmt = conn.prepare(
-        "SELECT id, name FROM USER WHERE name = ?",
+        "SELECT id, name, display_name, avatar_file_id FROM USER WHERE name = ?",
     )?;
 
     let mut rows = stmt.query([user_name])?;
 
     if let Some(row) = rows.next()? {
-        return Ok(Some(User{
-            id: row.get(0)?,
-            name: row.get(1)?
-        }));
+        return Ok(Some(extract_user(row, 0)?));
     }
 
     return Ok(None);
 }
 
-pub fn verify_user_password(pass_conn: &DbConnection, user: &User, pass_hex: &str) -> QueryResult<bool> {
+// Accepts Argon2 and legacy PBKDF2 hashes. On success hashes made with another
+// algorithm or other Argon2 costs are rehashed with the current ones.
+pub fn verify_user_password(pass_conn: &DbConnection, user: &User, pass_hex: &str, hash_params: &Argon2Params) -> QueryResult<bool> {
     let decoded_pass = hex::decode(pass_hex);
     if !decoded_pass.is_ok() {
         return Ok(false);
@@ -388,10 +670,19 @@ pub fn verify_user_password(pass_conn: &DbConnection, user: &User, pass_hex: &st
             return Ok(false);
         }
 
-        if !Pbkdf2.verify_password(&decoded_pass, &parsed_hash.unwrap()).is_ok() {
+        let parsed_hash = parsed_hash.unwrap();
+        if parsed_hash.verify_password(&[&Argon2::default(), &Pbkdf2], &decoded_pass).is_err() {
             return Ok(false);
         }
 
+        let outdated = parsed_hash.algorithm != Argon2Algorithm::Argon2id.ident()
+            || !Argon2Params::try_from(&parsed_hash).is_ok_and(|params| params.m_cost() == hash_params.m_cost()
+                && params.t_cost() == hash_params.t_cost()
+                && params.p_cost() == hash_params.p_cost());
+        if outdated {
+            save_user_password(pass_conn, user, pass_hex, hash_params)?;
+        }
+
         return Ok(true);
     }
 
@@ -411,7 +702,8 @@ pub fn save_user(conn: &DbConnection, user_name: &str) -> QueryResult<()> {
     return Ok(());
 }
 
-pub fn save_user_password(pass_conn: &DbConnection, user: &User, pass_hex: &str) -> QueryResult<()> {
+// Hashes with Argon2id
+pub fn save_user_password(pass_conn: &DbConnection, user: &User, pass_hex: &str, hash_params: &Argon2Params) -> QueryResult<()> {
     let decoded_pass = hex::decode(pass_hex);
     if !decoded_pass.is_ok() {
         return Err(LogicError::InvalidPass.into());
@@ -420,7 +712,8 @@ pub fn save_user_password(pass_conn: &DbConnection, user: &User, pass_hex: &str)
     let decoded_pass = decoded_pass.unwrap();
 
     let salt = SaltString::generate(&mut OsRng);
-    let password_hash = Pbkdf2.hash_password(&decoded_pass, &salt);
+    let argon2 = Argon2::new(Argon2Algorithm::Argon2id, Argon2Version::V0x13, hash_params.clone());
+    let password_hash = argon2.hash_password(&decoded_pass, &salt);
     if !password_hash.is_ok() {
         return Err(LogicError::CreateError.into());
     }
@@ -444,16 +737,28 @@ pub fn save_user_session(user_sessions_conn: &DbConnection, user: &User, session
     return Ok(());
 }
 
-pub fn save_message(conn: &mut DbConnection, message: &PostMessage, user: &User) -> QueryResult<()> {
+pub fn save_message(conn: &mut DbConnection, message: &PostMessage, user: &User, admin: bool) -> QueryResult<u32> {
     let transaction = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
 
-    let seq: Option<u32> = transaction.query_row("SELECT message_seq FROM THREAD WHERE id = ?", [message.thread_id], |row| row.get(0)).optional()?;
+    check_thread_visible(&transaction, message.thread_id, &Some(user.clone()))?;
 
-    if seq.is_none() {
-        return Err(LogicError::ThreadNotFound.into());
+    let (seq, locked, publish_time): (u32, bool, Option<i64>) = transaction.query_row("SELECT message_seq, locked, publish_time FROM THREAD WHERE id = ?", [message.thread_id],
+                                                                                      |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+
+    // admins can still post in a thread they locked
+    if locked && !admin {
+        return Err(LogicError::ThreadLocked.into());
+    }
+
+    if let Some(reply_to) = message.reply_to {
+        let exists: bool = transaction.query_row("SELECT EXISTS(SELECT 1 FROM MESSAGE WHERE thread_id = ? AND id = ?)",
+                                                 params![message.thread_id, reply_to], |row| row.get(0))?;
+        if !exists {
+            return Err(LogicError::InvalidReplyTo.into());
+        }
     }
 
-    let seq = seq.unwrap() + 1;
+    let seq = seq + 1;
     let t = chrono::offset::Utc::now();
     let t = t.timestamp_millis();
 
@@ -462,17 +767,28 @@ pub fn save_message(conn: &mut DbConnection, message: &PostMessage, user: &User)
 
     let message_content_id = transaction.last_insert_rowid() as u32;
 
-    transaction.execute("INSERT INTO MESSAGE(id, user_id, thread_id, create_time, update_time, content_id) VALUES(?, ?, ?, ?, ?, ?)",
-                        params![seq, user.id, message.thread_id, t, t, message_content_id])?;
+    transaction.execute("INSERT INTO MESSAGE(id, user_id, thread_id, create_time, update_time, content_id, reply_to) VALUES(?, ?, ?, ?, ?, ?, ?)",
+                        params![seq, user.id, message.thread_id, t, t, message_content_id, message.reply_to])?;
 
     transaction.execute("UPDATE THREAD SET update_time = ?, message_seq = ? WHERE id = ?",
                         params![t, seq, message.thread_id])?;
 
+    transaction.execute("INSERT OR REPLACE INTO THREAD_READ(user_id, thread_id, last_read_message_id) VALUES(?, ?, ?)",
+                        params![user.id, message.thread_id, seq])?;
+
+    subscribe(&transaction, user.id, SubscriptionTarget::Thread, message.thread_id)?;
+    // messages of a scheduled thread are announced when it is published, see announce_thread
+    if publish_time.is_none() {
+        notify_thread_subscribers(&transaction, message.thread_id, NotificationKind::Message, seq, user)?;
+        announce_message(&transaction, message.thread_id, seq, message.reply_to, &message.message, user)?;
+    }
+    delete_draft(&transaction, &DraftContext::Thread(message.thread_id), user)?;
+
     transaction.commit()?;
-    return Ok(());
+    Ok(seq)
 }
 
-pub fn save_comment(conn: &mut DbConnection, message: &PostComment, user: &User) -> QueryResult<()> {
+pub fn save_comment(conn: &mut DbConnection, message: &PostComment, user: &User) -> QueryResult<u32> {
     let article = query_article(&conn, message.article_id, message.article_version, &Some(user.clone()))?;
 
     let transaction = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
@@ -491,15 +807,66 @@ pub fn save_comment(conn: &mut DbConnection, message: &PostComment, user: &User)
     transaction.execute("INSERT INTO ARTICLE_COMMENT(id, user_id, article_id, article_version, create_time, update_time, content_id) VALUES(?, ?, ?, ?, ?, ?, ?)",
                         params![seq, user.id, message.article_id, article.version, t, t, comment_content_id])?;
 
+    subscribe(&transaction, user.id, SubscriptionTarget::Article, message.article_id)?;
+    notify_article_subscribers(&transaction, message.article_id, NotificationKind::Comment, Some(seq), user)?;
+    notify_mentions(&transaction, MentionSource::Comment{article_id: message.article_id, comment_id: seq}, &message.message, user)?;
+    if matches!(article.visibility, ArticleVisibility::Public) {
+        enqueue_webhook_event(&transaction, WebhookEvent::CommentPosted, json!({
+            "articleId": message.article_id,
+            "commentId": seq,
+            "content": message.message,
+        }), user)?;
+    }
+
     transaction.commit()?;
-    return Ok(());
+    Ok(seq)
+}
+
+// Notifications, mentions and webhooks of a new thread, sent once it is published
+// Mentions and webhooks of a new message in a published thread
+fn announce_message(conn: &Connection, thread_id: u32, message_id: u32, reply_to: Option<u32>, content: &str, user: &User) -> QueryResult<()> {
+    notify_mentions(conn, MentionSource::Message{thread_id, message_id}, content, user)?;
+    enqueue_webhook_event(conn, WebhookEvent::MessagePosted, json!({
+        "threadId": thread_id,
+        "messageId": message_id,
+        "replyTo": reply_to,
+        "content": content,
+    }), user)?;
+
+    Ok(())
+}
+
+// Notifications and webhooks of a new thread, sent once it is published.
+// The messages the author added to a scheduled thread are announced as well.
+pub fn announce_thread(conn: &Connection, thread_id: u32, subsection_id: u32, name: &str, content: &str, user: &User) -> QueryResult<()> {
+    notify_thread_subscribers(conn, thread_id, NotificationKind::Thread, 1, user)?;
+    notify_mentions(conn, MentionSource::Message{thread_id, message_id: 1}, content, user)?;
+    enqueue_webhook_event(conn, WebhookEvent::ThreadCreated, json!({
+        "threadId": thread_id,
+        "subsectionId": subsection_id,
+        "name": name,
+        "messageId": 1,
+        "content": content,
+    }), user)?;
+
+    let mut stmt = conn.prepare("SELECT m.id, m.reply_to, mc.content FROM MESSAGE m INNER JOIN MESSAGE_CONTENT_FTS mc ON mc.rowid = m.content_id \
+        WHERE m.thread_id = ? AND m.id > 1 ORDER BY m.id")?;
+    let messages = stmt.query_map([thread_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<Result<Vec<(u32, Option<u32>, String)>, _>>()?;
+
+    for (message_id, reply_to, content) in messages {
+        announce_message(conn, thread_id, message_id, reply_to, &content, user)?;
+    }
+
+    Ok(())
 }
 
-pub fn create_thread(conn: &mut DbConnection, message: &CreateThreadMessage, user: &User) -> QueryResult<()> {
+pub fn create_thread(conn: &mut DbConnection, message: &CreateThreadMessage, user: &User) -> QueryResult<u32> {
     let transaction = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
 
     let t = chrono::offset::Utc::now();
     let t = t.timestamp_millis();
+    let publish_time = scheduled_time(message.publish_at);
 
     transaction.execute("INSERT INTO THREAD_NAME_FTS(name) VALUES(?)",
                         params![message.thread_name]
@@ -507,12 +874,20 @@ pub fn create_thread(conn: &mut DbConnection, message: &CreateThreadMessage, use
 
     let thread_name_id = transaction.last_insert_rowid() as u32;
 
-    transaction.execute("INSERT INTO THREAD(subsection_id, name_id, author_id, create_time, update_time, message_seq) VALUES(?, ?, ?, ?, ?, ?)",
-                        params![message.subsection_id, thread_name_id, user.id, t, t, 1u32]
+    transaction.execute("INSERT INTO THREAD(subsection_id, name_id, author_id, create_time, update_time, message_seq, publish_time) VALUES(?, ?, ?, ?, ?, ?, ?)",
+                        params![message.subsection_id, thread_name_id, user.id, t, t, 1u32, publish_time]
     )?;
 
     let thread_id = transaction.last_insert_rowid() as u32;
 
+    if let Some(poll) = &message.poll {
+        create_poll(&transaction, thread_id, poll)?;
+    }
+
+    if let Some(tags) = &message.tags {
+        set_thread_tags(&transaction, thread_id, tags)?;
+    }
+
     transaction.execute("INSERT INTO MESSAGE_CONTENT_FTS(content) VALUES(?)",
                         params![&message.message])?;
 
@@ -521,50 +896,68 @@ pub fn create_thread(conn: &mut DbConnection, message: &CreateThreadMessage, use
     transaction.execute("INSERT INTO MESSAGE(id, user_id, thread_id, create_time, update_time, content_id) VALUES(?, ?, ?, ?, ?, ?)",
                         params![1u32, user.id, thread_id, t, t, message_content_id])?;
 
+    transaction.execute("INSERT INTO THREAD_READ(user_id, thread_id, last_read_message_id) VALUES(?, ?, ?)",
+                        params![user.id, thread_id, 1u32])?;
+
+    subscribe(&transaction, user.id, SubscriptionTarget::Thread, thread_id)?;
+    if publish_time.is_none() {
+        announce_thread(&transaction, thread_id, message.subsection_id, &message.thread_name, &message.message, user)?;
+    }
+    delete_draft(&transaction, &DraftContext::Subsection(message.subsection_id), user)?;
+
     transaction.commit()?;
-    return Ok(());
+    Ok(thread_id)
 }
 
 pub fn update_message(conn: &mut DbConnection, message: &UpdateMessage, user: &User) -> QueryResult<()> {
+    let transaction = conn.transaction()?;
+
+    check_thread_visible(&transaction, message.thread_id, &Some(user.clone()))?;
+
     let t = chrono::offset::Utc::now();
     let t = t.timestamp_millis();
 
-    let count = conn.execute("UPDATE MESSAGE_CONTENT_FTS SET content = ? WHERE rowid = (SELECT content_id FROM MESSAGE WHERE id = ? AND thread_id = ?)",
-                             params![message.message, message.message_id, message.thread_id])?;
+    // only the author can edit, the content is changed after the check
+    let count = transaction.execute("UPDATE MESSAGE SET update_time = ? WHERE id = ? AND thread_id = ? AND user_id = ?",
+                                    params![t, message.message_id, message.thread_id, user.id])?;
 
     if count == 0 {
         return Err(LogicError::MessageNotFound.into());
     }
 
-    let count = conn.execute("UPDATE MESSAGE SET update_time = ? WHERE id = ? AND thread_id = ? AND user_id = ?",
-                             params![t, message.message_id, message.thread_id, user.id])?;
+    transaction.execute("UPDATE MESSAGE_CONTENT_FTS SET content = ? WHERE rowid = (SELECT content_id FROM MESSAGE WHERE id = ? AND thread_id = ?)",
+                        params![message.message, message.message_id, message.thread_id])?;
 
-    if count == 0 {
-        return Err(LogicError::MessageNotFound.into());
+    // mentions in a scheduled thread are sent with the final content when it is published
+    let scheduled: bool = transaction.query_row("SELECT publish_time IS NOT NULL FROM THREAD WHERE id = ?", [message.thread_id], |row| row.get(0))?;
+    if !scheduled {
+        notify_mentions(&transaction, MentionSource::Message{thread_id: message.thread_id, message_id: message.message_id}, &message.message, user)?;
     }
 
-    return Ok(());
+    transaction.commit()?;
+    Ok(())
 }
 
 pub fn update_comment(conn: &mut DbConnection, message: &UpdateComment, user: &User) -> QueryResult<()> {
+    let transaction = conn.transaction()?;
+
     let t = chrono::offset::Utc::now();
     let t = t.timestamp_millis();
 
-    let count = conn.execute("UPDATE ARTICLE_COMMENT_CONTENT_FTS SET content = ? WHERE rowid = (SELECT content_id FROM ARTICLE_COMMENT WHERE id = ? AND article_id = ?)",
-                             params![message.message, message.comment_id, message.article_id])?;
+    let count = transaction.execute("UPDATE ARTICLE_COMMENT SET update_time = ? WHERE id = ? AND article_id = ? AND user_id = ?",
+                                    params![t, message.comment_id, message.article_id, user.id])?;
 
     if count == 0 {
         return Err(LogicError::MessageNotFound.into());
     }
 
-    let count = conn.execute("UPDATE ARTICLE_COMMENT SET update_time = ? WHERE id = ? AND article_id = ? AND user_id = ?",
-                             params![t, message.comment_id, message.article_id, user.id])?;
+    transaction.execute("UPDATE ARTICLE_COMMENT_CONTENT_FTS SET content = ? WHERE rowid = (SELECT content_id FROM ARTICLE_COMMENT WHERE id = ? AND article_id = ?)",
+                        params![message.message, message.comment_id, message.article_id])?;
 
-    if count == 0 {
-        return Err(LogicError::MessageNotFound.into());
-    }
+    notify_mentions(&transaction, MentionSource::Comment{article_id: message.article_id, comment_id: message.comment_id}, &message.message, user)?;
 
-    return Ok(());
+    transaction.commit()?;
+    Ok(())
 }
 
 pub fn query_user_by_session(conn: &DbConnection, user_sessions_conn: &DbConnection, session: &str) -> QueryResult<Option<User>> {
@@ -577,8 +970,8 @@ pub fn query_user_by_session(conn: &DbConnection, user_sessions_conn: &DbConnect
 
     let user_id = user_id.unwrap();
 
-    let user: Option<User> = conn.query_row("SELECT id, name from USER where id = ?", [user_id],
-                                            |row| Ok(User{id: row.get(0)?, name: row.get(1)?}))
+    let user: Option<User> = conn.query_row("SELECT id, name, display_name, avatar_file_id from USER where id = ?", [user_id],
+                                            |row| extract_user(row, 0))
         .optional()?;
 
     Ok(user)
@@ -589,12 +982,12 @@ pub fn query_messages_by_search_params(conn: &DbConnection, params: &MessageSear
 m.id,
 m.thread_id, tn.name,
 m.create_time,
-m.user_id, u.name,
+m.user_id, u.name, u.display_name, u.avatar_file_id,
 mc.content
 FROM MESSAGE_CONTENT_FTS mc
 INNER JOIN MESSAGE m ON m.content_id = mc.rowid
 INNER JOIN USER u ON u.id = m.user_id
-INNER JOIN THREAD t ON t.id = m.thread_id AND t.deleted = FALSE
+INNER JOIN THREAD t ON t.id = m.thread_id AND t.deleted = FALSE AND t.publish_time IS NULL
 INNER JOIN THREAD_NAME_FTS tn ON tn.rowid = t.name_id
 WHERE MESSAGE_CONTENT_FTS MATCH ?
 ORDER BY mc.rank
@@ -614,11 +1007,8 @@ LIMIT 100";
             thread_id: row.get(1)?,
             thread_name: row.get(2)?,
             create_time: row.get(3)?,
-            user: User {
-                id: row.get(4)?,
-                name: row.get(5)?,
-            },
-            content: row.get(6)?,
+            user: extract_user(row, 4)?,
+            content: row.get(8)?,
         };
 
         messages.push(message);
@@ -654,6 +1044,26 @@ pub fn remove_session(user_sessions_conn: &DbConnection, current_session: &str,
     return query_sessions(user_sessions_conn, current_session);
 }
 
+pub fn remove_other_sessions(user_sessions_conn: &DbConnection, current_session: &str) -> QueryResult<()> {
+    let mut stmt = user_sessions_conn.prepare(
+        "DELETE FROM USER_SESSION WHERE user_id = (SELECT user_id FROM USER_SESSION WHERE user_session = ?1) AND user_session <> ?1",
+    )?;
+
+    stmt.execute(params![current_session])?;
+
+    Ok(())
+}
+
+pub fn remove_user_sessions(user_sessions_conn: &DbConnection, user_id: u32) -> QueryResult<()> {
+    let mut stmt = user_sessions_conn.prepare(
+        "DELETE FROM USER_SESSION WHERE user_id = ?",
+    )?;
+
+    stmt.execute(params![user_id])?;
+
+    Ok(())
+}
+
 pub fn remove_all_sessions(user_sessions_conn: &DbConnection, current_session: &str) -> QueryResult<()> {
     let mut stmt = user_sessions_conn.prepare(
         "DELETE FROM USER_SESSION WHERE user_id = (SELECT user_id FROM USER_SESSION WHERE user_session = ?)",
@@ -664,7 +1074,7 @@ pub fn remove_all_sessions(user_sessions_conn: &DbConnection, current_session: &
     return Ok(());
 }
 
-pub fn save_file_mapping(conn: &DbConnection, file_name: &str, mime: &str, orig_file_name: &str, user: &User, i: u32) -> QueryResult<String> {
+pub fn save_file_mapping(conn: &DbConnection, file_name: &str, mime: &str, orig_file_name: &str, size: u64, user: &User, i: u32) -> QueryResult<String> {
     let now = Utc::now();
     let id = format!(
         "u{}f{:04}{:02}{:02}T{:02}{:02}{:02}{:03}i{}",
@@ -680,10 +1090,10 @@ pub fn save_file_mapping(conn: &DbConnection, file_name: &str, mime: &str, orig_
     );
 
     let mut stmt = conn.prepare(
-        "INSERT OR REPLACE INTO FILES(id, user_id, file_name, mime, orig_file_name) VALUES(?, ?, ?, ?, ?)",
+        "INSERT OR REPLACE INTO FILES(id, user_id, file_name, mime, orig_file_name, size) VALUES(?, ?, ?, ?, ?, ?)",
     )?;
 
-    stmt.execute(params![id, user.id, file_name, mime, orig_file_name])?;
+    stmt.execute(params![id, user.id, file_name, mime, orig_file_name, size])?;
 
     return Ok(id);
 }
@@ -699,53 +1109,207 @@ pub fn query_file(conn: &DbConnection, id: String) -> QueryResult<UploadedFileWi
     Ok(file)
 }
 
-pub fn delete_thread(conn: &mut DbConnection, id: u32) -> QueryResult<()> {
-    let mut stmt = conn.prepare("UPDATE THREAD SET deleted = TRUE WHERE id = ?")?;
-    stmt.execute([id])?;
+pub fn extract_file_ids(content: &str) -> Vec<String> {
+    let mut ids = Vec::new();
 
-    return Ok(());
+    for (index, matched) in content.match_indices("/files/") {
+        let id: String = content[index + matched.len()..].chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect();
+
+        if !id.is_empty() && !ids.contains(&id) {
+            ids.push(id);
+        }
+    }
+
+    ids
 }
 
-pub fn create_article(conn: &mut DbConnection, path: &str, name: &str, content: &str, visibility: ArticleVisibility, user: &User) -> QueryResult<()> {
-    let transaction = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+pub fn query_article_attachments(conn: &DbConnection, article_id: u32, user: &Option<User>) -> QueryResult<ArticleAttachments> {
+    let article = query_article(conn, article_id, None, user)?;
 
-    let max_id: Option<u32> = transaction.query_row("SELECT MAX(id) FROM ARTICLE", [], |row| row.get(0))?;
-    let id = max_id.unwrap_or(0) + 1;
+    let mut referenced: BTreeMap<String, Vec<u32>> = BTreeMap::new();
+    for id in extract_file_ids(&article.content) {
+        referenced.entry(id).or_default().push(article.version);
+    }
 
-    transaction.execute("INSERT INTO ARTICLE_CONTENT_FTS(name, content) VALUES(?, ?)",
-                        params![name, content]
+    let mut stmt = conn.prepare(
+        "SELECT version, content FROM ARTICLE WHERE id = ? AND active = FALSE AND content IS NOT NULL ORDER BY version DESC",
     )?;
 
-    let content_id = transaction.last_insert_rowid() as u32;
+    let mut rows = stmt.query([article_id])?;
 
-    let t = chrono::offset::Utc::now();
-    let t = t.timestamp_millis();
+    while let Some(row) = rows.next()? {
+        let version: u32 = row.get(0)?;
+        let content: String = row.get(1)?;
 
-    transaction.execute("INSERT INTO ARTICLE(id, path, content_id, user_id, create_time, version, active, visibility) VALUES(?, ?, ?, ?, ?, ?, ?, ?)",
-                        params![id, path, content_id, user.id, t, 1u32, true, visibility])?;
+        for id in extract_file_ids(&content) {
+            referenced.entry(id).or_default().push(version);
+        }
+    }
 
-    transaction.commit()?;
-    return Ok(());
-}
+    let mut stmt = conn.prepare(
+        "SELECT file_id FROM ARTICLE_FILE WHERE article_id = ?",
+    )?;
 
-pub fn update_article(conn: &mut DbConnection, id: u32, path: &str, name: &str, content: &str, visibility: ArticleVisibility, user: User) -> QueryResult<()> {
-    let user_id = user.id;
+    let attached = stmt.query_map([article_id], |row| row.get(0))?
+        .collect::<Result<Vec<String>, _>>()?;
 
-    let article = query_article(&conn, id, None, &Some(user))?;
+    for id in &attached {
+        referenced.entry(id.clone()).or_default();
+    }
 
-    if article.content_id.is_none() {
-        return Err(LogicError::ArticleNotFound.into());
+    let mut stmt = conn.prepare(
+        "SELECT f.orig_file_name, f.mime, f.file_name, f.size, u.id, u.name, u.display_name, u.avatar_file_id FROM FILES f \
+        INNER JOIN USER u ON u.id = f.user_id \
+        WHERE f.id = ?",
+    )?;
+
+    let mut attachments = Vec::new();
+
+    for (id, versions) in referenced {
+        let mut rows = stmt.query([&id])?;
+
+        if let Some(row) = rows.next()? {
+            attachments.push(ArticleAttachment {
+                file: UploadedFile {
+                    id: id.clone(),
+                    file_name: row.get(0)?,
+                    mime: row.get(1)?,
+                },
+                file_path: row.get(2)?,
+                size: row.get(3)?,
+                user: extract_user(row, 4)?,
+                attached: attached.contains(&id),
+                versions,
+            });
+        }
     }
 
-    let transaction = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+    Ok(ArticleAttachments{attachments})
+}
 
-    let max_version: Option<u32> = transaction.query_row("SELECT MAX(version) FROM ARTICLE WHERE id = ?", [id], |row| row.get(0)).optional()?;
+pub fn attach_article_file(conn: &DbConnection, article_id: u32, file_id: &str, user: &User) -> QueryResult<()> {
+    let t = chrono::offset::Utc::now();
+    let t = t.timestamp_millis();
 
-    if max_version.is_none() {
-        return Err(LogicError::ArticleNotFound.into());
-    }
+    let mut stmt = conn.prepare(
+        "INSERT OR IGNORE INTO ARTICLE_FILE(article_id, file_id, user_id, create_time) VALUES(?, ?, ?, ?)",
+    )?;
 
-    let version = max_version.unwrap() + 1;
+    stmt.execute(params![article_id, file_id, user.id, t])?;
+    Ok(())
+}
+
+pub fn remove_article_attachment(conn: &DbConnection, article_id: u32, file_id: &str, user: &User) -> QueryResult<()> {
+    query_article(conn, article_id, None, &Some(user.clone()))?;
+
+    let count = conn.execute("DELETE FROM ARTICLE_FILE WHERE article_id = ? AND file_id = ?",
+                             params![article_id, file_id])?;
+
+    if count == 0 {
+        return Err(LogicError::FileNotFound.into());
+    }
+
+    Ok(())
+}
+
+pub fn delete_thread(conn: &mut DbConnection, id: u32) -> QueryResult<()> {
+    // a deleted thread is never published, even if it was scheduled
+    let mut stmt = conn.prepare("UPDATE THREAD SET deleted = TRUE, publish_time = NULL WHERE id = ?")?;
+    stmt.execute([id])?;
+
+    return Ok(());
+}
+
+// Mentions and webhooks of a new article, sent once it is published
+pub fn announce_article(conn: &Connection, id: u32, path: &str, name: &str, content: &str, visibility: ArticleVisibility, user: &User) -> QueryResult<()> {
+    notify_mentions(conn, MentionSource::Article{article_id: id}, content, user)?;
+    if matches!(visibility, ArticleVisibility::Public) {
+        enqueue_webhook_event(conn, WebhookEvent::ArticleCreated, json!({
+            "articleId": id,
+            "path": path,
+            "name": name,
+            "version": 1,
+        }), user)?;
+    }
+
+    Ok(())
+}
+
+// Articles with a publish time are created with the scheduled visibility
+// The template, if any, is expected to be applied already, see apply_article_template
+pub fn create_article(conn: &mut DbConnection, article: &CreateArticle, user: &User) -> QueryResult<u32> {
+    let (path, name, content) = (article.path.as_str(), article.name.as_str(), article.content.as_str());
+    let visibility = article.visibility.unwrap_or(ArticleVisibility::Public);
+
+    let publish_time = scheduled_time(article.publish_at);
+    let visibility = match (visibility, publish_time) {
+        (ArticleVisibility::Scheduled, None) => return Err(LogicError::InvalidPublishTime.into()),
+        (_, Some(_)) => ArticleVisibility::Scheduled,
+        (visibility, None) => visibility,
+    };
+
+    let transaction = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+
+    let max_id: Option<u32> = transaction.query_row("SELECT MAX(id) FROM ARTICLE", [], |row| row.get(0))?;
+    let id = max_id.unwrap_or(0) + 1;
+
+    transaction.execute("INSERT INTO ARTICLE_CONTENT_FTS(name, content) VALUES(?, ?)",
+                        params![name, content]
+    )?;
+
+    let content_id = transaction.last_insert_rowid() as u32;
+
+    let t = chrono::offset::Utc::now();
+    let t = t.timestamp_millis();
+
+    transaction.execute("INSERT INTO ARTICLE(id, path, content_id, user_id, create_time, version, active, visibility) VALUES(?, ?, ?, ?, ?, ?, ?, ?)",
+                        params![id, path, content_id, user.id, t, 1u32, true, visibility])?;
+
+    set_article_slug(&transaction, id, path, name)?;
+    save_article_links(&transaction, id, content)?;
+    if let Some(tags) = &article.tags {
+        set_article_tags(&transaction, id, tags)?;
+    }
+    subscribe(&transaction, user.id, SubscriptionTarget::Article, id)?;
+    match publish_time {
+        Some(publish_time) => {
+            transaction.execute("INSERT INTO ARTICLE_SCHEDULE(article_id, publish_time) VALUES(?, ?)", params![id, publish_time])?;
+        }
+        None => announce_article(&transaction, id, path, name, content, visibility, user)?
+    }
+    delete_draft(&transaction, &DraftContext::NewArticle, user)?;
+
+    transaction.commit()?;
+    Ok(id)
+}
+
+pub fn update_article(conn: &mut DbConnection, params: &UpdateArticle, user: User) -> QueryResult<()> {
+    let (id, path, name, content, visibility) = (params.id, params.path.as_str(), params.name.as_str(), params.content.as_str(), params.visibility);
+    let user_id = user.id;
+
+    let article = query_article(&conn, id, None, &Some(user.clone()))?;
+
+    if article.content_id.is_none() {
+        return Err(LogicError::ArticleNotFound.into());
+    }
+
+    // an article can stay scheduled but can't be scheduled again once published
+    let scheduled = matches!(visibility, ArticleVisibility::Scheduled);
+    if scheduled && article.publish_at.is_none() {
+        return Err(LogicError::InvalidPublishTime.into());
+    }
+
+    let transaction = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+
+    let max_version: Option<u32> = transaction.query_row("SELECT MAX(version) FROM ARTICLE WHERE id = ?", [id], |row| row.get(0)).optional()?;
+
+    if max_version.is_none() {
+        return Err(LogicError::ArticleNotFound.into());
+    }
+
+    let version = max_version.unwrap() + 1;
 
     transaction.execute("UPDATE ARTICLE
 SET active = FALSE,
@@ -766,10 +1330,385 @@ WHERE id = ? AND active = TRUE",
     transaction.execute("INSERT INTO ARTICLE(id, path, content_id, user_id, create_time, version, active, visibility) VALUES(?, ?, ?, ?, ?, ?, ?, ?)",
                         params![id, path, article.content_id.unwrap(), user_id, t, version, true, visibility])?;
 
+    set_article_slug(&transaction, id, path, name)?;
+    save_article_links(&transaction, id, content)?;
+    if let Some(tags) = &params.tags {
+        set_article_tags(&transaction, id, tags)?;
+    }
+    subscribe(&transaction, user_id, SubscriptionTarget::Article, id)?;
+    if !scheduled {
+        transaction.execute("DELETE FROM ARTICLE_SCHEDULE WHERE article_id = ?", [id])?;
+        notify_article_subscribers(&transaction, id, NotificationKind::ArticleUpdate, None, &user)?;
+        notify_mentions(&transaction, MentionSource::Article{article_id: id}, content, &user)?;
+    }
+    if matches!(visibility, ArticleVisibility::Public) {
+        enqueue_webhook_event(&transaction, WebhookEvent::ArticleUpdated, json!({
+            "articleId": id,
+            "path": path,
+            "name": name,
+            "version": version,
+        }), &user)?;
+    }
+    delete_draft(&transaction, &DraftContext::Article(id), &user)?;
+
     transaction.commit()?;
     return Ok(());
 }
 
+fn slugify_segment(segment: &str) -> String {
+    let mut slug = String::new();
+
+    for c in segment.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        }
+        else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    slug.trim_end_matches('-').to_string()
+}
+
+pub fn slugify_path(path: &str) -> String {
+    path.split('/')
+        .map(slugify_segment)
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+pub fn article_slug_base(path: &str, name: &str) -> String {
+    let slug = slugify_path(&format!("{}/{}", path, name));
+    if slug.is_empty() {"article".to_string()} else {slug}
+}
+
+fn is_slug_of_base(slug: &str, base: &str) -> bool {
+    match slug.strip_prefix(base) {
+        Some("") => true,
+        Some(suffix) => suffix.strip_prefix('-').is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit())),
+        None => false
+    }
+}
+
+fn is_slug_taken(conn: &Connection, slug: &str, article_id: u32) -> QueryResult<bool> {
+    let taken: bool = conn.query_row("SELECT EXISTS(SELECT 1 FROM ARTICLE_SLUG WHERE slug = ?1 AND article_id != ?2) \
+        OR EXISTS(SELECT 1 FROM ARTICLE_SLUG_REDIRECT WHERE slug = ?1 AND article_id != ?2)",
+                                     params![slug, article_id], |row| row.get(0))?;
+    Ok(taken)
+}
+
+// Keeps the current slug while it still matches path and name, otherwise assigns a new one
+// and leaves the old slug as a redirect
+pub fn set_article_slug(conn: &Connection, article_id: u32, path: &str, name: &str) -> QueryResult<String> {
+    let base = article_slug_base(path, name);
+
+    let current: Option<String> = conn.query_row("SELECT slug FROM ARTICLE_SLUG WHERE article_id = ?", [article_id], |row| row.get(0)).optional()?;
+
+    if let Some(current) = &current {
+        if is_slug_of_base(current, &base) {
+            return Ok(current.clone());
+        }
+    }
+
+    let mut slug = base.clone();
+    let mut n = 2;
+    while is_slug_taken(conn, &slug, article_id)? {
+        slug = format!("{}-{}", base, n);
+        n += 1;
+    }
+
+    let t = chrono::offset::Utc::now();
+    let t = t.timestamp_millis();
+
+    conn.execute("DELETE FROM ARTICLE_SLUG_REDIRECT WHERE slug = ?", [&slug])?;
+
+    if let Some(current) = current {
+        conn.execute("INSERT OR REPLACE INTO ARTICLE_SLUG_REDIRECT(slug, article_id, create_time) VALUES(?, ?, ?)",
+                     params![current, article_id, t])?;
+    }
+
+    conn.execute("INSERT OR REPLACE INTO ARTICLE_SLUG(article_id, slug) VALUES(?, ?)", params![article_id, slug])?;
+
+    Ok(slug)
+}
+
+pub fn backfill_article_slugs(conn: &mut DbConnection) -> QueryResult<()> {
+    let transaction = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+
+    let mut stmt = transaction.prepare(
+        "SELECT a.id, a.path, COALESCE(a.name, c.name) FROM ARTICLE a \
+        LEFT JOIN ARTICLE_CONTENT_FTS c ON c.rowid = a.content_id \
+        WHERE a.version = (SELECT MAX(version) FROM ARTICLE WHERE id = a.id) \
+        AND NOT EXISTS (SELECT 1 FROM ARTICLE_SLUG WHERE article_id = a.id) \
+        ORDER BY a.id",
+    )?;
+
+    let articles = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<Result<Vec<(u32, String, String)>, _>>()?;
+    drop(stmt);
+
+    for (id, path, name) in articles {
+        set_article_slug(&transaction, id, &path, &name)?;
+    }
+
+    transaction.commit()?;
+    Ok(())
+}
+
+pub fn resolve_article_slug(conn: &DbConnection, path: &str) -> QueryResult<Option<u32>> {
+    let slug = slugify_path(path);
+
+    let id: Option<u32> = conn.query_row("SELECT article_id FROM ARTICLE_SLUG WHERE slug = ?", [&slug], |row| row.get(0)).optional()?;
+    if id.is_some() {
+        return Ok(id);
+    }
+
+    let id: Option<u32> = conn.query_row("SELECT article_id FROM ARTICLE_SLUG_REDIRECT WHERE slug = ?", [&slug], |row| row.get(0)).optional()?;
+    Ok(id)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ArticleLinkTarget {
+    Id(u32),
+    Slug(String),
+}
+
+// Finds [[path/Article Name]] wiki links and /article/a/{id}, /article/{id}, /wiki/{slug} urls
+pub fn extract_article_links(content: &str) -> Vec<(ArticleLinkTarget, String)> {
+    let mut links: Vec<(ArticleLinkTarget, String)> = Vec::new();
+
+    let mut rest = content;
+    while let Some(start) = rest.find("[[") {
+        rest = &rest[start + 2..];
+
+        if let Some(end) = rest.find("]]") {
+            let link = &rest[..end];
+            let target = link.split('|').next().unwrap_or("");
+            let slug = slugify_path(target);
+
+            if !slug.is_empty() && !link.contains('\n') {
+                links.push((ArticleLinkTarget::Slug(slug), format!("[[{}]]", link)));
+            }
+
+            rest = &rest[end + 2..];
+        }
+    }
+
+    for (index, matched) in content.match_indices("/article/") {
+        let tail = &content[index + matched.len()..];
+        let tail = tail.strip_prefix("a/").unwrap_or(tail);
+        let id: String = tail.chars().take_while(|c| c.is_ascii_digit()).collect();
+
+        if let Ok(id) = id.parse() {
+            links.push((ArticleLinkTarget::Id(id), format!("/article/a/{}", id)));
+        }
+    }
+
+    for (index, matched) in content.match_indices("/wiki/") {
+        let tail: String = content[index + matched.len()..].chars()
+            .take_while(|c| c.is_alphanumeric() || *c == '-' || *c == '/')
+            .collect();
+        let slug = slugify_path(&tail);
+
+        if !slug.is_empty() {
+            links.push((ArticleLinkTarget::Slug(slug), format!("/wiki/{}", tail)));
+        }
+    }
+
+    let mut unique: Vec<(ArticleLinkTarget, String)> = Vec::new();
+    for link in links {
+        if !unique.iter().any(|l| l.0 == link.0) {
+            unique.push(link);
+        }
+    }
+
+    unique
+}
+
+fn save_article_links(conn: &Connection, article_id: u32, content: &str) -> QueryResult<()> {
+    conn.execute("DELETE FROM ARTICLE_LINK WHERE source_id = ?", [article_id])?;
+
+    for (target, link) in extract_article_links(content) {
+        match target {
+            ArticleLinkTarget::Id(id) => conn.execute("INSERT INTO ARTICLE_LINK(source_id, target_id, link) VALUES(?, ?, ?)",
+                                                      params![article_id, id, link])?,
+            ArticleLinkTarget::Slug(slug) => conn.execute("INSERT INTO ARTICLE_LINK(source_id, target_slug, link) VALUES(?, ?, ?)",
+                                                          params![article_id, slug, link])?,
+        };
+    }
+
+    Ok(())
+}
+
+pub fn index_all_article_links(conn: &Connection) -> QueryResult<()> {
+    let mut stmt = conn.prepare(
+        "SELECT a.id, c.content FROM ARTICLE a INNER JOIN ARTICLE_CONTENT_FTS c ON c.rowid = a.content_id WHERE a.active = TRUE",
+    )?;
+
+    let articles = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<(u32, String)>, _>>()?;
+
+    for (id, content) in articles {
+        save_article_links(conn, id, &content)?;
+    }
+
+    Ok(())
+}
+
+pub fn query_article_backlinks(conn: &DbConnection, article_id: u32, user: &Option<User>) -> QueryResult<Vec<ArticleInfo>> {
+    let mut stmt = conn.prepare(
+        &format!("SELECT DISTINCT a.id, a.path, c.name FROM ARTICLE_LINK l \
+        INNER JOIN ARTICLE a ON a.id = l.source_id AND a.active = TRUE \
+        INNER JOIN ARTICLE_CONTENT_FTS c ON c.rowid = a.content_id \
+        WHERE (l.target_id = ?1 \
+            OR l.target_slug IN (SELECT slug FROM ARTICLE_SLUG WHERE article_id = ?1 UNION SELECT slug FROM ARTICLE_SLUG_REDIRECT WHERE article_id = ?1)) \
+        AND a.id != ?1 \
+        AND {} \
+        ORDER BY a.path, c.name", article_visibility_restriction(user)),
+    )?;
+
+    let mut rows = if let Some(user) = user {stmt.query(params![article_id, user.id])?} else {stmt.query([article_id])?};
+
+    let mut articles = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        articles.push(ArticleInfo {
+            id: row.get(0)?,
+            path: row.get(1)?,
+            name: row.get(2)?,
+        });
+    }
+
+    Ok(articles)
+}
+
+pub fn query_broken_links(conn: &DbConnection, user: &Option<User>) -> QueryResult<BrokenLinks> {
+    let mut stmt = conn.prepare(
+        &format!("SELECT a.id, a.path, c.name, l.link FROM ARTICLE_LINK l \
+        INNER JOIN ARTICLE a ON a.id = l.source_id AND a.active = TRUE \
+        INNER JOIN ARTICLE_CONTENT_FTS c ON c.rowid = a.content_id \
+        WHERE {} \
+        AND NOT EXISTS (SELECT 1 FROM ARTICLE t WHERE t.active = TRUE AND t.id = COALESCE(l.target_id, \
+            (SELECT article_id FROM ARTICLE_SLUG WHERE slug = l.target_slug), \
+            (SELECT article_id FROM ARTICLE_SLUG_REDIRECT WHERE slug = l.target_slug))) \
+        ORDER BY a.path, c.name", article_visibility_restriction(user)),
+    )?;
+
+    let mut rows = if let Some(user) = user {stmt.query([user.id])?} else {stmt.query([])?};
+
+    let mut links = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        links.push(BrokenLink {
+            source: ArticleInfo {
+                id: row.get(0)?,
+                path: row.get(1)?,
+                name: row.get(2)?,
+            },
+            link: row.get(3)?,
+        });
+    }
+
+    Ok(BrokenLinks{links})
+}
+
+pub fn query_article_templates(conn: &DbConnection) -> QueryResult<ArticleTemplates> {
+    let mut stmt = conn.prepare(
+        "SELECT id, name, path_prefix, visibility, content FROM ARTICLE_TEMPLATE WHERE deleted = FALSE ORDER BY name",
+    )?;
+
+    let templates = stmt.query_map([], extract_article_template)?
+        .collect::<Result<Vec<ArticleTemplate>, _>>()?;
+
+    Ok(ArticleTemplates{templates})
+}
+
+pub fn query_article_template(conn: &DbConnection, id: u32) -> QueryResult<ArticleTemplate> {
+    let template = conn.query_row(
+        "SELECT id, name, path_prefix, visibility, content FROM ARTICLE_TEMPLATE WHERE id = ? AND deleted = FALSE",
+        [id], extract_article_template).optional()?;
+
+    Ok(template.ok_or(LogicError::TemplateNotFound)?)
+}
+
+fn extract_article_template(row: &Row) -> rusqlite::Result<ArticleTemplate> {
+    Ok(ArticleTemplate {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        path_prefix: row.get(2)?,
+        visibility: row.get(3)?,
+        content: row.get(4)?,
+    })
+}
+
+pub fn save_article_template(conn: &DbConnection, template: &SaveArticleTemplate) -> QueryResult<()> {
+    if let Some(id) = template.id {
+        let count = conn.execute("UPDATE ARTICLE_TEMPLATE SET name = ?, path_prefix = ?, visibility = ?, content = ? WHERE id = ? AND deleted = FALSE",
+                                 params![template.name, template.path_prefix, template.visibility, template.content, id])?;
+
+        if count == 0 {
+            return Err(LogicError::TemplateNotFound.into());
+        }
+    }
+    else {
+        conn.execute("INSERT INTO ARTICLE_TEMPLATE(name, path_prefix, visibility, content) VALUES(?, ?, ?, ?)",
+                     params![template.name, template.path_prefix, template.visibility, template.content])?;
+    }
+
+    Ok(())
+}
+
+pub fn delete_article_template(conn: &DbConnection, id: u32) -> QueryResult<()> {
+    let count = conn.execute("UPDATE ARTICLE_TEMPLATE SET deleted = TRUE WHERE id = ?", [id])?;
+
+    if count == 0 {
+        return Err(LogicError::TemplateNotFound.into());
+    }
+
+    Ok(())
+}
+
+// Replaces {{date}}, {{time}}, {{datetime}} and {{user}}, unknown placeholders are left as is
+pub fn expand_template_placeholders(text: &str, user: &User, now: DateTime<Utc>) -> String {
+    text.replace("{{date}}", &now.format("%Y-%m-%d").to_string())
+        .replace("{{time}}", &now.format("%H:%M").to_string())
+        .replace("{{datetime}}", &now.format("%Y-%m-%d %H:%M").to_string())
+        .replace("{{user}}", &user.name)
+}
+
+// Fills path, content and visibility left empty in the request from the template,
+// placeholders are expanded only in the text taken from the template
+pub fn apply_article_template(article: &CreateArticle, template: Option<&ArticleTemplate>, user: &User) -> CreateArticle {
+    let now = Utc::now();
+
+    let mut path = article.path.clone();
+    let mut content = article.content.clone();
+    let mut visibility = article.visibility;
+
+    if let Some(template) = template {
+        if path.trim().is_empty() {
+            path = expand_template_placeholders(&template.path_prefix, user, now);
+        }
+
+        if content.trim().is_empty() {
+            content = expand_template_placeholders(&template.content, user, now);
+        }
+
+        visibility = visibility.or(Some(template.visibility));
+    }
+
+    CreateArticle {
+        path,
+        name: article.name.clone(),
+        content,
+        visibility: Some(visibility.unwrap_or(ArticleVisibility::Public)),
+        template_id: None,
+        tags: article.tags.clone(),
+        publish_at: article.publish_at,
+    }
+}
+
 pub fn article_visibility_restriction(user: &Option<User>) -> &str {
     if user.is_some() {
         "(a.visibility = 'public' OR a.user_id = ?)"
@@ -778,13 +1717,16 @@ pub fn article_visibility_restriction(user: &Option<User>) -> &str {
     }
 }
 
-pub fn query_articles(conn: &DbConnection, user: &Option<User>) -> QueryResult<Articles> {
+pub fn query_articles(conn: &DbConnection, tags: &[String], user: &Option<User>) -> QueryResult<Articles> {
+    let tags_clause = if tags.is_empty() { String::new() } else { format!(" AND {}", tags_filter_clause("a.id", "ARTICLE_TAG", "article_id", tags.len())) };
+
     let mut stmt = conn.prepare(
         &format!("SELECT a.id, a.path, (SELECT name FROM ARTICLE_CONTENT_FTS WHERE ARTICLE_CONTENT_FTS.rowid = a.content_id) FROM ARTICLE a \
-            WHERE a.active = TRUE AND {}", article_visibility_restriction(user)),
+            WHERE a.active = TRUE AND a.visibility <> 'scheduled' AND {}{}", article_visibility_restriction(user), tags_clause),
     )?;
 
-    let mut rows = if user.is_some() {stmt.query(params![user.as_ref().unwrap().id])?} else {stmt.query([])?};
+    let values = user.iter().map(|user| Value::from(user.id)).chain(tags.iter().cloned().map(Value::from));
+    let mut rows = stmt.query(params_from_iter(values))?;
 
     let mut articles = Vec::new();
 
@@ -833,7 +1775,7 @@ pub fn query_articles_by_search_params(conn: &DbConnection, params: &ArticleSear
         &format!("SELECT a.id, a.path, c.name, highlight(ARTICLE_CONTENT_FTS, 1, '<<%%>>', '<<%%>>') \
         FROM ARTICLE a \
         INNER JOIN ARTICLE_CONTENT_FTS c ON c.rowid = a.content_id
-        WHERE a.active = TRUE \
+        WHERE a.active = TRUE AND a.visibility <> 'scheduled' \
         AND ARTICLE_CONTENT_FTS MATCH ? \
         AND {}
         ORDER BY c.rank \
@@ -863,8 +1805,8 @@ pub fn query_articles_by_search_params(conn: &DbConnection, params: &ArticleSear
 
 pub fn query_article(conn: &DbConnection, id: u32, version: Option<u32>, user: &Option<User>) -> QueryResult<Article> {
     let mut stmt = conn.prepare(
-        &format!("SELECT a.version, a.create_time, a.user_id, (SELECT name FROM USER WHERE USER.id = a.user_id), active \
-    FROM ARTICLE a \
+        &format!("SELECT a.version, a.create_time, a.user_id, u.name, u.display_name, u.avatar_file_id, a.active \
+    FROM ARTICLE a INNER JOIN USER u ON u.id = a.user_id \
     WHERE a.id = ? \
     AND {}
     ORDER BY a.create_time DESC", article_visibility_restriction(user)))?;
@@ -876,11 +1818,8 @@ pub fn query_article(conn: &DbConnection, id: u32, version: Option<u32>, user: &
         versions.push(ArticleVersion{
             version: row.get(0)?,
             create_time: row.get(1)?,
-            user: User {
-                id: row.get(2)?,
-                name: row.get(3)?
-            },
-            active: row.get(4)?
+            user: extract_user(row, 2)?,
+            active: row.get(6)?
         });
     }
 
@@ -889,16 +1828,18 @@ pub fn query_article(conn: &DbConnection, id: u32, version: Option<u32>, user: &
     }
 
     let sql = if version.is_some() {
-        "SELECT a.id, a.path, a.name, a.content, u.id, u.name, a.create_time, a.version, a.content_id, a.active, \
+        "SELECT a.id, a.path, a.name, a.content, u.id, u.name, u.display_name, u.avatar_file_id, a.create_time, a.version, a.content_id, a.active, \
         (SELECT COUNT(1) FROM ARTICLE_COMMENT WHERE article_id = a.id), \
-        a.visibility FROM ARTICLE a \
+        a.visibility, (SELECT slug FROM ARTICLE_SLUG WHERE article_id = a.id), \
+        (SELECT publish_time FROM ARTICLE_SCHEDULE WHERE article_id = a.id) FROM ARTICLE a \
         INNER JOIN USER u ON u.id = a.user_id \
         WHERE a.id = ? AND a.version = ? AND a.active = FALSE"
     }
     else {
-        "SELECT a.id, a.path, c.name, c.content, u.id, u.name, a.create_time, a.version, a.content_id, a.active, \
+        "SELECT a.id, a.path, c.name, c.content, u.id, u.name, u.display_name, u.avatar_file_id, a.create_time, a.version, a.content_id, a.active, \
         (SELECT COUNT(1) FROM ARTICLE_COMMENT WHERE article_id = a.id), \
-        a.visibility FROM ARTICLE a \
+        a.visibility, (SELECT slug FROM ARTICLE_SLUG WHERE article_id = a.id), \
+        (SELECT publish_time FROM ARTICLE_SCHEDULE WHERE article_id = a.id) FROM ARTICLE a \
         INNER JOIN ARTICLE_CONTENT_FTS c ON c.rowid = a.content_id \
         INNER JOIN USER u ON u.id = a.user_id \
         WHERE a.id = ? AND a.active = TRUE"
@@ -909,24 +1850,27 @@ pub fn query_article(conn: &DbConnection, id: u32, version: Option<u32>, user: &
     let mut rows = if version.is_some() {stmt.query( params![id, version])} else {stmt.query(params![id])}?;
 
     if let Some(row) = rows.next()? {
+        let content: String = row.get(3)?;
         let article = Article {
             info: ArticleInfo {
                 id: row.get(0)?,
                 path: row.get(1)?,
                 name: row.get(2)?,
             },
-            content: row.get(3)?,
-            user: User {
-                id: row.get(4)?,
-                name: row.get(5)?,
-            },
-            create_time: row.get(6)?,
-            version: row.get(7)?,
-            content_id: row.get(8)?,
-            active: row.get(9)?,
-            comments_count: row.get(10)?,
-            visibility: row.get(11)?,
-            versions
+            mentions: resolve_mentions(conn, &content)?,
+            content,
+            user: extract_user(row, 4)?,
+            create_time: row.get(8)?,
+            version: row.get(9)?,
+            content_id: row.get(10)?,
+            active: row.get(11)?,
+            comments_count: row.get(12)?,
+            visibility: row.get(13)?,
+            slug: row.get(14)?,
+            versions,
+            backlinks: query_article_backlinks(conn, id, user)?,
+            tags: query_article_tags(conn, id)?,
+            publish_at: row.get(15)?,
         };
 
         return Ok(article);
@@ -957,10 +1901,151 @@ WHERE id = ? AND active = TRUE",
                         params![article.content_id.unwrap()]
     )?;
 
+    if let Some(user) = user {
+        let t = chrono::offset::Utc::now();
+        let t = t.timestamp_millis();
+
+        transaction.execute("INSERT OR REPLACE INTO DELETED_ARTICLE(article_id, user_id, delete_time) VALUES(?, ?, ?)",
+                            params![id, user.id, t]
+        )?;
+
+        if matches!(article.visibility, ArticleVisibility::Public) {
+            enqueue_webhook_event(&transaction, WebhookEvent::ArticleDeleted, json!({
+                "articleId": id,
+                "path": article.info.path,
+                "name": article.info.name,
+            }), user)?;
+        }
+    }
+
     transaction.commit()?;
     return Ok(());
 }
 
+pub fn query_deleted_articles(conn: &DbConnection, deleted_by: Option<&User>) -> QueryResult<Vec<DeletedArticle>> {
+    let mut stmt = conn.prepare(
+        &format!("SELECT a.id, a.path, a.name, a.version, d.delete_time, u.id, u.name, u.display_name, u.avatar_file_id FROM ARTICLE a \
+        LEFT JOIN DELETED_ARTICLE d ON d.article_id = a.id \
+        LEFT JOIN USER u ON u.id = d.user_id \
+        WHERE a.version = (SELECT MAX(version) FROM ARTICLE WHERE id = a.id) \
+        AND NOT EXISTS (SELECT 1 FROM ARTICLE WHERE id = a.id AND active = TRUE) \
+        {} \
+        ORDER BY d.delete_time DESC", if deleted_by.is_some() {"AND d.user_id = ?"} else {""}),
+    )?;
+
+    let mut rows = if let Some(user) = deleted_by {stmt.query([user.id])?} else {stmt.query([])?};
+
+    let mut articles = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        let deleter_id: Option<u32> = row.get(5)?;
+
+        articles.push(DeletedArticle {
+            info: ArticleInfo {
+                id: row.get(0)?,
+                path: row.get(1)?,
+                name: row.get(2)?,
+            },
+            version: row.get(3)?,
+            delete_time: row.get(4)?,
+            deleted_by: match deleter_id {
+                Some(_) => Some(extract_user(row, 5)?),
+                None => None
+            },
+        });
+    }
+
+    Ok(articles)
+}
+
+pub fn restore_article(conn: &mut DbConnection, id: u32, user: &User, admin: bool) -> QueryResult<()> {
+    let transaction = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+
+    let active: bool = transaction.query_row("SELECT EXISTS(SELECT 1 FROM ARTICLE WHERE id = ? AND active = TRUE)", [id], |row| row.get(0))?;
+    if active {
+        return Err(LogicError::ArticleNotDeleted.into());
+    }
+
+    // the author of the latest version stays the owner, whoever restores the article
+    let latest: Option<(String, String, String, ArticleVisibility, u32, u32)> = transaction.query_row(
+        "SELECT path, name, content, visibility, version, user_id FROM ARTICLE WHERE id = ? ORDER BY version DESC LIMIT 1",
+        [id],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?))).optional()?;
+
+    let (path, name, content, visibility, version, owner_id) = latest.ok_or(LogicError::ArticleNotFound)?;
+
+    // the scheduler drops the schedules of deleted articles, such articles come back private
+    let scheduled: bool = transaction.query_row("SELECT EXISTS(SELECT 1 FROM ARTICLE_SCHEDULE WHERE article_id = ?)", [id], |row| row.get(0))?;
+    let visibility = match visibility {
+        ArticleVisibility::Scheduled if !scheduled => ArticleVisibility::Private,
+        visibility => visibility,
+    };
+
+    let deleted_by: Option<u32> = transaction.query_row("SELECT user_id FROM DELETED_ARTICLE WHERE article_id = ?", [id], |row| row.get(0)).optional()?;
+    if !admin && deleted_by != Some(user.id) {
+        return Err(LogicError::AccessDenied.into());
+    }
+
+    transaction.execute("INSERT INTO ARTICLE_CONTENT_FTS(name, content) VALUES(?, ?)",
+                        params![name, content]
+    )?;
+
+    let content_id = transaction.last_insert_rowid() as u32;
+
+    let t = chrono::offset::Utc::now();
+    let t = t.timestamp_millis();
+
+    transaction.execute("INSERT INTO ARTICLE(id, path, content_id, user_id, create_time, version, active, visibility) VALUES(?, ?, ?, ?, ?, ?, ?, ?)",
+                        params![id, path, content_id, owner_id, t, version + 1, true, visibility])?;
+
+    transaction.execute("DELETE FROM DELETED_ARTICLE WHERE article_id = ?", [id])?;
+
+    transaction.commit()?;
+    Ok(())
+}
+
+pub fn purge_deleted_articles(conn: &mut DbConnection, deleted_before: i64) -> QueryResult<usize> {
+    let transaction = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+
+    let mut stmt = transaction.prepare(
+        "SELECT article_id FROM DELETED_ARTICLE d WHERE delete_time < ? \
+        AND NOT EXISTS (SELECT 1 FROM ARTICLE WHERE id = d.article_id AND active = TRUE)",
+    )?;
+
+    let ids = stmt.query_map([deleted_before], |row| row.get(0))?
+        .collect::<Result<Vec<u32>, _>>()?;
+    drop(stmt);
+
+    for id in &ids {
+        transaction.execute("DELETE FROM ARTICLE_COMMENT_CONTENT_FTS WHERE rowid IN (SELECT content_id FROM ARTICLE_COMMENT WHERE article_id = ?)", [id])?;
+        transaction.execute("DELETE FROM ARTICLE_COMMENT WHERE article_id = ?", [id])?;
+        transaction.execute("DELETE FROM FAVORITE_ARTICLE WHERE article_id = ?", [id])?;
+        transaction.execute("DELETE FROM ARTICLE_FILE WHERE article_id = ?", [id])?;
+        transaction.execute("DELETE FROM ARTICLE WHERE id = ?", [id])?;
+        transaction.execute("DELETE FROM ARTICLE_LINK WHERE source_id = ?", [id])?;
+        transaction.execute("DELETE FROM ARTICLE_SLUG WHERE article_id = ?", [id])?;
+        transaction.execute("DELETE FROM ARTICLE_SLUG_REDIRECT WHERE article_id = ?", [id])?;
+        transaction.execute("DELETE FROM DELETED_ARTICLE WHERE article_id = ?", [id])?;
+        transaction.execute("DELETE FROM SUBSCRIPTION WHERE target_type = 'article' AND target_id = ?", [id])?;
+        transaction.execute("DELETE FROM NOTIFICATION WHERE article_id = ?", [id])?;
+        transaction.execute("DELETE FROM MENTION WHERE article_id = ?", [id])?;
+        transaction.execute("DELETE FROM REACTION WHERE article_id = ?", [id])?;
+        transaction.execute("DELETE FROM ARTICLE_TAG WHERE article_id = ?", [id])?;
+        transaction.execute("DELETE FROM DRAFT WHERE context = ?", [DraftContext::Article(*id).key()])?;
+        transaction.execute("DELETE FROM ARTICLE_SCHEDULE WHERE article_id = ?", [id])?;
+    }
+
+    delete_unused_tags(&transaction)?;
+
+    transaction.commit()?;
+    Ok(ids.len())
+}
+
+pub fn query_user_is_admin(conn: &DbConnection, user_id: u32) -> QueryResult<bool> {
+    let admin: Option<bool> = conn.query_row("SELECT admin FROM USER WHERE id = ?", [user_id], |row| row.get(0)).optional()?;
+    Ok(admin.unwrap_or(false))
+}
+
 pub fn query_favorite_articles(conn: &DbConnection, user: &User) -> QueryResult<Articles> {
     let mut stmt = conn.prepare(
         "SELECT a.id, a.path, (SELECT name FROM ARTICLE_CONTENT_FTS WHERE ARTICLE_CONTENT_FTS.rowid = a.content_id) FROM ARTICLE a \
@@ -1059,9 +2144,330 @@ pub fn reformat_fts_query(query: &str) -> String {
     return strings.join(" ");
 }
 
+pub fn update_thread_flags(conn: &DbConnection, params: &UpdateThreadFlags) -> QueryResult<()> {
+    let count = conn.execute("UPDATE THREAD SET pinned = COALESCE(?, pinned), locked = COALESCE(?, locked), announcement = COALESCE(?, announcement) WHERE id = ?",
+                             params![params.pinned, params.locked, params.announcement, params.thread_id])?;
+
+    if count == 0 {
+        return Err(LogicError::ThreadNotFound.into());
+    }
+
+    Ok(())
+}
+
+// Moves the messages (sorted ids) to the end of another thread, the message ids continue
+// from first_id. The old locations are redirected to the new ones.
+// Replies between moved and remaining messages lose their reply_to.
+fn move_messages(conn: &Connection, thread_id: u32, message_ids: &[u32], new_thread_id: u32, first_id: u32) -> QueryResult<()> {
+    let new_ids: HashMap<u32, u32> = message_ids.iter().enumerate()
+        .map(|(i, &message_id)| (message_id, first_id + i as u32))
+        .collect();
+
+    for (i, &message_id) in message_ids.iter().enumerate() {
+        let new_message_id = first_id + i as u32;
+
+        let reply_to: Option<u32> = conn.query_row("SELECT reply_to FROM MESSAGE WHERE thread_id = ? AND id = ?",
+                                                   params![thread_id, message_id], |row| row.get(0))?;
+        let new_reply_to = reply_to.and_then(|reply_to| new_ids.get(&reply_to));
+
+        conn.execute("UPDATE MESSAGE SET thread_id = ?, id = ?, reply_to = ? WHERE thread_id = ? AND id = ?",
+                     params![new_thread_id, new_message_id, new_reply_to, thread_id, message_id])?;
+        conn.execute("UPDATE MESSAGE_REDIRECT SET new_thread_id = ?, new_message_id = ? WHERE new_thread_id = ? AND new_message_id = ?",
+                     params![new_thread_id, new_message_id, thread_id, message_id])?;
+        conn.execute("INSERT INTO MESSAGE_REDIRECT(thread_id, message_id, new_thread_id, new_message_id) VALUES(?, ?, ?, ?)",
+                     params![thread_id, message_id, new_thread_id, new_message_id])?;
+        conn.execute("UPDATE MENTION SET thread_id = ?, message_id = ? WHERE thread_id = ? AND message_id = ?",
+                     params![new_thread_id, new_message_id, thread_id, message_id])?;
+        conn.execute("UPDATE NOTIFICATION SET thread_id = ?, message_id = ? WHERE thread_id = ? AND message_id = ?",
+                     params![new_thread_id, new_message_id, thread_id, message_id])?;
+        conn.execute("UPDATE REACTION SET thread_id = ?, message_id = ? WHERE thread_id = ? AND article_id = 0 AND message_id = ?",
+                     params![new_thread_id, new_message_id, thread_id, message_id])?;
+    }
+
+    for &message_id in message_ids {
+        conn.execute("UPDATE MESSAGE SET reply_to = NULL WHERE thread_id = ? AND reply_to = ?", params![thread_id, message_id])?;
+    }
+
+    Ok(())
+}
+
+fn query_active_thread_seq(conn: &Connection, thread_id: u32) -> QueryResult<u32> {
+    let seq = conn.query_row("SELECT message_seq FROM THREAD WHERE id = ? AND deleted = FALSE", [thread_id], |row| row.get(0))
+        .optional()?
+        .ok_or(LogicError::ThreadNotFound)?;
+
+    Ok(seq)
+}
+
+// Appends all messages of the thread to the target thread and deletes the thread
+pub fn merge_threads(conn: &mut DbConnection, params: &MergeThreads) -> QueryResult<()> {
+    if params.thread_id == params.target_thread_id {
+        return Err(LogicError::CannotMergeThreadIntoItself.into());
+    }
+
+    let transaction = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+
+    query_active_thread_seq(&transaction, params.thread_id)?;
+    let seq = query_active_thread_seq(&transaction, params.target_thread_id)?;
+
+    let message_ids = {
+        let mut stmt = transaction.prepare("SELECT id FROM MESSAGE WHERE thread_id = ? ORDER BY id")?;
+        let ids = stmt.query_map([params.thread_id], |row| row.get(0))?.collect::<Result<Vec<u32>, _>>()?;
+        ids
+    };
+
+    move_messages(&transaction, params.thread_id, &message_ids, params.target_thread_id, seq + 1)?;
+
+    transaction.execute("UPDATE THREAD SET message_seq = ?, update_time = max(update_time, (SELECT update_time FROM THREAD WHERE id = ?)) WHERE id = ?",
+                        params![seq + message_ids.len() as u32, params.thread_id, params.target_thread_id])?;
+    transaction.execute("UPDATE THREAD SET deleted = TRUE WHERE id = ?", [params.thread_id])?;
+    transaction.execute("INSERT OR IGNORE INTO SUBSCRIPTION(user_id, target_type, target_id) \
+        SELECT user_id, target_type, ? FROM SUBSCRIPTION WHERE target_type = ? AND target_id = ?",
+                        params![params.target_thread_id, SubscriptionTarget::Thread, params.thread_id])?;
+    transaction.execute("DELETE FROM SUBSCRIPTION WHERE target_type = ? AND target_id = ?",
+                        params![SubscriptionTarget::Thread, params.thread_id])?;
+    transaction.execute("DELETE FROM THREAD_READ WHERE thread_id = ?", [params.thread_id])?;
+
+    transaction.commit()?;
+    Ok(())
+}
+
+// Moves the selected messages into a new thread, at least one message has to stay in the old one.
+// Returns the id of the new thread.
+pub fn split_thread(conn: &mut DbConnection, params: &SplitThread) -> QueryResult<u32> {
+    let mut message_ids = params.message_ids.clone();
+    message_ids.sort_unstable();
+    message_ids.dedup();
+
+    let transaction = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+
+    query_active_thread_seq(&transaction, params.thread_id)?;
+
+    let (subsection_id, total_messages): (u32, usize) = transaction.query_row(
+        "SELECT t.subsection_id, (SELECT count(1) FROM MESSAGE m WHERE m.thread_id = t.id) FROM THREAD t WHERE t.id = ?",
+        [params.thread_id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+    if message_ids.is_empty() || message_ids.len() >= total_messages {
+        return Err(LogicError::InvalidMessageSelection.into());
+    }
+
+    let mut first_message: Option<(u32, i64)> = None;
+    let mut update_time = 0;
+    for &message_id in &message_ids {
+        let message: (u32, i64, i64) = transaction.query_row("SELECT user_id, create_time, update_time FROM MESSAGE WHERE thread_id = ? AND id = ?",
+                                                             params![params.thread_id, message_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .optional()?
+            .ok_or(LogicError::MessageNotFound)?;
+
+        first_message.get_or_insert((message.0, message.1));
+        update_time = update_time.max(message.2);
+    }
+    let (author_id, create_time) = first_message.ok_or(LogicError::InvalidMessageSelection)?;
+
+    transaction.execute("INSERT INTO THREAD_NAME_FTS(name) VALUES(?)", params![params.thread_name])?;
+
+    let thread_name_id = transaction.last_insert_rowid() as u32;
+
+    transaction.execute("INSERT INTO THREAD(subsection_id, name_id, author_id, create_time, update_time, message_seq) VALUES(?, ?, ?, ?, ?, ?)",
+                        params![params.subsection_id.unwrap_or(subsection_id), thread_name_id, author_id, create_time, update_time, message_ids.len() as u32])?;
+
+    let new_thread_id = transaction.last_insert_rowid() as u32;
+
+    move_messages(&transaction, params.thread_id, &message_ids, new_thread_id, 1)?;
+
+    // readers who had read all the moved messages have read the new thread
+    transaction.execute("INSERT INTO THREAD_READ(user_id, thread_id, last_read_message_id) \
+        SELECT user_id, ?, ? FROM THREAD_READ WHERE thread_id = ? AND last_read_message_id >= ?",
+                        params![new_thread_id, message_ids.len() as u32, params.thread_id, message_ids[message_ids.len() - 1]])?;
+    transaction.execute("INSERT OR IGNORE INTO SUBSCRIPTION(user_id, target_type, target_id) \
+        SELECT user_id, target_type, ? FROM SUBSCRIPTION WHERE target_type = ? AND target_id = ?",
+                        params![new_thread_id, SubscriptionTarget::Thread, params.thread_id])?;
+
+    transaction.commit()?;
+    Ok(new_thread_id)
+}
+
+// Current location of a message that could have been moved by a merge or a split,
+// without the message id the location of the first message of the thread is resolved
+pub fn query_message_location(conn: &DbConnection, thread_id: u32, message_id: Option<u32>, user: &Option<User>) -> QueryResult<MessageLocation> {
+    let redirect = match message_id {
+        Some(message_id) => conn.query_row("SELECT new_thread_id, new_message_id FROM MESSAGE_REDIRECT WHERE thread_id = ? AND message_id = ?",
+                                           params![thread_id, message_id], |row| Ok((row.get(0)?, row.get(1)?))).optional()?,
+        None => conn.query_row("SELECT new_thread_id, new_message_id FROM MESSAGE_REDIRECT r WHERE thread_id = ? \
+            AND NOT EXISTS (SELECT 1 FROM MESSAGE m WHERE m.thread_id = r.thread_id) ORDER BY message_id LIMIT 1",
+                               [thread_id], |row| Ok((row.get(0)?, row.get(1)?))).optional()?,
+    };
+
+    let (thread_id, message_id) = match redirect {
+        Some(redirect) => redirect,
+        None => {
+            let message_id: Option<u32> = match message_id {
+                Some(message_id) => conn.query_row("SELECT id FROM MESSAGE WHERE thread_id = ? AND id = ?",
+                                                   params![thread_id, message_id], |row| row.get(0)).optional()?,
+                None => conn.query_row("SELECT min(id) FROM MESSAGE WHERE thread_id = ?", [thread_id], |row| row.get(0))?
+            };
+            (thread_id, message_id.ok_or(LogicError::MessageNotFound)?)
+        }
+    };
+
+    // the old location may be a deleted thread, the current one has to be visible
+    check_thread_visible(conn, thread_id, user)?;
+
+    let position: u32 = conn.query_row("SELECT count(1) FROM MESSAGE WHERE thread_id = ? AND id <= ?",
+                                       params![thread_id, message_id], |row| row.get(0))?;
+
+    Ok(MessageLocation {
+        thread_id,
+        message_id,
+        page: (position - 1) / MESSAGES_PER_PAGE + 1,
+        position,
+    })
+}
+
+pub fn query_comment_location(conn: &DbConnection, article_id: u32, comment_id: u32, user: &Option<User>) -> QueryResult<CommentLocation> {
+    // fails when the article is not visible to the user
+    query_article(conn, article_id, None, user)?;
+
+    let article_version: u32 = conn.query_row("SELECT article_version FROM ARTICLE_COMMENT WHERE article_id = ? AND id = ?",
+                                              params![article_id, comment_id], |row| row.get(0))
+        .optional()?
+        .ok_or(LogicError::MessageNotFound)?;
+
+    let position: u32 = conn.query_row("SELECT count(1) FROM ARTICLE_COMMENT WHERE article_id = ? AND id <= ?",
+                                       params![article_id, comment_id], |row| row.get(0))?;
+
+    Ok(CommentLocation {
+        article_id,
+        article_version,
+        comment_id,
+        page: (position - 1) / MESSAGES_PER_PAGE + 1,
+        position,
+    })
+}
+
+// Paths of the SPA pages showing the message or the comment
+pub fn message_location_path(location: &MessageLocation) -> String {
+    format!("/forum/t/{}?page={}#m{}", location.thread_id, location.page, location.message_id)
+}
+
+pub fn comment_location_path(location: &CommentLocation) -> String {
+    format!("/forum/ac/{}/{}?page={}#m{}", location.article_id, location.article_version, location.page, location.comment_id)
+}
+
+pub const MESSAGE_EXCERPT_LENGTH: usize = 150;
+
+// Beginning of the message on one line, cut at MESSAGE_EXCERPT_LENGTH characters
+pub fn message_excerpt(content: &str) -> String {
+    let text = content.split_whitespace().collect::<Vec<&str>>().join(" ");
+
+    match text.char_indices().nth(MESSAGE_EXCERPT_LENGTH) {
+        Some((index, _)) => format!("{}…", &text[..index]),
+        None => text
+    }
+}
+
+fn extract_message_preview(conn: &Connection, thread_id: u32, row: &Row) -> QueryResult<MessagePreview> {
+    let id = row.get(0)?;
+    let content: String = row.get(5)?;
+
+    Ok(MessagePreview {
+        id,
+        user: extract_user(row, 1)?,
+        excerpt: message_excerpt(&content),
+        page: query_message_page(conn, thread_id, id)?,
+    })
+}
+
+pub fn query_message_preview(conn: &Connection, thread_id: u32, message_id: u32) -> QueryResult<Option<MessagePreview>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT m.id, m.user_id, u.name, u.display_name, u.avatar_file_id, mc.content FROM MESSAGE m INNER JOIN USER u ON u.id = m.user_id \
+        INNER JOIN MESSAGE_CONTENT_FTS mc ON mc.rowid = m.content_id WHERE m.thread_id = ? AND m.id = ?",
+    )?;
+
+    let mut rows = stmt.query(params![thread_id, message_id])?;
+
+    match rows.next()? {
+        Some(row) => Ok(Some(extract_message_preview(conn, thread_id, row)?)),
+        None => Ok(None)
+    }
+}
+
+pub fn query_message_replies(conn: &DbConnection, thread_id: u32, message_id: u32, user: &Option<User>) -> QueryResult<MessageReplies> {
+    check_thread_visible(conn, thread_id, user)?;
+
+    let mut stmt = conn.prepare(
+        "SELECT m.id, m.user_id, u.name, u.display_name, u.avatar_file_id, mc.content FROM MESSAGE m INNER JOIN USER u ON u.id = m.user_id \
+        INNER JOIN MESSAGE_CONTENT_FTS mc ON mc.rowid = m.content_id WHERE m.thread_id = ? AND m.reply_to = ? ORDER BY m.id",
+    )?;
+
+    let mut rows = stmt.query(params![thread_id, message_id])?;
+
+    let mut replies = Vec::new();
+    while let Some(row) = rows.next()? {
+        replies.push(extract_message_preview(conn, thread_id, row)?);
+    }
+
+    Ok(MessageReplies{replies})
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::queries::reformat_fts_query;
+    use chrono::TimeZone;
+    use rusqlite::params;
+    use crate::create_db::create_memory_db;
+    use crate::scheduling::publish_due_threads;
+    use crate::queries::{article_slug_base, ArticleLinkTarget, create_thread, DbConnection, expand_template_placeholders, extract_article_links, extract_file_ids, merge_threads, message_excerpt, MESSAGE_EXCERPT_LENGTH, query_message_location, reformat_fts_query, save_message, save_user, slugify_path, split_thread};
+    use crate::structs::{CreateThreadMessage, MergeThreads, PostMessage, SplitThread, User};
+
+    fn create_user(conn: &DbConnection, name: &str) -> User {
+        save_user(conn, name).unwrap();
+        let id = conn.query_row("SELECT id FROM USER WHERE name = ?", [name], |row| row.get(0)).unwrap();
+
+        User{id, name: name.to_string(), display_name: None, avatar_url: None}
+    }
+
+    // Messages are (content, reply_to), the first one starts the thread
+    fn create_test_thread(conn: &mut DbConnection, name: &str, messages: &[(&str, Option<u32>)], user: &User) -> u32 {
+        let thread_id = create_thread(conn, &CreateThreadMessage {
+            message: messages[0].0.to_string(),
+            thread_name: name.to_string(),
+            subsection_id: 1,
+            poll: None,
+            tags: None,
+            publish_at: None,
+        }, user).unwrap();
+
+        for (message, reply_to) in &messages[1..] {
+            save_message(conn, &PostMessage{message: message.to_string(), thread_id, reply_to: *reply_to}, user, false).unwrap();
+        }
+
+        thread_id
+    }
+
+    // (id, content, reply_to) of the thread messages in order
+    fn thread_messages(conn: &DbConnection, thread_id: u32) -> Vec<(u32, String, Option<u32>)> {
+        let mut stmt = conn.prepare("SELECT m.id, c.content, m.reply_to FROM MESSAGE m \
+            INNER JOIN MESSAGE_CONTENT_FTS c ON c.rowid = m.content_id WHERE m.thread_id = ? ORDER BY m.id").unwrap();
+
+        stmt.query_map([thread_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))).unwrap()
+            .collect::<Result<Vec<_>, _>>().unwrap()
+    }
+
+    fn location(conn: &DbConnection, thread_id: u32, message_id: Option<u32>) -> (u32, u32) {
+        let location = query_message_location(conn, thread_id, message_id, &None).unwrap();
+        (location.thread_id, location.message_id)
+    }
+
+    fn create_forum() -> (DbConnection, User, User) {
+        let conn = create_memory_db();
+        conn.execute("INSERT INTO SECTION(name) VALUES('section')", []).unwrap();
+        conn.execute("INSERT INTO SUBSECTION(section_id, name) VALUES(1, 'subsection')", []).unwrap();
+
+        let alice = create_user(&conn, "alice");
+        let bob = create_user(&conn, "bob");
+
+        (conn, alice, bob)
+    }
 
     #[test]
     fn it_works() {
@@ -1070,4 +2476,139 @@ mod tests {
         assert_eq!("Test AND query", reformat_fts_query("Test AND query"));
         assert_eq!("\"Test-123\" NOT query*", reformat_fts_query("Test-123 NOT query*"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn file_ids_are_extracted_from_markdown() {
+        let content = "![a.png](/files/u1f20230101T101010000i0 \"a.png\")\n\
+            [doc.pdf](/files/u2f20230101T101010000i1 \"doc.pdf\") and again /files/u1f20230101T101010000i0";
+
+        assert_eq!(vec!["u1f20230101T101010000i0", "u2f20230101T101010000i1"], extract_file_ids(content));
+        assert!(extract_file_ids("no files here /files/").is_empty());
+    }
+
+    #[test]
+    fn article_slugs() {
+        assert_eq!("runbooks/deploy/release-checklist", article_slug_base("Runbooks/Deploy", "Release checklist"));
+        assert_eq!("заметки/встреча-2023", article_slug_base("/Заметки//", "Встреча: 2023!"));
+        assert_eq!("article", article_slug_base("", "?!"));
+        assert_eq!("runbooks/deploy/release-checklist", slugify_path("runbooks/deploy/release-checklist"));
+    }
+
+    #[test]
+    fn article_links_are_extracted() {
+        let content = "See [[Runbooks/Deploy Guide]] and [[Runbooks/Deploy Guide|the guide]], \
+            [old](/article/a/12), [older](/article/7) and [wiki](/wiki/notes/meeting-1). [[broken\nlink]]";
+
+        assert_eq!(vec![
+            (ArticleLinkTarget::Slug("runbooks/deploy-guide".to_string()), "[[Runbooks/Deploy Guide]]".to_string()),
+            (ArticleLinkTarget::Id(12), "/article/a/12".to_string()),
+            (ArticleLinkTarget::Id(7), "/article/a/7".to_string()),
+            (ArticleLinkTarget::Slug("notes/meeting-1".to_string()), "/wiki/notes/meeting-1".to_string()),
+        ], extract_article_links(content));
+    }
+
+    #[test]
+    fn template_placeholders_are_expanded() {
+        let user = User{id: 1, name: "alice".to_string(), display_name: None, avatar_url: None};
+        let now = chrono::Utc.with_ymd_and_hms(2023, 3, 7, 9, 5, 0).unwrap();
+
+        assert_eq!("Meeting 2023-03-07 09:05 by alice, {{unknown}}",
+                   expand_template_placeholders("Meeting {{date}} {{time}} by {{user}}, {{unknown}}", &user, now));
+    }
+
+    #[test]
+    fn message_excerpt_is_one_line_and_cut() {
+        assert_eq!("> quoted text and the answer", message_excerpt("> quoted\n  text\n\nand the answer\n"));
+
+        let excerpt = message_excerpt(&"ж".repeat(MESSAGE_EXCERPT_LENGTH + 1));
+        assert_eq!(format!("{}…", "ж".repeat(MESSAGE_EXCERPT_LENGTH)), excerpt);
+    }
+
+    #[test]
+    fn merged_then_split_messages_keep_their_old_locations() {
+        let (mut conn, alice, bob) = create_forum();
+
+        let a = create_test_thread(&mut conn, "a", &[("a1", None), ("a2", Some(1)), ("a3 @bob", Some(2))], &alice);
+        let b = create_test_thread(&mut conn, "b", &[("b1", None), ("b2", None)], &alice);
+
+        merge_threads(&mut conn, &MergeThreads{thread_id: a, target_thread_id: b}).unwrap();
+
+        assert!(thread_messages(&conn, a).is_empty());
+        assert_eq!(vec![
+            (1, "b1".to_string(), None),
+            (2, "b2".to_string(), None),
+            (3, "a1".to_string(), None),
+            (4, "a2".to_string(), Some(3)),
+            (5, "a3 @bob".to_string(), Some(4)),
+        ], thread_messages(&conn, b));
+        assert_eq!((b, 4), location(&conn, a, Some(2)));
+
+        let c = split_thread(&mut conn, &SplitThread{thread_id: b, message_ids: vec![5, 4], thread_name: "c".to_string(), subsection_id: None}).unwrap();
+
+        assert_eq!(vec![(1, "a2".to_string(), None), (2, "a3 @bob".to_string(), Some(1))], thread_messages(&conn, c));
+        assert_eq!(3, thread_messages(&conn, b).len());
+
+        // redirects of the merge follow the messages into the new thread
+        assert_eq!((b, 3), location(&conn, a, Some(1)));
+        assert_eq!((c, 1), location(&conn, a, Some(2)));
+        assert_eq!((c, 2), location(&conn, a, Some(3)));
+        assert_eq!((c, 2), location(&conn, b, Some(5)));
+        assert_eq!((b, 3), location(&conn, a, None));
+        assert_eq!(2, query_message_location(&conn, a, Some(3), &None).unwrap().position);
+        assert!(query_message_location(&conn, b, Some(6), &None).is_err());
+
+        let mention: (u32, u32) = conn.query_row("SELECT thread_id, message_id FROM MENTION WHERE user_id = ?", [bob.id],
+                                                 |row| Ok((row.get(0)?, row.get(1)?))).unwrap();
+        assert_eq!((c, 2), mention);
+    }
+
+    #[test]
+    fn split_drops_replies_across_the_threads() {
+        let (mut conn, alice, _) = create_forum();
+
+        let a = create_test_thread(&mut conn, "a", &[("1", None), ("2", Some(1)), ("3", Some(2)), ("4", Some(1)), ("5", Some(3))], &alice);
+
+        let b = split_thread(&mut conn, &SplitThread{thread_id: a, message_ids: vec![2, 3], thread_name: "b".to_string(), subsection_id: None}).unwrap();
+
+        assert_eq!(vec![(1, "2".to_string(), None), (2, "3".to_string(), Some(1))], thread_messages(&conn, b));
+        assert_eq!(vec![
+            (1, "1".to_string(), None),
+            (4, "4".to_string(), Some(1)),
+            (5, "5".to_string(), None),
+        ], thread_messages(&conn, a));
+
+        let seq: (u32, u32) = conn.query_row("SELECT (SELECT message_seq FROM THREAD WHERE id = ?1), (SELECT message_seq FROM THREAD WHERE id = ?2)",
+                                             params![a, b], |row| Ok((row.get(0)?, row.get(1)?))).unwrap();
+        assert_eq!((5, 2), seq);
+
+        assert!(split_thread(&mut conn, &SplitThread{thread_id: a, message_ids: vec![1, 4, 5], thread_name: "c".to_string(), subsection_id: None}).is_err());
+    }
+
+    #[test]
+    fn messages_of_scheduled_threads_are_announced_on_publish() {
+        let (mut conn, alice, bob) = create_forum();
+
+        let thread_id = create_thread(&mut conn, &CreateThreadMessage {
+            message: "first".to_string(),
+            thread_name: "scheduled".to_string(),
+            subsection_id: 1,
+            poll: None,
+            tags: None,
+            publish_at: Some(chrono::Utc::now().timestamp_millis() + 3600 * 1000),
+        }, &alice).unwrap();
+
+        save_message(&mut conn, &PostMessage{message: "hi @bob".to_string(), thread_id, reply_to: None}, &alice, false).unwrap();
+        assert!(save_message(&mut conn, &PostMessage{message: "early".to_string(), thread_id, reply_to: None}, &bob, false).is_err());
+
+        let mentions = |conn: &DbConnection| -> u32 {
+            conn.query_row("SELECT count(1) FROM MENTION WHERE user_id = ?", [bob.id], |row| row.get(0)).unwrap()
+        };
+        assert_eq!(0, mentions(&conn));
+
+        conn.execute("UPDATE THREAD SET publish_time = 1 WHERE id = ?", [thread_id]).unwrap();
+        publish_due_threads(&mut conn).unwrap();
+
+        assert_eq!(1, mentions(&conn));
+        save_message(&mut conn, &PostMessage{message: "welcome".to_string(), thread_id, reply_to: None}, &bob, false).unwrap();
+    }
+}
@@ -0,0 +1,108 @@
+use rusqlite::{Connection, params};
+use crate::queries::{extract_user, is_thread_visible, QueryResult};
+use crate::structs::{LogicError, ReactionCount, ReactionParams, User, Users};
+
+pub enum ReactionTarget {
+    Message { thread_id: u32, message_id: u32 },
+    Comment { article_id: u32, comment_id: u32 },
+}
+
+impl ReactionTarget {
+    // Either threadId and messageId or articleId and commentId have to be set
+    pub fn from_params(params: &ReactionParams) -> Result<ReactionTarget, LogicError> {
+        match (params.thread_id, params.message_id, params.article_id, params.comment_id) {
+            (Some(thread_id), Some(message_id), None, None) => Ok(ReactionTarget::Message { thread_id, message_id }),
+            (None, None, Some(article_id), Some(comment_id)) => Ok(ReactionTarget::Comment { article_id, comment_id }),
+            _ => Err(LogicError::MessageNotFound)
+        }
+    }
+
+    // (thread_id, article_id, message_id) as stored in REACTION
+    fn key(&self) -> (u32, u32, u32) {
+        match self {
+            ReactionTarget::Message { thread_id, message_id } => (*thread_id, 0, *message_id),
+            ReactionTarget::Comment { article_id, comment_id } => (0, *article_id, *comment_id),
+        }
+    }
+}
+
+fn check_target_visible(conn: &Connection, target: &ReactionTarget, user: &Option<User>) -> QueryResult<()> {
+    let user_id = user.as_ref().map_or(0, |user| user.id);
+    let visible: bool = match target {
+        ReactionTarget::Message { thread_id, message_id } =>
+            is_thread_visible(conn, *thread_id, user)?
+                && conn.query_row("SELECT EXISTS(SELECT 1 FROM MESSAGE WHERE thread_id = ? AND id = ?)",
+                                  params![thread_id, message_id], |row| row.get(0))?,
+        ReactionTarget::Comment { article_id, comment_id } =>
+            conn.query_row("SELECT EXISTS(SELECT 1 FROM ARTICLE_COMMENT c INNER JOIN ARTICLE a ON a.id = c.article_id AND a.active = TRUE \
+                WHERE c.article_id = ? AND c.id = ? AND (a.visibility = 'public' OR a.user_id = ?))",
+                           params![article_id, comment_id, user_id], |row| row.get(0))?,
+    };
+
+    if !visible {
+        return Err(LogicError::MessageNotFound.into());
+    }
+
+    Ok(())
+}
+
+// Reaction counts in the order the reactions were first added
+pub fn query_reactions(conn: &Connection, target: ReactionTarget, user: &Option<User>) -> QueryResult<Vec<ReactionCount>> {
+    let (thread_id, article_id, message_id) = target.key();
+    let user_id = user.as_ref().map_or(0, |user| user.id);
+
+    let mut stmt = conn.prepare_cached(
+        "SELECT emoji, count(1), max(user_id = ?) FROM REACTION WHERE thread_id = ? AND article_id = ? AND message_id = ? \
+        GROUP BY emoji ORDER BY min(create_time)",
+    )?;
+
+    let reactions = stmt.query_map(params![user_id, thread_id, article_id, message_id], |row| Ok(ReactionCount {
+        emoji: row.get(0)?,
+        count: row.get(1)?,
+        reacted_by_me: row.get(2)?,
+    }))?.collect::<Result<Vec<ReactionCount>, _>>()?;
+
+    Ok(reactions)
+}
+
+pub fn add_reaction(conn: &Connection, target: ReactionTarget, emoji: &str, allowed: &[String], user: &User) -> QueryResult<()> {
+    if !allowed.iter().any(|a| a == emoji) {
+        return Err(LogicError::ReactionNotAllowed.into());
+    }
+
+    check_target_visible(conn, &target, &Some(user.clone()))?;
+
+    let (thread_id, article_id, message_id) = target.key();
+    let t = chrono::offset::Utc::now();
+    let t = t.timestamp_millis();
+
+    conn.execute("INSERT OR IGNORE INTO REACTION(thread_id, article_id, message_id, user_id, emoji, create_time) VALUES(?, ?, ?, ?, ?, ?)",
+                 params![thread_id, article_id, message_id, user.id, emoji, t])?;
+
+    Ok(())
+}
+
+pub fn remove_reaction(conn: &Connection, target: ReactionTarget, emoji: &str, user: &User) -> QueryResult<()> {
+    let (thread_id, article_id, message_id) = target.key();
+
+    conn.execute("DELETE FROM REACTION WHERE thread_id = ? AND article_id = ? AND message_id = ? AND user_id = ? AND emoji = ?",
+                 params![thread_id, article_id, message_id, user.id, emoji])?;
+
+    Ok(())
+}
+
+pub fn query_reaction_users(conn: &Connection, target: ReactionTarget, emoji: &str, user: &Option<User>) -> QueryResult<Users> {
+    check_target_visible(conn, &target, user)?;
+
+    let (thread_id, article_id, message_id) = target.key();
+
+    let mut stmt = conn.prepare(
+        "SELECT u.id, u.name, u.display_name, u.avatar_file_id FROM REACTION r INNER JOIN USER u ON u.id = r.user_id \
+        WHERE r.thread_id = ? AND r.article_id = ? AND r.message_id = ? AND r.emoji = ? ORDER BY r.create_time",
+    )?;
+
+    let users = stmt.query_map(params![thread_id, article_id, message_id, emoji], |row| extract_user(row, 0))?
+        .collect::<Result<Vec<User>, _>>()?;
+
+    Ok(Users{users})
+}
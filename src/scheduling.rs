@@ -0,0 +1,142 @@
+use std::time::Duration;
+use rusqlite::{Connection, params, TransactionBehavior};
+use crate::live_events::{article_event, LiveEvent, LiveEventKind, thread_event};
+use crate::queries::{announce_article, announce_thread, article_visibility_restriction, DbConnection, extract_user, QueryResult};
+use crate::structs::{ArticleInfo, ArticleVisibility, ScheduledArticle, ScheduledPublications, ScheduledThread, User};
+
+pub const SCHEDULER_INTERVAL: Duration = Duration::from_secs(30);
+
+// Publish times in the past mean publishing right away
+pub fn scheduled_time(publish_at: Option<i64>) -> Option<i64> {
+    let t = chrono::offset::Utc::now();
+    publish_at.filter(|publish_at| *publish_at > t.timestamp_millis())
+}
+
+struct DueThread {
+    id: u32,
+    subsection_id: u32,
+    name: String,
+    content: String,
+    author: User,
+}
+
+struct DueArticle {
+    id: u32,
+    path: String,
+    name: String,
+    content: String,
+    author: User,
+}
+
+// Makes the due threads visible as if they were created now, returns the live events to send
+pub fn publish_due_threads(conn: &mut DbConnection) -> QueryResult<Vec<LiveEvent>> {
+    let transaction = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+
+    let t = chrono::offset::Utc::now();
+    let t = t.timestamp_millis();
+
+    let mut stmt = transaction.prepare(
+        "SELECT t.id, t.subsection_id, tn.name, mc.content, u.id, u.name, u.display_name, u.avatar_file_id FROM THREAD t \
+        INNER JOIN THREAD_NAME_FTS tn ON tn.rowid = t.name_id \
+        INNER JOIN MESSAGE m ON m.thread_id = t.id AND m.id = 1 \
+        INNER JOIN MESSAGE_CONTENT_FTS mc ON mc.rowid = m.content_id \
+        INNER JOIN USER u ON u.id = t.author_id \
+        WHERE t.publish_time <= ? AND t.deleted = FALSE ORDER BY t.publish_time",
+    )?;
+
+    let threads = stmt.query_map([t], |row| Ok(DueThread {
+        id: row.get(0)?,
+        subsection_id: row.get(1)?,
+        name: row.get(2)?,
+        content: row.get(3)?,
+        author: extract_user(row, 4)?,
+    }))?.collect::<Result<Vec<DueThread>, _>>()?;
+    drop(stmt);
+
+    let mut events = Vec::new();
+    for thread in &threads {
+        transaction.execute("UPDATE THREAD SET publish_time = NULL, create_time = ?1, \
+            update_time = max(update_time, ?1) WHERE id = ?2", params![t, thread.id])?;
+        transaction.execute("UPDATE MESSAGE SET create_time = ?1, update_time = ?1 WHERE thread_id = ?2 AND id = 1", params![t, thread.id])?;
+
+        announce_thread(&transaction, thread.id, thread.subsection_id, &thread.name, &thread.content, &thread.author)?;
+        events.push(thread_event(&transaction, LiveEventKind::Thread, thread.id, Some(1), &thread.author)?);
+    }
+
+    transaction.commit()?;
+    Ok(events)
+}
+
+// Makes the due articles public, returns the live events to send
+pub fn publish_due_articles(conn: &mut DbConnection) -> QueryResult<Vec<LiveEvent>> {
+    let transaction = conn.transaction_with_behavior(TransactionBehavior::Exclusive)?;
+
+    let t = chrono::offset::Utc::now();
+    let t = t.timestamp_millis();
+
+    let mut stmt = transaction.prepare(
+        "SELECT a.id, a.path, c.name, c.content, u.id, u.name, u.display_name, u.avatar_file_id FROM ARTICLE_SCHEDULE s \
+        INNER JOIN ARTICLE a ON a.id = s.article_id AND a.active = TRUE AND a.visibility = 'scheduled' \
+        INNER JOIN ARTICLE_CONTENT_FTS c ON c.rowid = a.content_id \
+        INNER JOIN USER u ON u.id = a.user_id \
+        WHERE s.publish_time <= ? ORDER BY s.publish_time",
+    )?;
+
+    let articles = stmt.query_map([t], |row| Ok(DueArticle {
+        id: row.get(0)?,
+        path: row.get(1)?,
+        name: row.get(2)?,
+        content: row.get(3)?,
+        author: extract_user(row, 4)?,
+    }))?.collect::<Result<Vec<DueArticle>, _>>()?;
+    drop(stmt);
+
+    let mut events = Vec::new();
+    for article in &articles {
+        transaction.execute("UPDATE ARTICLE SET visibility = ?, create_time = ? WHERE id = ? AND active = TRUE",
+                            params![ArticleVisibility::Public, t, article.id])?;
+
+        announce_article(&transaction, article.id, &article.path, &article.name, &article.content, ArticleVisibility::Public, &article.author)?;
+        events.push(article_event(&transaction, LiveEventKind::Article, article.id, None, &article.author)?);
+    }
+
+    // schedules of published and deleted articles are dropped as well
+    transaction.execute("DELETE FROM ARTICLE_SCHEDULE WHERE publish_time <= ? \
+        OR article_id NOT IN (SELECT id FROM ARTICLE WHERE active = TRUE AND visibility = 'scheduled')", [t])?;
+
+    transaction.commit()?;
+    Ok(events)
+}
+
+pub fn query_scheduled_publications(conn: &Connection, user: &User) -> QueryResult<ScheduledPublications> {
+    let mut stmt = conn.prepare(
+        "SELECT t.id, t.subsection_id, tn.name, t.publish_time FROM THREAD t \
+        INNER JOIN THREAD_NAME_FTS tn ON tn.rowid = t.name_id \
+        WHERE t.author_id = ? AND t.deleted = FALSE AND t.publish_time IS NOT NULL ORDER BY t.publish_time",
+    )?;
+
+    let threads = stmt.query_map([user.id], |row| Ok(ScheduledThread {
+        id: row.get(0)?,
+        sub_section_id: row.get(1)?,
+        name: row.get(2)?,
+        publish_at: row.get(3)?,
+    }))?.collect::<Result<Vec<ScheduledThread>, _>>()?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT a.id, a.path, c.name, s.publish_time FROM ARTICLE_SCHEDULE s \
+        INNER JOIN ARTICLE a ON a.id = s.article_id AND a.active = TRUE AND a.visibility = 'scheduled' \
+        INNER JOIN ARTICLE_CONTENT_FTS c ON c.rowid = a.content_id \
+        WHERE {} ORDER BY s.publish_time", article_visibility_restriction(&Some(user.clone()))),
+    )?;
+
+    let articles = stmt.query_map([user.id], |row| Ok(ScheduledArticle {
+        info: ArticleInfo {
+            id: row.get(0)?,
+            path: row.get(1)?,
+            name: row.get(2)?,
+        },
+        publish_at: row.get(3)?,
+    }))?.collect::<Result<Vec<ScheduledArticle>, _>>()?;
+
+    Ok(ScheduledPublications{threads, articles})
+}
@@ -0,0 +1,81 @@
+use std::str::FromStr;
+use argon2::Params;
+
+const DEFAULT_REACTIONS: &str = "👍,👎,😄,🎉,😕,❤️,🚀,👀";
+
+#[derive(Clone)]
+pub struct Settings {
+    // Deleted articles are purged from the trash after this many days, 0 disables purging
+    pub trash_purge_days: u32,
+    // Public address of the site, used for links in emails
+    pub base_url: String,
+    // Email notifications are disabled when no SMTP host is set
+    pub smtp_host: Option<String>,
+    pub smtp_port: u16,
+    // none, starttls or tls
+    pub smtp_security: String,
+    pub smtp_user: Option<String>,
+    pub smtp_password: Option<String>,
+    pub email_from: String,
+    // Undelivered emails are retried with exponential backoff this many times
+    pub email_max_attempts: u32,
+    // Same for webhook deliveries
+    pub webhook_max_attempts: u32,
+    // Emoji allowed as reactions to messages and comments
+    pub reactions: Vec<String>,
+    // Drafts not changed for this many days are removed, 0 keeps them forever
+    pub draft_expire_days: u32,
+    // Password policy for new passwords, see passwords::check_password_strength
+    pub password_min_length: usize,
+    pub password_min_classes: usize,
+    // Argon2id costs for new password hashes, older hashes are upgraded on sign on
+    pub argon2_params: Params,
+    // Password reset links expire after this many hours
+    pub password_reset_hours: u32,
+    // A user gets at most one requested reset link by email within this many minutes
+    pub password_reset_cooldown_minutes: u32,
+}
+
+impl Settings {
+    pub fn from_env() -> Settings {
+        Settings {
+            trash_purge_days: env_or("ARK_TRASH_PURGE_DAYS", 30),
+            base_url: env_or("ARK_BASE_URL", "http://localhost:8080".to_string()).trim_end_matches('/').to_string(),
+            smtp_host: env_opt("ARK_SMTP_HOST"),
+            smtp_port: env_or("ARK_SMTP_PORT", 25),
+            smtp_security: env_or("ARK_SMTP_SECURITY", "none".to_string()),
+            smtp_user: env_opt("ARK_SMTP_USER"),
+            smtp_password: env_opt("ARK_SMTP_PASSWORD"),
+            email_from: env_or("ARK_EMAIL_FROM", "The Ark <noreply@localhost>".to_string()),
+            email_max_attempts: env_or("ARK_EMAIL_MAX_ATTEMPTS", 8),
+            webhook_max_attempts: env_or("ARK_WEBHOOK_MAX_ATTEMPTS", 8),
+            reactions: env_or("ARK_REACTIONS", DEFAULT_REACTIONS.to_string())
+                .split(',')
+                .map(|r| r.trim().to_string())
+                .filter(|r| !r.is_empty())
+                .collect(),
+            draft_expire_days: env_or("ARK_DRAFT_EXPIRE_DAYS", 30),
+            password_min_length: env_or("ARK_PASSWORD_MIN_LENGTH", 8),
+            password_min_classes: env_or("ARK_PASSWORD_MIN_CLASSES", 2),
+            argon2_params: argon2_params(env_or("ARK_ARGON2_MEMORY_KIB", 19456), env_or("ARK_ARGON2_TIME_COST", 2)),
+            password_reset_hours: env_or("ARK_PASSWORD_RESET_HOURS", 24),
+            password_reset_cooldown_minutes: env_or("ARK_PASSWORD_RESET_COOLDOWN_MINUTES", 15),
+        }
+    }
+}
+
+fn env_or<T: FromStr>(name: &str, default: T) -> T {
+    match std::env::var(name) {
+        Ok(value) => value.parse().unwrap_or_else(|_| panic!("Invalid value of {}: {}", name, value)),
+        Err(_) => default
+    }
+}
+
+fn argon2_params(memory_kib: u32, time_cost: u32) -> Params {
+    Params::new(memory_kib, time_cost, Params::DEFAULT_P_COST, None)
+        .unwrap_or_else(|e| panic!("Invalid Argon2 costs {} KiB, {} passes: {}", memory_kib, time_cost, e))
+}
+
+fn env_opt(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
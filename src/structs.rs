@@ -13,6 +13,8 @@ pub struct ThreadQuery {
     pub query_type: ThreadsQueryType,
     pub id: Option<u32>,
     pub page: u32,
+    // comma separated, threads having all of the tags
+    pub tags: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -21,16 +23,70 @@ pub struct ThreadQueryResult {
     pub count: u32,
 }
 
+// name is the login, display name and avatar are set in the profile
 #[derive(Serialize, Clone)]
 pub struct User {
     pub id: u32,
     pub name: String,
+    #[serde(rename = "displayName")]
+    pub display_name: Option<String>,
+    #[serde(rename = "avatarUrl")]
+    pub avatar_url: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct UserProfile {
+    pub user: User,
+    pub bio: Option<String>,
+    pub timezone: Option<String>,
+    pub locale: Option<String>,
+}
+
+// Missing fields are kept and empty ones cleared, avatarFileId is an image uploaded through /api/upload-files
+#[derive(Deserialize)]
+pub struct UpdateProfile {
+    #[serde(rename = "displayName")]
+    pub display_name: Option<String>,
+    #[serde(rename = "avatarFileId")]
+    pub avatar_file_id: Option<String>,
+    pub bio: Option<String>,
+    pub timezone: Option<String>,
+    pub locale: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ProfileQuery {
+    #[serde(rename = "userId")]
+    pub user_id: u32,
+}
+
+#[derive(Deserialize)]
+pub struct ProfileActivityQuery {
+    #[serde(rename = "userId")]
+    pub user_id: u32,
+    pub page: u32,
+}
+
+#[derive(Serialize)]
+pub struct ProfileComment {
+    pub id: u32,
+    pub article: ArticleInfo,
+    #[serde(rename = "createTime")]
+    pub create_time: u64,
+    pub content: String,
+}
+
+#[derive(Serialize)]
+pub struct ProfileComments {
+    pub comments: Vec<ProfileComment>,
 }
 
 #[derive(Serialize)]
 pub struct Section {
     pub id: u32,
     pub name: String,
+    #[serde(rename = "sortOrder")]
+    pub sort_order: i32,
 }
 
 #[derive(Serialize)]
@@ -39,6 +95,16 @@ pub struct SubSection {
     #[serde(rename = "sectionId")]
     pub section_id: u32,
     pub name: String,
+    pub description: String,
+    #[serde(rename = "sortOrder")]
+    pub sort_order: i32,
+}
+
+#[derive(Serialize)]
+pub struct DeletedSections {
+    pub sections: Vec<Section>,
+    #[serde(rename = "subSections")]
+    pub subsections: Vec<SubSection>,
 }
 
 #[derive(Serialize)]
@@ -69,6 +135,60 @@ pub struct Thread {
     pub last_message_id: u32,
     #[serde(rename = "lastMessageDateTime")]
     pub last_message_date_time: u64,
+    #[serde(rename = "unreadCount")]
+    pub unread_count: Option<u32>,
+    #[serde(rename = "firstUnreadMessageId")]
+    pub first_unread_message_id: Option<u32>,
+    #[serde(rename = "firstUnreadPage")]
+    pub first_unread_page: Option<u32>,
+    pub pinned: ThreadPin,
+    pub locked: bool,
+    pub announcement: bool,
+    pub tags: Vec<String>,
+    // set until the scheduled thread is published
+    #[serde(rename = "publishAt")]
+    pub publish_at: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone)]
+pub enum ThreadPin {
+    #[serde(rename = "none")]
+    None,
+    #[serde(rename = "subsection")]
+    Subsection,
+    #[serde(rename = "global")]
+    Global
+}
+
+impl ToSql for ThreadPin {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        match self {
+            ThreadPin::None => Ok(ToSqlOutput::from(0)),
+            ThreadPin::Subsection => Ok(ToSqlOutput::from(1)),
+            ThreadPin::Global => Ok(ToSqlOutput::from(2))
+        }
+    }
+}
+
+impl FromSql for ThreadPin {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_i64()? {
+            0 => Ok(ThreadPin::None),
+            1 => Ok(ThreadPin::Subsection),
+            2 => Ok(ThreadPin::Global),
+            _ => Err(InvalidType)
+        }
+    }
+}
+
+// Only the flags present in the request are changed
+#[derive(Deserialize)]
+pub struct UpdateThreadFlags {
+    #[serde(rename = "threadId")]
+    pub thread_id: u32,
+    pub pinned: Option<ThreadPin>,
+    pub locked: Option<bool>,
+    pub announcement: Option<bool>,
 }
 
 #[derive(Serialize)]
@@ -82,6 +202,32 @@ pub struct Message {
     #[serde(rename = "updateTime")]
     pub update_time: u64,
     pub content: String,
+    pub mentions: Vec<MentionSpan>,
+    pub reactions: Vec<ReactionCount>,
+    #[serde(rename = "replyTo")]
+    pub reply_to: Option<MessagePreview>,
+}
+
+// Compact form of a message for reply context, page is where the message is in the thread
+#[derive(Serialize)]
+pub struct MessagePreview {
+    pub id: u32,
+    pub user: User,
+    pub excerpt: String,
+    pub page: u32,
+}
+
+#[derive(Deserialize)]
+pub struct MessageRepliesQuery {
+    #[serde(rename = "threadId")]
+    pub thread_id: u32,
+    #[serde(rename = "messageId")]
+    pub message_id: u32,
+}
+
+#[derive(Serialize)]
+pub struct MessageReplies {
+    pub replies: Vec<MessagePreview>,
 }
 
 #[derive(Serialize)]
@@ -95,6 +241,53 @@ pub struct Comment {
     #[serde(rename = "updateTime")]
     pub update_time: u64,
     pub content: String,
+    pub mentions: Vec<MentionSpan>,
+    pub reactions: Vec<ReactionCount>,
+}
+
+#[derive(Serialize)]
+pub struct ReactionCount {
+    pub emoji: String,
+    pub count: u32,
+    #[serde(rename = "reactedByMe")]
+    pub reacted_by_me: bool,
+}
+
+// A message is identified by threadId and messageId, a comment by articleId and commentId
+#[derive(Deserialize)]
+pub struct ReactionParams {
+    #[serde(rename = "threadId")]
+    pub thread_id: Option<u32>,
+    #[serde(rename = "messageId")]
+    pub message_id: Option<u32>,
+    #[serde(rename = "articleId")]
+    pub article_id: Option<u32>,
+    #[serde(rename = "commentId")]
+    pub comment_id: Option<u32>,
+    pub emoji: String,
+}
+
+#[derive(Serialize)]
+pub struct AllowedReactions {
+    pub reactions: Vec<String>,
+}
+
+// start and end are offsets in UTF-16 code units, as used by JS strings
+#[derive(Serialize)]
+pub struct MentionSpan {
+    pub start: u32,
+    pub end: u32,
+    pub user: User,
+}
+
+#[derive(Deserialize)]
+pub struct UserSearchQuery {
+    pub prefix: String,
+}
+
+#[derive(Serialize)]
+pub struct Users {
+    pub users: Vec<User>,
 }
 
 #[derive(Deserialize)]
@@ -107,6 +300,71 @@ pub struct PostNewSubsection {
     pub name: String,
     #[serde(rename = "sectionId")]
     pub section_id: u32,
+    pub description: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateSubsectionDescription {
+    pub description: String,
+    #[serde(rename = "subsectionId")]
+    pub subsection_id: u32,
+}
+
+// What happens to the threads of a deleted section or subsection
+#[derive(Deserialize, Copy, Clone)]
+pub enum DeletedThreadsPolicy {
+    // the deletion fails if there are threads
+    #[serde(rename = "block")]
+    Block,
+    // the threads are moved to targetSubsectionId
+    #[serde(rename = "move")]
+    Move,
+    // the threads are deleted and restored together with the section or subsection
+    #[serde(rename = "delete")]
+    Delete
+}
+
+#[derive(Deserialize)]
+pub struct DeleteSection {
+    #[serde(rename = "sectionId")]
+    pub section_id: u32,
+    pub threads: DeletedThreadsPolicy,
+    #[serde(rename = "targetSubsectionId")]
+    pub target_subsection_id: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct DeleteSubsection {
+    #[serde(rename = "subsectionId")]
+    pub subsection_id: u32,
+    pub threads: DeletedThreadsPolicy,
+    #[serde(rename = "targetSubsectionId")]
+    pub target_subsection_id: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct RestoreSection {
+    #[serde(rename = "sectionId")]
+    pub section_id: u32,
+}
+
+#[derive(Deserialize)]
+pub struct RestoreSubsection {
+    #[serde(rename = "subsectionId")]
+    pub subsection_id: u32,
+}
+
+// Sort order is the position in the list
+#[derive(Deserialize)]
+pub struct ReorderSections {
+    #[serde(rename = "sectionIds")]
+    pub section_ids: Vec<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct ReorderSubsections {
+    #[serde(rename = "subsectionIds")]
+    pub subsection_ids: Vec<u32>,
 }
 
 #[derive(Deserialize)]
@@ -144,6 +402,87 @@ pub struct DeleteThread {
     pub thread_id: u32,
 }
 
+#[derive(Deserialize)]
+pub struct MergeThreads {
+    #[serde(rename = "threadId")]
+    pub thread_id: u32,
+    #[serde(rename = "targetThreadId")]
+    pub target_thread_id: u32,
+}
+
+#[derive(Deserialize)]
+pub struct SplitThread {
+    #[serde(rename = "threadId")]
+    pub thread_id: u32,
+    #[serde(rename = "messageIds")]
+    pub message_ids: Vec<u32>,
+    #[serde(rename = "threadName")]
+    pub thread_name: String,
+    // the subsection of the original thread if not set
+    #[serde(rename = "subsectionId")]
+    pub subsection_id: Option<u32>,
+}
+
+#[derive(Serialize)]
+pub struct SplitThreadResult {
+    #[serde(rename = "threadId")]
+    pub thread_id: u32,
+}
+
+#[derive(Deserialize)]
+pub struct MessageLocateQuery {
+    #[serde(rename = "threadId")]
+    pub thread_id: u32,
+    #[serde(rename = "messageId")]
+    pub message_id: Option<u32>,
+}
+
+// Current location of a message, position is 1-based within the thread
+#[derive(Serialize)]
+pub struct MessageLocation {
+    #[serde(rename = "threadId")]
+    pub thread_id: u32,
+    #[serde(rename = "messageId")]
+    pub message_id: u32,
+    pub page: u32,
+    pub position: u32,
+}
+
+#[derive(Deserialize)]
+pub struct CommentLocateQuery {
+    #[serde(rename = "articleId")]
+    pub article_id: u32,
+    #[serde(rename = "commentId")]
+    pub comment_id: u32,
+}
+
+#[derive(Serialize)]
+pub struct CommentLocation {
+    #[serde(rename = "articleId")]
+    pub article_id: u32,
+    #[serde(rename = "articleVersion")]
+    pub article_version: u32,
+    #[serde(rename = "commentId")]
+    pub comment_id: u32,
+    pub page: u32,
+    pub position: u32,
+}
+
+#[derive(Deserialize)]
+pub struct MarkThreadRead {
+    #[serde(rename = "threadId")]
+    pub thread_id: u32,
+    #[serde(rename = "messageId")]
+    pub message_id: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct MarkThreadsRead {
+    #[serde(rename = "queryType")]
+    pub query_type: ThreadsQueryType,
+    pub id: Option<u32>,
+}
+
 #[derive(Deserialize)]
 pub struct MoveSubsection {
     #[serde(rename = "subsectionId")]
@@ -157,6 +496,8 @@ pub struct PostMessage {
     pub message: String,
     #[serde(rename = "threadId")]
     pub thread_id: u32,
+    #[serde(rename = "replyTo")]
+    pub reply_to: Option<u32>,
 }
 
 #[derive(Deserialize)]
@@ -166,6 +507,65 @@ pub struct CreateThreadMessage {
     pub thread_name: String,
     #[serde(rename = "subsectionId")]
     pub subsection_id: u32,
+    pub poll: Option<CreatePoll>,
+    pub tags: Option<Vec<String>>,
+    #[serde(rename = "publishAt")]
+    pub publish_at: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct CreatePoll {
+    pub question: String,
+    pub options: Vec<String>,
+    #[serde(default)]
+    pub multiple: bool,
+    #[serde(default)]
+    pub anonymous: bool,
+    #[serde(rename = "hideResults", default)]
+    pub hide_results: bool,
+    #[serde(rename = "closeTime")]
+    pub close_time: Option<i64>,
+}
+
+// votes, voters and totalVoters are not set while the results are hidden,
+// voters are never set for anonymous polls
+#[derive(Serialize)]
+pub struct Poll {
+    pub question: String,
+    pub multiple: bool,
+    pub anonymous: bool,
+    #[serde(rename = "hideResults")]
+    pub hide_results: bool,
+    #[serde(rename = "closeTime")]
+    pub close_time: Option<i64>,
+    pub closed: bool,
+    pub options: Vec<PollOption>,
+    #[serde(rename = "totalVoters")]
+    pub total_voters: Option<u32>,
+    #[serde(rename = "myVotes")]
+    pub my_votes: Vec<u32>,
+}
+
+#[derive(Serialize)]
+pub struct PollOption {
+    pub id: u32,
+    pub text: String,
+    pub votes: Option<u32>,
+    pub voters: Option<Vec<User>>,
+}
+
+#[derive(Deserialize)]
+pub struct PollQuery {
+    #[serde(rename = "threadId")]
+    pub thread_id: u32,
+}
+
+#[derive(Deserialize)]
+pub struct PollVote {
+    #[serde(rename = "threadId")]
+    pub thread_id: u32,
+    #[serde(rename = "optionIds")]
+    pub option_ids: Vec<u32>,
 }
 
 #[derive(Deserialize)]
@@ -235,7 +635,69 @@ pub struct SignOnParams {
 pub struct SignUpParams {
     #[serde(rename = "userName")]
     pub user_name: String,
+    // Plain password, checked against the password policy
+    pub password: String,
+}
+
+#[derive(Deserialize)]
+pub struct ChangePassword {
+    #[serde(rename = "currentPassword")]
+    pub current_password: String,
+    #[serde(rename = "newPassword")]
+    pub new_password: String,
+}
+
+#[derive(Serialize)]
+pub struct PasswordPolicy {
+    #[serde(rename = "minLength")]
+    pub min_length: usize,
+    // Number of character kinds (lowercase, uppercase, digits, other) the password must contain
+    #[serde(rename = "minClasses")]
+    pub min_classes: usize,
+}
+
+#[derive(Deserialize)]
+pub struct RequestPasswordReset {
+    #[serde(rename = "userName")]
+    pub user_name: String,
+}
+
+#[derive(Deserialize)]
+pub struct CreatePasswordReset {
+    #[serde(rename = "userId")]
+    pub user_id: u32,
+}
+
+#[derive(Serialize)]
+pub struct PasswordResetLink {
+    pub url: String,
+    #[serde(rename = "expireTime")]
+    pub expire_time: i64,
+    // Whether the link was also sent to the user's email
+    pub emailed: bool,
+}
+
+#[derive(Serialize)]
+pub struct PasswordSchemeCount {
+    // PHC algorithm id like argon2id or pbkdf2-sha256
+    pub scheme: String,
+    pub count: u32,
+}
+
+#[derive(Serialize)]
+pub struct PasswordHashReport {
+    pub total: u32,
+    // Accounts not on Argon2id yet, they are upgraded on the next sign on
+    pub legacy: u32,
+    // Argon2id hashes made with other memory or time costs than the current ones
+    pub outdated: u32,
+    pub schemes: Vec<PasswordSchemeCount>,
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordForm {
     pub password: String,
+    pub confirmation: String,
 }
 
 #[derive(Deserialize)]
@@ -267,6 +729,36 @@ pub struct UploadedFileWithLocation {
     pub file_path: String,
 }
 
+#[derive(Deserialize)]
+pub struct ArticleAttachmentsQuery {
+    #[serde(rename = "articleId")]
+    pub article_id: u32,
+}
+
+#[derive(Serialize)]
+pub struct ArticleAttachment {
+    pub file: UploadedFile,
+    pub size: Option<u64>,
+    pub user: User,
+    pub versions: Vec<u32>,
+    pub attached: bool,
+    #[serde(skip_serializing)]
+    pub file_path: String,
+}
+
+#[derive(Serialize)]
+pub struct ArticleAttachments {
+    pub attachments: Vec<ArticleAttachment>,
+}
+
+#[derive(Deserialize)]
+pub struct RemoveArticleAttachment {
+    #[serde(rename = "articleId")]
+    pub article_id: u32,
+    #[serde(rename = "fileId")]
+    pub file_id: String,
+}
+
 #[derive(Debug, Clone)]
 pub enum LogicError {
     CreateError,
@@ -275,9 +767,36 @@ pub enum LogicError {
     SubsectionNotFound,
     ThreadNotFound,
     MessageNotFound,
+    FileNotFound,
     InvalidPass,
     NoSession,
     UserNotLoggedIn,
+    AccessDenied,
+    ArticleNotDeleted,
+    TemplateNotFound,
+    InvalidEmail,
+    WebhookNotFound,
+    InvalidWebhookUrl,
+    ThreadLocked,
+    CannotMergeThreadIntoItself,
+    InvalidMessageSelection,
+    SectionNotEmpty,
+    ReactionNotAllowed,
+    InvalidReplyTo,
+    InvalidPoll,
+    PollNotFound,
+    PollClosed,
+    InvalidPollVote,
+    InvalidTag,
+    TagNotFound,
+    TagExists,
+    InvalidDraftContext,
+    InvalidPublishTime,
+    UserNotFound,
+    InvalidProfile,
+    WeakPassword,
+    HashedPassword,
+    WrongPassword,
 }
 
 impl std::fmt::Display for LogicError {
@@ -289,9 +808,36 @@ impl std::fmt::Display for LogicError {
             LogicError::SubsectionNotFound => {write!(f, "Subsection not found")}
             LogicError::ThreadNotFound => {write!(f, "Thread not found")}
             LogicError::MessageNotFound => {write!(f, "Message not found")}
+            LogicError::FileNotFound => {write!(f, "File not found")}
             LogicError::InvalidPass => {write!(f, "Invalid pass")}
             LogicError::NoSession => {write!(f, "No session")}
             LogicError::UserNotLoggedIn => {write!(f, "User is not logged in")}
+            LogicError::AccessDenied => {write!(f, "Access denied")}
+            LogicError::ArticleNotDeleted => {write!(f, "Article is not deleted")}
+            LogicError::TemplateNotFound => {write!(f, "Template not found")}
+            LogicError::InvalidEmail => {write!(f, "Invalid email")}
+            LogicError::WebhookNotFound => {write!(f, "Webhook not found")}
+            LogicError::InvalidWebhookUrl => {write!(f, "Webhook url must start with http:// or https://")}
+            LogicError::ThreadLocked => {write!(f, "Thread is locked")}
+            LogicError::CannotMergeThreadIntoItself => {write!(f, "Thread can't be merged into itself")}
+            LogicError::InvalidMessageSelection => {write!(f, "Some but not all messages of the thread must be selected")}
+            LogicError::SectionNotEmpty => {write!(f, "Section or subsection has threads")}
+            LogicError::ReactionNotAllowed => {write!(f, "Reaction is not allowed")}
+            LogicError::InvalidReplyTo => {write!(f, "Replied message is not in the thread")}
+            LogicError::InvalidPoll => {write!(f, "Poll must have a question and from 2 to {} options", crate::polls::MAX_POLL_OPTIONS)}
+            LogicError::PollNotFound => {write!(f, "Poll not found")}
+            LogicError::PollClosed => {write!(f, "Poll is closed")}
+            LogicError::InvalidPollVote => {write!(f, "Invalid poll options")}
+            LogicError::InvalidTag => {write!(f, "Invalid tag")}
+            LogicError::TagNotFound => {write!(f, "Tag not found")}
+            LogicError::TagExists => {write!(f, "Tag already exists")}
+            LogicError::InvalidDraftContext => {write!(f, "Invalid draft context")}
+            LogicError::InvalidPublishTime => {write!(f, "Scheduled visibility requires a publish time in the future")}
+            LogicError::UserNotFound => {write!(f, "User not found")}
+            LogicError::InvalidProfile => {write!(f, "Invalid profile: check the avatar, timezone and locale")}
+            LogicError::WeakPassword => {write!(f, "Password does not meet the password policy")}
+            LogicError::HashedPassword => {write!(f, "Password looks like the sign on hash, send the plain password")}
+            LogicError::WrongPassword => {write!(f, "Wrong password")}
         }
     }
 }
@@ -309,6 +855,7 @@ pub struct MessagesQuery {
 pub struct MessagesQueryResult {
     pub thread: Thread,
     pub messages: Vec<Message>,
+    pub poll: Option<Poll>,
 }
 
 #[derive(Deserialize)]
@@ -339,6 +886,106 @@ pub struct Articles {
     pub articles: Vec<ArticleInfo>,
 }
 
+#[derive(Deserialize)]
+pub struct ArticlesQuery {
+    // comma separated, articles having all of the tags
+    pub tags: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct TagCount {
+    pub name: String,
+    pub threads: u32,
+    pub articles: u32,
+}
+
+#[derive(Serialize)]
+pub struct Tags {
+    pub tags: Vec<TagCount>,
+}
+
+#[derive(Deserialize)]
+pub struct TagSearchQuery {
+    pub prefix: String,
+}
+
+#[derive(Deserialize)]
+pub struct SetThreadTags {
+    #[serde(rename = "threadId")]
+    pub thread_id: u32,
+    pub tags: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct RenameTag {
+    pub name: String,
+    #[serde(rename = "newName")]
+    pub new_name: String,
+}
+
+#[derive(Deserialize)]
+pub struct MergeTags {
+    pub tags: Vec<String>,
+    pub into: String,
+}
+
+// context is thread:{id}, subsection:{id}, article:{id} or new-article,
+// title is the name of a new thread or of the article
+#[derive(Serialize)]
+pub struct Draft {
+    pub context: String,
+    pub title: Option<String>,
+    pub content: String,
+    #[serde(rename = "updateTime")]
+    pub update_time: i64,
+}
+
+#[derive(Serialize)]
+pub struct Drafts {
+    pub drafts: Vec<Draft>,
+}
+
+#[derive(Deserialize)]
+pub struct SaveDraft {
+    pub context: String,
+    pub title: Option<String>,
+    pub content: String,
+}
+
+#[derive(Deserialize)]
+pub struct DraftQuery {
+    pub context: String,
+}
+
+#[derive(Deserialize)]
+pub struct DeleteDraft {
+    pub context: String,
+}
+
+#[derive(Serialize)]
+pub struct ScheduledThread {
+    pub id: u32,
+    #[serde(rename = "subSectionId")]
+    pub sub_section_id: u32,
+    pub name: String,
+    #[serde(rename = "publishAt")]
+    pub publish_at: i64,
+}
+
+#[derive(Serialize)]
+pub struct ScheduledArticle {
+    pub info: ArticleInfo,
+    #[serde(rename = "publishAt")]
+    pub publish_at: i64,
+}
+
+// Threads and articles of the user waiting for publication
+#[derive(Serialize)]
+pub struct ScheduledPublications {
+    pub threads: Vec<ScheduledThread>,
+    pub articles: Vec<ScheduledArticle>,
+}
+
 #[derive(Serialize)]
 pub enum SearchResultFragment {
     Normal(String),
@@ -370,7 +1017,10 @@ pub enum ArticleVisibility {
     #[serde(rename = "public")]
     Public,
     #[serde(rename = "private")]
-    Private
+    Private,
+    // visible only to the author until published
+    #[serde(rename = "scheduled")]
+    Scheduled
 }
 
 #[derive(Deserialize)]
@@ -386,7 +1036,8 @@ impl ToSql for ArticleVisibility {
     fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
         match self {
             ArticleVisibility::Public => Ok(ToSqlOutput::from("public")),
-            ArticleVisibility::Private => Ok(ToSqlOutput::from("private"))
+            ArticleVisibility::Private => Ok(ToSqlOutput::from("private")),
+            ArticleVisibility::Scheduled => Ok(ToSqlOutput::from("scheduled"))
         }
     }
 }
@@ -397,6 +1048,7 @@ impl FromSql for ArticleVisibility {
         match str {
             "public" => Ok(ArticleVisibility:: Public),
             "private" => Ok(ArticleVisibility:: Private),
+            "scheduled" => Ok(ArticleVisibility:: Scheduled),
             _ => Err(InvalidType)
         }
     }
@@ -406,6 +1058,7 @@ impl FromSql for ArticleVisibility {
 pub struct Article {
     pub info: ArticleInfo,
     pub content: String,
+    pub mentions: Vec<MentionSpan>,
     pub user: User,
     #[serde(rename = "createTime")]
     pub create_time: u64,
@@ -416,8 +1069,13 @@ pub struct Article {
     #[serde(rename = "commentsCount")]
     pub comments_count: u32,
     pub visibility: ArticleVisibility,
+    pub slug: Option<String>,
 
     pub versions: Vec<ArticleVersion>,
+    pub backlinks: Vec<ArticleInfo>,
+    pub tags: Vec<String>,
+    #[serde(rename = "publishAt")]
+    pub publish_at: Option<i64>,
 }
 
 #[derive(Deserialize)]
@@ -425,7 +1083,42 @@ pub struct CreateArticle {
     pub path: String,
     pub name: String,
     pub content: String,
+    pub visibility: Option<ArticleVisibility>,
+    #[serde(rename = "templateId")]
+    pub template_id: Option<u32>,
+    pub tags: Option<Vec<String>>,
+    #[serde(rename = "publishAt")]
+    pub publish_at: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct ArticleTemplate {
+    pub id: u32,
+    pub name: String,
+    #[serde(rename = "pathPrefix")]
+    pub path_prefix: String,
+    pub visibility: ArticleVisibility,
+    pub content: String,
+}
+
+#[derive(Serialize)]
+pub struct ArticleTemplates {
+    pub templates: Vec<ArticleTemplate>,
+}
+
+#[derive(Deserialize)]
+pub struct SaveArticleTemplate {
+    pub id: Option<u32>,
+    pub name: String,
+    #[serde(rename = "pathPrefix")]
+    pub path_prefix: String,
     pub visibility: ArticleVisibility,
+    pub content: String,
+}
+
+#[derive(Deserialize)]
+pub struct DeleteArticleTemplate {
+    pub id: u32,
 }
 
 #[derive(Deserialize)]
@@ -435,6 +1128,7 @@ pub struct UpdateArticle {
     pub name: String,
     pub content: String,
     pub visibility: ArticleVisibility,
+    pub tags: Option<Vec<String>>,
 }
 
 #[derive(Deserialize)]
@@ -442,6 +1136,49 @@ pub struct DeleteArticle {
     pub id: u32,
 }
 
+#[derive(Serialize)]
+pub struct BrokenLink {
+    pub source: ArticleInfo,
+    pub link: String,
+}
+
+#[derive(Serialize)]
+pub struct BrokenLinks {
+    pub links: Vec<BrokenLink>,
+}
+
+#[derive(Deserialize)]
+pub struct GetArticleByPath {
+    pub path: String,
+}
+
+#[derive(Deserialize)]
+pub struct RestoreArticle {
+    pub id: u32,
+}
+
+#[derive(Deserialize)]
+pub struct TrashQuery {
+    pub all: Option<bool>,
+}
+
+#[derive(Serialize)]
+pub struct DeletedArticle {
+    pub info: ArticleInfo,
+    pub version: u32,
+    #[serde(rename = "deleteTime")]
+    pub delete_time: Option<u64>,
+    #[serde(rename = "deletedBy")]
+    pub deleted_by: Option<User>,
+}
+
+#[derive(Serialize)]
+pub struct DeletedArticles {
+    pub articles: Vec<DeletedArticle>,
+    #[serde(rename = "purgeDays")]
+    pub purge_days: u32,
+}
+
 #[derive(Deserialize)]
 pub struct AddFavoriteArticle {
     pub id: u32,
@@ -457,3 +1194,338 @@ pub struct GetArticle {
     pub id: u32,
     pub version: Option<u32>,
 }
+
+
+#[derive(Serialize, Deserialize, Copy, Clone)]
+pub enum SubscriptionTarget {
+    #[serde(rename = "thread")]
+    Thread,
+    #[serde(rename = "subsection")]
+    Subsection,
+    #[serde(rename = "article")]
+    Article
+}
+
+impl ToSql for SubscriptionTarget {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        match self {
+            SubscriptionTarget::Thread => Ok(ToSqlOutput::from("thread")),
+            SubscriptionTarget::Subsection => Ok(ToSqlOutput::from("subsection")),
+            SubscriptionTarget::Article => Ok(ToSqlOutput::from("article"))
+        }
+    }
+}
+
+impl FromSql for SubscriptionTarget {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let str = value.as_str()?;
+        match str {
+            "thread" => Ok(SubscriptionTarget::Thread),
+            "subsection" => Ok(SubscriptionTarget::Subsection),
+            "article" => Ok(SubscriptionTarget::Article),
+            _ => Err(InvalidType)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Subscribe {
+    #[serde(rename = "targetType")]
+    pub target_type: SubscriptionTarget,
+    #[serde(rename = "targetId")]
+    pub target_id: u32,
+}
+
+#[derive(Serialize)]
+pub struct Subscription {
+    #[serde(rename = "targetType")]
+    pub target_type: SubscriptionTarget,
+    #[serde(rename = "targetId")]
+    pub target_id: u32,
+    pub title: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Subscriptions {
+    pub subscriptions: Vec<Subscription>,
+}
+
+#[derive(Serialize, Copy, Clone)]
+pub enum NotificationKind {
+    #[serde(rename = "message")]
+    Message,
+    #[serde(rename = "thread")]
+    Thread,
+    #[serde(rename = "comment")]
+    Comment,
+    #[serde(rename = "articleUpdate")]
+    ArticleUpdate,
+    #[serde(rename = "mention")]
+    Mention
+}
+
+impl ToSql for NotificationKind {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        match self {
+            NotificationKind::Message => Ok(ToSqlOutput::from("message")),
+            NotificationKind::Thread => Ok(ToSqlOutput::from("thread")),
+            NotificationKind::Comment => Ok(ToSqlOutput::from("comment")),
+            NotificationKind::ArticleUpdate => Ok(ToSqlOutput::from("article_update")),
+            NotificationKind::Mention => Ok(ToSqlOutput::from("mention"))
+        }
+    }
+}
+
+impl FromSql for NotificationKind {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let str = value.as_str()?;
+        match str {
+            "message" => Ok(NotificationKind::Message),
+            "thread" => Ok(NotificationKind::Thread),
+            "comment" => Ok(NotificationKind::Comment),
+            "article_update" => Ok(NotificationKind::ArticleUpdate),
+            "mention" => Ok(NotificationKind::Mention),
+            _ => Err(InvalidType)
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Notification {
+    pub id: u64,
+    pub kind: NotificationKind,
+    #[serde(rename = "threadId")]
+    pub thread_id: Option<u32>,
+    #[serde(rename = "articleId")]
+    pub article_id: Option<u32>,
+    pub title: Option<String>,
+    #[serde(rename = "messageId")]
+    pub message_id: Option<u32>,
+    pub page: Option<u32>,
+    pub actor: User,
+    pub count: u32,
+    #[serde(rename = "createTime")]
+    pub create_time: u64,
+    #[serde(rename = "updateTime")]
+    pub update_time: u64,
+    pub read: bool,
+}
+
+#[derive(Deserialize)]
+pub struct NotificationsQuery {
+    pub page: u32,
+}
+
+#[derive(Serialize)]
+pub struct Notifications {
+    pub notifications: Vec<Notification>,
+    #[serde(rename = "unreadCount")]
+    pub unread_count: u32,
+}
+
+#[derive(Serialize)]
+pub struct NotificationCount {
+    pub unread: u32,
+}
+
+#[derive(Deserialize)]
+pub struct MarkNotificationsRead {
+    pub ids: Option<Vec<u64>>,
+}
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq)]
+pub enum EmailMode {
+    #[serde(rename = "immediate")]
+    Immediate,
+    #[serde(rename = "digest")]
+    Digest,
+    #[serde(rename = "off")]
+    Off
+}
+
+impl ToSql for EmailMode {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        match self {
+            EmailMode::Immediate => Ok(ToSqlOutput::from("immediate")),
+            EmailMode::Digest => Ok(ToSqlOutput::from("digest")),
+            EmailMode::Off => Ok(ToSqlOutput::from("off"))
+        }
+    }
+}
+
+impl FromSql for EmailMode {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let str = value.as_str()?;
+        match str {
+            "immediate" => Ok(EmailMode::Immediate),
+            "digest" => Ok(EmailMode::Digest),
+            "off" => Ok(EmailMode::Off),
+            _ => Err(InvalidType)
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct EmailSettings {
+    pub email: Option<String>,
+    pub mode: EmailMode,
+}
+
+#[derive(Deserialize)]
+pub struct SaveEmailSettings {
+    // Not required when emails are turned off
+    #[serde(default)]
+    pub email: String,
+    pub mode: EmailMode,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq)]
+pub enum WebhookEvent {
+    #[serde(rename = "threadCreated")]
+    ThreadCreated,
+    #[serde(rename = "messagePosted")]
+    MessagePosted,
+    #[serde(rename = "articleCreated")]
+    ArticleCreated,
+    #[serde(rename = "articleUpdated")]
+    ArticleUpdated,
+    #[serde(rename = "articleDeleted")]
+    ArticleDeleted,
+    #[serde(rename = "commentPosted")]
+    CommentPosted
+}
+
+impl ToSql for WebhookEvent {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        match self {
+            WebhookEvent::ThreadCreated => Ok(ToSqlOutput::from("thread_created")),
+            WebhookEvent::MessagePosted => Ok(ToSqlOutput::from("message_posted")),
+            WebhookEvent::ArticleCreated => Ok(ToSqlOutput::from("article_created")),
+            WebhookEvent::ArticleUpdated => Ok(ToSqlOutput::from("article_updated")),
+            WebhookEvent::ArticleDeleted => Ok(ToSqlOutput::from("article_deleted")),
+            WebhookEvent::CommentPosted => Ok(ToSqlOutput::from("comment_posted"))
+        }
+    }
+}
+
+impl FromSql for WebhookEvent {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let str = value.as_str()?;
+        match str {
+            "thread_created" => Ok(WebhookEvent::ThreadCreated),
+            "message_posted" => Ok(WebhookEvent::MessagePosted),
+            "article_created" => Ok(WebhookEvent::ArticleCreated),
+            "article_updated" => Ok(WebhookEvent::ArticleUpdated),
+            "article_deleted" => Ok(WebhookEvent::ArticleDeleted),
+            "comment_posted" => Ok(WebhookEvent::CommentPosted),
+            _ => Err(InvalidType)
+        }
+    }
+}
+
+#[derive(Serialize, Copy, Clone)]
+pub enum WebhookDeliveryStatus {
+    #[serde(rename = "pending")]
+    Pending,
+    #[serde(rename = "delivered")]
+    Delivered,
+    #[serde(rename = "failed")]
+    Failed
+}
+
+impl ToSql for WebhookDeliveryStatus {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        match self {
+            WebhookDeliveryStatus::Pending => Ok(ToSqlOutput::from("pending")),
+            WebhookDeliveryStatus::Delivered => Ok(ToSqlOutput::from("delivered")),
+            WebhookDeliveryStatus::Failed => Ok(ToSqlOutput::from("failed"))
+        }
+    }
+}
+
+impl FromSql for WebhookDeliveryStatus {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let str = value.as_str()?;
+        match str {
+            "pending" => Ok(WebhookDeliveryStatus::Pending),
+            "delivered" => Ok(WebhookDeliveryStatus::Delivered),
+            "failed" => Ok(WebhookDeliveryStatus::Failed),
+            _ => Err(InvalidType)
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Webhook {
+    pub id: u32,
+    pub url: String,
+    pub secret: String,
+    pub events: Vec<WebhookEvent>,
+    pub active: bool,
+    #[serde(rename = "createTime")]
+    pub create_time: u64,
+}
+
+#[derive(Serialize)]
+pub struct Webhooks {
+    pub webhooks: Vec<Webhook>,
+}
+
+#[derive(Deserialize)]
+pub struct SaveWebhook {
+    pub id: Option<u32>,
+    pub url: String,
+    // generated for new webhooks and kept for existing ones when not set
+    pub secret: Option<String>,
+    pub events: Vec<WebhookEvent>,
+    pub active: bool,
+}
+
+#[derive(Deserialize)]
+pub struct DeleteWebhook {
+    pub id: u32,
+}
+
+#[derive(Deserialize)]
+pub struct WebhookDeliveriesQuery {
+    #[serde(rename = "webhookId")]
+    pub webhook_id: u32,
+    pub page: u32,
+}
+
+#[derive(Serialize)]
+pub struct WebhookDeliveryAttempt {
+    pub attempt: u32,
+    pub time: u64,
+    #[serde(rename = "responseStatus")]
+    pub response_status: Option<u16>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct WebhookDelivery {
+    pub id: u64,
+    pub event: WebhookEvent,
+    pub status: WebhookDeliveryStatus,
+    pub attempts: u32,
+    #[serde(rename = "createTime")]
+    pub create_time: u64,
+    #[serde(rename = "nextAttemptTime")]
+    pub next_attempt_time: u64,
+    pub log: Vec<WebhookDeliveryAttempt>,
+}
+
+#[derive(Serialize)]
+pub struct WebhookDeliveries {
+    pub deliveries: Vec<WebhookDelivery>,
+}
+
+#[derive(Serialize)]
+pub struct FeedToken {
+    // None until the user creates a token
+    pub token: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct FeedTokenQuery {
+    pub token: Option<String>,
+}
@@ -0,0 +1,174 @@
+use rusqlite::{Connection, OptionalExtension, params};
+use crate::queries::QueryResult;
+use crate::structs::{LogicError, TagCount, Tags, User};
+
+pub const MAX_TAG_LENGTH: usize = 50;
+pub const TAG_SEARCH_LIMIT: u32 = 20;
+
+// Lower case, words joined with '-', None for an empty tag
+pub fn normalize_tag(name: &str) -> Option<String> {
+    let tag = name.split_whitespace()
+        .collect::<Vec<&str>>()
+        .join("-")
+        .trim_start_matches('#')
+        .to_lowercase();
+
+    let tag: String = tag.chars().take(MAX_TAG_LENGTH).collect();
+    if tag.is_empty() { None } else { Some(tag) }
+}
+
+// Comma separated tags from a query string
+pub fn parse_tags(tags: &Option<String>) -> Vec<String> {
+    let mut tags: Vec<String> = tags.as_deref().unwrap_or("").split(',').filter_map(normalize_tag).collect();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+// Condition on the id of the target having all the tags, one ? per tag
+pub fn tags_filter_clause(id_column: &str, link_table: &str, link_column: &str, tag_count: usize) -> String {
+    format!("{} IN (SELECT l.{} FROM {} l INNER JOIN TAG g ON g.id = l.tag_id WHERE g.name IN ({}) GROUP BY l.{} HAVING count(1) = {})",
+            id_column, link_column, link_table, vec!["?"; tag_count].join(", "), link_column, tag_count)
+}
+
+fn save_tag(conn: &Connection, name: &str) -> QueryResult<u32> {
+    conn.execute("INSERT OR IGNORE INTO TAG(name) VALUES(?)", [name])?;
+    let id = conn.query_row("SELECT id FROM TAG WHERE name = ?", [name], |row| row.get(0))?;
+    Ok(id)
+}
+
+pub fn delete_unused_tags(conn: &Connection) -> QueryResult<()> {
+    conn.execute("DELETE FROM TAG WHERE id NOT IN (SELECT tag_id FROM THREAD_TAG) AND id NOT IN (SELECT tag_id FROM ARTICLE_TAG)", [])?;
+    Ok(())
+}
+
+pub fn set_thread_tags(conn: &Connection, thread_id: u32, tags: &[String]) -> QueryResult<()> {
+    conn.execute("DELETE FROM THREAD_TAG WHERE thread_id = ?", [thread_id])?;
+
+    for tag in tags.iter().filter_map(|tag| normalize_tag(tag)) {
+        let tag_id = save_tag(conn, &tag)?;
+        conn.execute("INSERT OR IGNORE INTO THREAD_TAG(thread_id, tag_id) VALUES(?, ?)", params![thread_id, tag_id])?;
+    }
+
+    delete_unused_tags(conn)
+}
+
+pub fn set_article_tags(conn: &Connection, article_id: u32, tags: &[String]) -> QueryResult<()> {
+    conn.execute("DELETE FROM ARTICLE_TAG WHERE article_id = ?", [article_id])?;
+
+    for tag in tags.iter().filter_map(|tag| normalize_tag(tag)) {
+        let tag_id = save_tag(conn, &tag)?;
+        conn.execute("INSERT OR IGNORE INTO ARTICLE_TAG(article_id, tag_id) VALUES(?, ?)", params![article_id, tag_id])?;
+    }
+
+    delete_unused_tags(conn)
+}
+
+pub fn query_thread_tags(conn: &Connection, thread_id: u32) -> QueryResult<Vec<String>> {
+    let mut stmt = conn.prepare_cached("SELECT g.name FROM THREAD_TAG l INNER JOIN TAG g ON g.id = l.tag_id WHERE l.thread_id = ? ORDER BY g.name")?;
+    let tags = stmt.query_map([thread_id], |row| row.get(0))?.collect::<Result<Vec<String>, _>>()?;
+    Ok(tags)
+}
+
+pub fn query_article_tags(conn: &Connection, article_id: u32) -> QueryResult<Vec<String>> {
+    let mut stmt = conn.prepare_cached("SELECT g.name FROM ARTICLE_TAG l INNER JOIN TAG g ON g.id = l.tag_id WHERE l.article_id = ? ORDER BY g.name")?;
+    let tags = stmt.query_map([article_id], |row| row.get(0))?.collect::<Result<Vec<String>, _>>()?;
+    Ok(tags)
+}
+
+// Tags with the numbers of live threads and articles visible to the user,
+// tags used only by invisible targets are skipped
+fn query_tag_counts(conn: &Connection, name_clause: &str, limit_clause: &str, name_param: Option<&str>, user: &Option<User>) -> QueryResult<Tags> {
+    let user_id = user.as_ref().map_or(0, |user| user.id);
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT c.name, c.threads, c.articles FROM (SELECT g.name AS name, \
+        (SELECT count(1) FROM THREAD_TAG l INNER JOIN THREAD t ON t.id = l.thread_id \
+            WHERE l.tag_id = g.id AND t.deleted = FALSE AND t.publish_time IS NULL) AS threads, \
+        (SELECT count(1) FROM ARTICLE_TAG l INNER JOIN ARTICLE a ON a.id = l.article_id AND a.active = TRUE \
+            WHERE l.tag_id = g.id AND (a.visibility = 'public' OR (a.visibility = 'private' AND a.user_id = ?1))) AS articles \
+        FROM TAG g WHERE {}) c WHERE c.threads > 0 OR c.articles > 0 \
+        ORDER BY c.threads + c.articles DESC, c.name {}", name_clause, limit_clause),
+    )?;
+
+    let map_row = |row: &rusqlite::Row| Ok(TagCount {
+        name: row.get(0)?,
+        threads: row.get(1)?,
+        articles: row.get(2)?,
+    });
+
+    let tags = match name_param {
+        Some(name) => stmt.query_map(params![user_id, name], map_row)?,
+        None => stmt.query_map(params![user_id], map_row)?,
+    }.collect::<Result<Vec<TagCount>, _>>()?;
+
+    Ok(Tags{tags})
+}
+
+pub fn query_tag_cloud(conn: &Connection, user: &Option<User>) -> QueryResult<Tags> {
+    query_tag_counts(conn, "TRUE", "", None, user)
+}
+
+// Autocomplete, the most used tags starting with the prefix first
+pub fn search_tags(conn: &Connection, prefix: &str, user: &Option<User>) -> QueryResult<Tags> {
+    let prefix = normalize_tag(prefix).unwrap_or_default();
+    let pattern = format!("{}%", prefix.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"));
+
+    query_tag_counts(conn, "g.name LIKE ?2 ESCAPE '\\'", &format!("LIMIT {}", TAG_SEARCH_LIMIT), Some(&pattern), user)
+}
+
+fn query_tag_id(conn: &Connection, name: &str) -> QueryResult<Option<u32>> {
+    let id = conn.query_row("SELECT id FROM TAG WHERE name = ?", [name], |row| row.get(0)).optional()?;
+    Ok(id)
+}
+
+pub fn rename_tag(conn: &Connection, name: &str, new_name: &str) -> QueryResult<()> {
+    let name = normalize_tag(name).ok_or(LogicError::TagNotFound)?;
+    let new_name = normalize_tag(new_name).ok_or(LogicError::InvalidTag)?;
+
+    let id = query_tag_id(conn, &name)?.ok_or(LogicError::TagNotFound)?;
+    if name == new_name {
+        return Ok(());
+    }
+
+    if query_tag_id(conn, &new_name)?.is_some() {
+        return Err(LogicError::TagExists.into());
+    }
+
+    conn.execute("UPDATE TAG SET name = ? WHERE id = ?", params![new_name, id])?;
+    Ok(())
+}
+
+// Moves threads and articles of the tags to the target tag, which is created if missing
+pub fn merge_tags(conn: &Connection, tags: &[String], into: &str) -> QueryResult<()> {
+    let into = normalize_tag(into).ok_or(LogicError::InvalidTag)?;
+    let into_id = save_tag(conn, &into)?;
+
+    for name in tags.iter().filter_map(|tag| normalize_tag(tag)) {
+        let id = query_tag_id(conn, &name)?.ok_or(LogicError::TagNotFound)?;
+        if id == into_id {
+            continue;
+        }
+
+        conn.execute("INSERT OR IGNORE INTO THREAD_TAG(thread_id, tag_id) SELECT thread_id, ? FROM THREAD_TAG WHERE tag_id = ?", params![into_id, id])?;
+        conn.execute("INSERT OR IGNORE INTO ARTICLE_TAG(article_id, tag_id) SELECT article_id, ? FROM ARTICLE_TAG WHERE tag_id = ?", params![into_id, id])?;
+        conn.execute("DELETE FROM THREAD_TAG WHERE tag_id = ?", [id])?;
+        conn.execute("DELETE FROM ARTICLE_TAG WHERE tag_id = ?", [id])?;
+        conn.execute("DELETE FROM TAG WHERE id = ?", [id])?;
+    }
+
+    delete_unused_tags(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_are_normalized() {
+        assert_eq!(normalize_tag("  Rust  Lang "), Some("rust-lang".to_string()));
+        assert_eq!(normalize_tag("#Async"), Some("async".to_string()));
+        assert_eq!(normalize_tag("   "), None);
+        assert_eq!(parse_tags(&Some("b, A,,a".to_string())), vec!["a".to_string(), "b".to_string()]);
+    }
+}
@@ -0,0 +1,238 @@
+use std::time::Duration;
+use data_encoding::HEXLOWER;
+use ring::hmac;
+use rusqlite::{Connection, params};
+use serde_json::json;
+use crate::email::retry_delay_seconds;
+use crate::queries::{DbConnection, QueryResult};
+use crate::structs::{LogicError, SaveWebhook, User, Webhook, WebhookDeliveries, WebhookDelivery, WebhookDeliveryAttempt, WebhookDeliveryStatus, WebhookEvent, Webhooks};
+
+pub const DELIVERIES_PER_PAGE: u32 = 50;
+const DELIVERY_BATCH_SIZE: u32 = 100;
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+struct PendingDelivery {
+    id: u64,
+    event: WebhookEvent,
+    payload: String,
+    attempts: u32,
+    url: String,
+    secret: String,
+}
+
+// Queues the event for every active webhook subscribed to it, called inside the transaction
+// of the change so that the event is stored only when the change is
+pub fn enqueue_webhook_event(conn: &Connection, event: WebhookEvent, data: serde_json::Value, user: &User) -> QueryResult<()> {
+    let t = chrono::offset::Utc::now();
+    let t = t.timestamp_millis();
+
+    let payload = json!({
+        "event": event,
+        "time": t,
+        "user": user,
+        "data": data,
+    }).to_string();
+
+    conn.execute("INSERT INTO WEBHOOK_DELIVERY(webhook_id, event, payload, status, next_attempt_time, create_time) \
+        SELECT w.id, ?1, ?2, 'pending', ?3, ?3 FROM WEBHOOK w INNER JOIN WEBHOOK_EVENT e ON e.webhook_id = w.id \
+        WHERE w.active = TRUE AND e.event = ?1",
+                 params![event, payload, t])?;
+
+    Ok(())
+}
+
+pub fn sign_payload(secret: &str, payload: &str) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    HEXLOWER.encode(hmac::sign(&key, payload.as_bytes()).as_ref())
+}
+
+pub fn query_webhooks(conn: &DbConnection) -> QueryResult<Webhooks> {
+    let mut stmt = conn.prepare("SELECT id, url, secret, active, create_time FROM WEBHOOK ORDER BY id")?;
+
+    let mut webhooks = stmt.query_map([], |row| Ok(Webhook {
+        id: row.get(0)?,
+        url: row.get(1)?,
+        secret: row.get(2)?,
+        events: Vec::new(),
+        active: row.get(3)?,
+        create_time: row.get(4)?,
+    }))?.collect::<Result<Vec<Webhook>, _>>()?;
+
+    let mut stmt = conn.prepare("SELECT event FROM WEBHOOK_EVENT WHERE webhook_id = ? ORDER BY event")?;
+    for webhook in &mut webhooks {
+        webhook.events = stmt.query_map([webhook.id], |row| row.get(0))?
+            .collect::<Result<Vec<WebhookEvent>, _>>()?;
+    }
+
+    Ok(Webhooks{webhooks})
+}
+
+pub fn save_webhook(conn: &mut DbConnection, webhook: &SaveWebhook, generated_secret: &str) -> QueryResult<u32> {
+    let url = webhook.url.trim();
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err(LogicError::InvalidWebhookUrl.into());
+    }
+
+    let transaction = conn.transaction()?;
+
+    let id = match webhook.id {
+        Some(id) => {
+            let count = transaction.execute("UPDATE WEBHOOK SET url = ?, secret = COALESCE(?, secret), active = ? WHERE id = ?",
+                                            params![url, webhook.secret, webhook.active, id])?;
+
+            if count == 0 {
+                return Err(LogicError::WebhookNotFound.into());
+            }
+
+            transaction.execute("DELETE FROM WEBHOOK_EVENT WHERE webhook_id = ?", [id])?;
+            id
+        }
+        None => {
+            let t = chrono::offset::Utc::now();
+            let t = t.timestamp_millis();
+
+            let secret = webhook.secret.as_deref().unwrap_or(generated_secret);
+            transaction.execute("INSERT INTO WEBHOOK(url, secret, active, create_time) VALUES(?, ?, ?, ?)",
+                                params![url, secret, webhook.active, t])?;
+            transaction.last_insert_rowid() as u32
+        }
+    };
+
+    for event in &webhook.events {
+        transaction.execute("INSERT OR IGNORE INTO WEBHOOK_EVENT(webhook_id, event) VALUES(?, ?)", params![id, event])?;
+    }
+
+    transaction.commit()?;
+    Ok(id)
+}
+
+pub fn delete_webhook(conn: &mut DbConnection, id: u32) -> QueryResult<()> {
+    let transaction = conn.transaction()?;
+
+    transaction.execute("DELETE FROM WEBHOOK_DELIVERY_ATTEMPT WHERE delivery_id IN (SELECT id FROM WEBHOOK_DELIVERY WHERE webhook_id = ?)", [id])?;
+    transaction.execute("DELETE FROM WEBHOOK_DELIVERY WHERE webhook_id = ?", [id])?;
+    transaction.execute("DELETE FROM WEBHOOK_EVENT WHERE webhook_id = ?", [id])?;
+    let count = transaction.execute("DELETE FROM WEBHOOK WHERE id = ?", [id])?;
+
+    if count == 0 {
+        return Err(LogicError::WebhookNotFound.into());
+    }
+
+    transaction.commit()?;
+    Ok(())
+}
+
+pub fn query_webhook_deliveries(conn: &DbConnection, webhook_id: u32, page: u32) -> QueryResult<WebhookDeliveries> {
+    let limit_clause = format!(" LIMIT {}", DELIVERIES_PER_PAGE);
+    let offset_clause = if page > 1 {format!(" OFFSET {}", (page - 1) * DELIVERIES_PER_PAGE)} else { "".to_string() };
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, event, status, attempts, create_time, next_attempt_time FROM WEBHOOK_DELIVERY \
+        WHERE webhook_id = ? ORDER BY id DESC {} {}", limit_clause, offset_clause),
+    )?;
+
+    let mut deliveries = stmt.query_map([webhook_id], |row| Ok(WebhookDelivery {
+        id: row.get(0)?,
+        event: row.get(1)?,
+        status: row.get(2)?,
+        attempts: row.get(3)?,
+        create_time: row.get(4)?,
+        next_attempt_time: row.get(5)?,
+        log: Vec::new(),
+    }))?.collect::<Result<Vec<WebhookDelivery>, _>>()?;
+
+    let mut stmt = conn.prepare("SELECT attempt, time, response_status, error FROM WEBHOOK_DELIVERY_ATTEMPT WHERE delivery_id = ? ORDER BY attempt")?;
+    for delivery in &mut deliveries {
+        delivery.log = stmt.query_map([delivery.id], |row| Ok(WebhookDeliveryAttempt {
+            attempt: row.get(0)?,
+            time: row.get(1)?,
+            response_status: row.get(2)?,
+            error: row.get(3)?,
+        }))?.collect::<Result<Vec<WebhookDeliveryAttempt>, _>>()?;
+    }
+
+    Ok(WebhookDeliveries{deliveries})
+}
+
+// POSTs the payload, returns the response status and the error if the delivery failed
+fn post_payload(delivery: &PendingDelivery) -> (Option<u16>, Option<String>) {
+    let event = serde_json::to_value(delivery.event).ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default();
+    let signature = format!("sha256={}", sign_payload(&delivery.secret, &delivery.payload));
+
+    let result = ureq::post(&delivery.url)
+        .timeout(DELIVERY_TIMEOUT)
+        .set("Content-Type", "application/json")
+        .set("X-Ark-Event", &event)
+        .set("X-Ark-Delivery", &delivery.id.to_string())
+        .set("X-Ark-Signature", &signature)
+        .send_string(&delivery.payload);
+
+    match result {
+        Ok(response) => (Some(response.status()), None),
+        Err(ureq::Error::Status(status, _)) => (Some(status), Some(format!("HTTP status {}", status))),
+        Err(e) => (None, Some(e.to_string())),
+    }
+}
+
+// Sends due deliveries of active webhooks, failed ones are retried with exponential backoff
+// until max_attempts is reached. Returns (delivered, failed) counts.
+pub fn deliver_pending_webhooks(conn: &DbConnection, max_attempts: u32) -> QueryResult<(usize, usize)> {
+    let t = chrono::offset::Utc::now();
+    let t = t.timestamp_millis();
+
+    let mut stmt = conn.prepare(
+        "SELECT d.id, d.event, d.payload, d.attempts, w.url, w.secret FROM WEBHOOK_DELIVERY d \
+        INNER JOIN WEBHOOK w ON w.id = d.webhook_id \
+        WHERE d.status = 'pending' AND d.next_attempt_time <= ? AND w.active = TRUE \
+        ORDER BY d.id LIMIT ?",
+    )?;
+
+    let deliveries = stmt.query_map(params![t, DELIVERY_BATCH_SIZE], |row| Ok(PendingDelivery {
+        id: row.get(0)?,
+        event: row.get(1)?,
+        payload: row.get(2)?,
+        attempts: row.get(3)?,
+        url: row.get(4)?,
+        secret: row.get(5)?,
+    }))?.collect::<Result<Vec<PendingDelivery>, _>>()?;
+
+    let mut delivered = 0;
+    let mut failed = 0;
+    for delivery in &deliveries {
+        let (response_status, error) = post_payload(delivery);
+        let t = chrono::offset::Utc::now().timestamp_millis();
+        let attempt = delivery.attempts + 1;
+
+        let status = if error.is_none() {
+            delivered += 1;
+            WebhookDeliveryStatus::Delivered
+        } else {
+            failed += 1;
+            if attempt >= max_attempts { WebhookDeliveryStatus::Failed } else { WebhookDeliveryStatus::Pending }
+        };
+
+        let next_attempt_time = match status {
+            WebhookDeliveryStatus::Pending => t + retry_delay_seconds(delivery.attempts) * 1000,
+            _ => t
+        };
+        conn.execute("UPDATE WEBHOOK_DELIVERY SET status = ?, attempts = ?, next_attempt_time = ? WHERE id = ?",
+                     params![status, attempt, next_attempt_time, delivery.id])?;
+        conn.execute("INSERT INTO WEBHOOK_DELIVERY_ATTEMPT(delivery_id, attempt, time, response_status, error) VALUES(?, ?, ?, ?, ?)",
+                     params![delivery.id, attempt, t, response_status, error])?;
+    }
+
+    Ok((delivered, failed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payloads_are_signed_with_hmac_sha256() {
+        // RFC 4231, test case 2
+        assert_eq!(sign_payload("Jefe", "what do ya want for nothing?"), "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+    }
+}
@@ -18,6 +18,7 @@ import {
     ARTICLE_SEARCH_PATH,
     ARTICLES_PATH,
     currentAddress,
+    DRAFTS_PATH,
     MESSAGE_SEARCH_PATH,
     ROOT_PATH,
     SECTION_PATH,
@@ -45,6 +46,7 @@ import {
 } from "./ts/forumStructure";
 import {currentUserState, loginState, SignonForm, SignupForm} from "./ts/currentUser";
 import {UserForm} from "./ts/userSessions";
+import {DraftsPage} from "./ts/drafts";
 import {ArticleDialog, ArticlePage, ArticlesPage, DeleteArticleDialog} from "./ts/article";
 import {DATE_TIME_FORMAT} from "./ts/utils";
 import {ThreadPage} from "./ts/thread";
@@ -259,6 +261,8 @@ const Header = observer(() => {
         }
         {currentUserState.currentUser &&
             <div>
+                <Link address={{template: DRAFTS_PATH}}>{"Drafts"}</Link>
+                {" "}
                 <Link address={{template: USER_PROFILE_PATH}}>{currentUserState.currentUser.name}</Link>
             </div>
         }
@@ -391,6 +395,13 @@ const App = observer(() => {
                 <SignonForm/>
             </div>
 
+        case DRAFTS_PATH:
+            return <div className={"body-container user-form"}>
+                <Header/>
+                <DraftsPage/>
+                <SignonForm/>
+            </div>
+
         case SIGNUP_PATH:
             return <div className={"body-container"}>
                 <Header/>
@@ -127,10 +127,9 @@ class SignupState {
         e.preventDefault();
         e.stopPropagation();
 
-        let hashedPassword = sha256.hmac('[the-ark]', this.password);
-
-        ajax.post("/signup", {userName: this.login, password: hashedPassword}).subscribe(v => {
-            runInAction(() => {
+        // the server checks the password policy, so the password is sent as is
+        ajax.post("/signup", {userName: this.login, password: this.password}).subscribe({
+            next: v => runInAction(() => {
                 if (v.response == "OK") {
                     this.error = "";
                     currentAddress.goTo({template: ROOT_PATH, params: {}});
@@ -141,7 +140,10 @@ class SignupState {
                 else {
                     this.error = "Unexpected error";
                 }
-            });
+            }),
+            error: e => runInAction(() => {
+                this.error = typeof e.response == "string" ? e.response : "Unexpected error";
+            }),
         });
     }
 
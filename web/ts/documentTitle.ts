@@ -5,6 +5,7 @@ import {
     ARTICLE_PATH,
     ARTICLES_PATH,
     currentAddress,
+    DRAFTS_PATH,
     MESSAGE_SEARCH_PATH, NOT_FOUND_PATH,
     SECTION_PATH,
     SIGNUP_PATH,
@@ -53,6 +54,10 @@ class DocumentTitleState {
             return "User profile";
         }
 
+        if (currentAddress.address.template == DRAFTS_PATH)  {
+            return "Drafts";
+        }
+
         if (currentAddress.address.template == SIGNUP_PATH)  {
             return "Sign up";
         }
@@ -0,0 +1,88 @@
+import {action, makeObservable, observable} from "mobx";
+import {observer} from "mobx-react";
+import * as React from "react";
+import {Instant, LocalDateTime} from "@js-joda/core";
+import {EMPTY, switchMap} from "rxjs";
+import {deleteDraft, Draft, Drafts, queryDrafts} from "./requests";
+import {ARTICLE_PATH, currentAddress, DRAFTS_PATH, PageAddress, SUBSECTION_PATH, THREAD_PATH} from "./router";
+import {Link} from "./components";
+import {DATE_TIME_FORMAT} from "./utils";
+
+class DraftsState {
+    constructor() {
+        makeObservable(this);
+
+        currentAddress.pageAddress$.pipe(
+            switchMap(v => {
+                if (v.template == DRAFTS_PATH)
+                    return queryDrafts();
+
+                return EMPTY;
+            })
+        ).subscribe(this.updateDrafts);
+    }
+
+    @observable drafts: Draft[] = [];
+
+    @action.bound
+    updateDrafts(drafts: Drafts) {
+        this.drafts = drafts.drafts;
+    }
+
+    @action.bound
+    removeDraft(context: string) {
+        deleteDraft(context).pipe(switchMap(() => queryDrafts())).subscribe(this.updateDrafts);
+    }
+}
+
+export const draftsState = new DraftsState();
+
+// Where the draft is going to be published, new articles have no page of their own
+function draftAddress(context: string): PageAddress | null {
+    let [kind, id] = context.split(":");
+
+    switch (kind) {
+        case "thread":
+            return {template: THREAD_PATH, threadId: id, params: {}};
+        case "subsection":
+            return {template: SUBSECTION_PATH, subSectionId: id, params: {}};
+        case "article":
+            return {template: ARTICLE_PATH, articleId: id};
+    }
+
+    return null;
+}
+
+export const DraftsPage = observer(() => {
+    return <>
+        <h1>{"Drafts"}</h1>
+
+        <table className={"forum-table"}>
+            <thead>
+            <tr>
+                <th>{"Draft"}</th>
+                <th className={"right"}>{"Saved"}</th>
+                <th></th>
+            </tr>
+            </thead>
+            <tbody>
+            {draftsState.drafts.map(v => {
+                let address = draftAddress(v.context);
+                let name = v.title || v.content.substring(0, 100);
+                let updateTime = LocalDateTime.ofInstant(Instant.ofEpochMilli(v.updateTime));
+
+                return <tr key={v.context}>
+                    <td>
+                        {address ? <Link address={address}>{name}</Link> : name}
+                        {` (${v.context})`}
+                    </td>
+                    <td className={"right"}>{DATE_TIME_FORMAT.format(updateTime)}</td>
+                    <td>
+                        <a className={"action-link"} href={"##"} onClick={() => draftsState.removeDraft(v.context)}>{"Remove"}</a>
+                    </td>
+                </tr>
+            })}
+            </tbody>
+        </table>
+    </>
+});
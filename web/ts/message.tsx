@@ -2,10 +2,10 @@ import {FileUploadable} from "./fileUploadable";
 import {action, makeObservable, observable, runInAction} from "mobx";
 import * as React from "react";
 import {ARTICLE_COMMENTS_PATH, currentAddress, router, THREAD_PATH} from "./router";
-import {Comment, filterErrors, Message, postComment, postMessage, Thread} from "./requests";
+import {Comment, Draft, draftChanges$, draftPublished$, filterErrors, Message, postComment, postMessage, queryDraft, Thread} from "./requests";
 import {currentThreadState} from "./thread";
 import {ajax} from "rxjs/ajax";
-import {switchMap} from "rxjs";
+import {catchError, combineLatest, distinctUntilChanged, map, of, startWith, switchMap} from "rxjs";
 import {observer} from "mobx-react";
 import {LargeDialog} from "./components";
 import MarkdownEditor from "@uiw/react-markdown-editor";
@@ -13,19 +13,52 @@ import {EditorView} from "codemirror";
 import {Instant, LocalDateTime} from "@js-joda/core";
 import {currentUserState} from "./currentUser";
 import {DATE_FORMAT, TIME_FORMAT} from "./utils";
-import {articleCommentEvent$, threadMessageEvent$} from "./events";
+import {articleCommentEvent$, currentUserEvent$, threadMessageEvent$} from "./events";
 import {articleCommentsState} from "./articleComments";
 
 class NewMessageState implements FileUploadable {
     constructor() {
         makeObservable(this);
+
+        // Restores the saved draft when a thread is opened
+        combineLatest([currentAddress.pageAddress$.pipe(
+                map(v => v.template == THREAD_PATH ? `thread:${v.threadId}` : null),
+                distinctUntilChanged()),
+            currentUserEvent$.pipe(startWith("start"))], (context, e) => context).pipe(
+            switchMap(context => {
+                if (!context || !currentUserState.currentUser)
+                    return of({context, draft: null});
+
+                return queryDraft(context).pipe(
+                    catchError(() => of(null)),
+                    map(draft => ({context, draft})));
+            })
+        ).subscribe(this.restoreDraft);
     }
 
     @observable messageText = "";
+    draftContext: string | null = null;
+
+    @action.bound
+    restoreDraft({context, draft}: {context: string | null, draft: Draft | null}) {
+        if (!context)
+            return;
+
+        if (draft)
+            this.messageText = draft.content;
+        else if (context != this.draftContext)
+            this.messageText = "";
+
+        this.draftContext = context;
+    }
 
     @action.bound
     onChangeMessageText(value: string) {
         this.messageText = value;
+
+        if (currentAddress.address.template == THREAD_PATH && currentThreadState.currentThread) {
+            draftChanges$.next({context: `thread:${currentThreadState.currentThread.id}`, content: value});
+        }
     }
 
     @action.bound
@@ -35,6 +68,7 @@ class NewMessageState implements FileUploadable {
 
         if (currentAddress.address.template == THREAD_PATH) {
             if (currentThreadState.currentThread) {
+                draftPublished$.next(`thread:${currentThreadState.currentThread.id}`);
                 postMessage(currentThreadState.currentThread.id, this.messageText).subscribe(v => {
                     runInAction(() => {
                         this.messageText = "";
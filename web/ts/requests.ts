@@ -1,4 +1,4 @@
-import {catchError, EMPTY, from, Observable, of, startWith, switchMap} from "rxjs";
+import {catchError, EMPTY, filter, from, groupBy, mergeMap, Observable, of, startWith, Subject, switchMap, takeUntil, timer} from "rxjs";
 import {ajax, AjaxResponse} from "rxjs/ajax";
 import {PageAddress, ROOT_PATH, SECTION_PATH, SUBSECTION_PATH} from "./router";
 import Cookies from "js-cookie";
@@ -6,17 +6,22 @@ import Cookies from "js-cookie";
 export interface User {
     id: number,
     name: string;
+    displayName: string | null,
+    avatarUrl: string | null,
 }
 
 export interface Section {
     id: number,
     name: string,
+    sortOrder: number,
 }
 
 export interface SubSection {
     id: number,
     sectionId: number,
     name: string,
+    description: string,
+    sortOrder: number,
 }
 
 export interface Thread {
@@ -29,6 +34,20 @@ export interface Thread {
     lastMessageUser: User,
     lastMessageId: number,
     lastMessageDateTime: number,
+    unreadCount: number | null,
+    firstUnreadMessageId: number | null,
+    firstUnreadPage: number | null,
+    pinned: "none" | "subsection" | "global",
+    locked: boolean,
+    announcement: boolean,
+    tags: string[],
+    publishAt: number | null,
+}
+
+export interface MentionSpan {
+    start: number,
+    end: number,
+    user: User,
 }
 
 export interface Message {
@@ -38,6 +57,22 @@ export interface Message {
     createTime: number,
     updateTime: number,
     content: string,
+    mentions: MentionSpan[],
+    reactions: ReactionCount[],
+    replyTo: MessagePreview | null,
+}
+
+export interface MessagePreview {
+    id: number,
+    user: User,
+    excerpt: string,
+    page: number,
+}
+
+export interface ReactionCount {
+    emoji: string,
+    count: number,
+    reactedByMe: boolean,
 }
 
 export interface Comment {
@@ -47,6 +82,8 @@ export interface Comment {
     createTime: number,
     updateTime: number,
     content: string,
+    mentions: MentionSpan[],
+    reactions: ReactionCount[],
 }
 
 export interface ForumStructure {
@@ -124,6 +161,26 @@ export function queryCurrentUser() {
 export interface MessagesQueryResult {
     thread: Thread,
     messages: Message[],
+    poll: Poll | null,
+}
+
+export interface Poll {
+    question: string,
+    multiple: boolean,
+    anonymous: boolean,
+    hideResults: boolean,
+    closeTime: number | null,
+    closed: boolean,
+    options: PollOption[],
+    totalVoters: number | null,
+    myVotes: number[],
+}
+
+export interface PollOption {
+    id: number,
+    text: string,
+    votes: number | null,
+    voters: User[] | null,
 }
 
 export interface CommentsQueryResult {
@@ -164,6 +221,57 @@ export function postComment(articleId: number, message: string) {
     return ajax.post<{}>("/api/comment", {articleId, message}).pipe(switchMap(filterErrors));
 }
 
+// thread:{id}, subsection:{id}, article:{id} or new-article
+export interface Draft {
+    context: string,
+    title: string | null,
+    content: string,
+    updateTime: number,
+}
+
+export interface Drafts {
+    drafts: Draft[],
+}
+
+export interface SaveDraft {
+    context: string,
+    title?: string,
+    content: string,
+}
+
+export const DRAFT_AUTOSAVE_DELAY_MS = 2000;
+
+export function queryDrafts() {
+    return ajax.getJSON<Drafts>("/api/drafts");
+}
+
+export function queryDraft(context: string) {
+    return ajax.getJSON<Draft | null>(`/api/draft?context=${encodeURIComponent(context)}`);
+}
+
+export function saveDraft(draft: SaveDraft) {
+    return ajax.post<{}>("/api/draft", draft).pipe(switchMap(filterErrors));
+}
+
+export function deleteDraft(context: string) {
+    return ajax.post<{}>("/api/draft/delete", {context}).pipe(switchMap(filterErrors));
+}
+
+// Saves the latest draft of each context once the text stops changing for a while
+export const draftChanges$ = new Subject<SaveDraft>();
+// Context of a draft that is being published, its pending save is dropped
+export const draftPublished$ = new Subject<string>();
+
+draftChanges$.pipe(
+    groupBy(draft => draft.context),
+    mergeMap(drafts$ => drafts$.pipe(
+        switchMap(draft => timer(DRAFT_AUTOSAVE_DELAY_MS).pipe(
+            switchMap(() => saveDraft(draft).pipe(catchError(() => EMPTY))),
+            takeUntil(draftPublished$.pipe(filter(context => context == draft.context))),
+        )),
+    )),
+).subscribe();
+
 export function isNonNulled<T>(value: T): value is NonNullable<T> {
     return value != null;
 }
@@ -188,17 +296,131 @@ export interface ArticleVersion {
 export interface Article {
     info: ArticleInfo,
     content: string,
+    mentions: MentionSpan[],
     user: User,
     createTime: number,
     version: number,
     active: boolean,
     commentsCount: number,
     visibility: ArticleVisibility,
+    slug: string | null,
 
-    versions: ArticleVersion[]
+    versions: ArticleVersion[],
+    backlinks: ArticleInfo[],
+    tags: string[],
+    publishAt: number | null,
 }
 
-export type ArticleVisibility = "public" | "private";
+export type ArticleVisibility = "public" | "private" | "scheduled";
+
+export interface ScheduledThread {
+    id: number,
+    subSectionId: number,
+    name: string,
+    publishAt: number,
+}
+
+export interface ScheduledArticle {
+    info: ArticleInfo,
+    publishAt: number,
+}
+
+export interface ScheduledPublications {
+    threads: ScheduledThread[],
+    articles: ScheduledArticle[],
+}
+
+export function queryScheduledPublications() {
+    return ajax.getJSON<ScheduledPublications>("/api/scheduled");
+}
+
+export interface PasswordPolicy {
+    minLength: number,
+    minClasses: number,
+}
+
+export interface PasswordResetLink {
+    url: string,
+    expireTime: number,
+    emailed: boolean,
+}
+
+export interface PasswordHashReport {
+    total: number,
+    legacy: number,
+    outdated: number,
+    schemes: {scheme: string, count: number}[],
+}
+
+export function queryPasswordHashReport() {
+    return ajaxGet<PasswordHashReport>("/api/admin/password-hashes");
+}
+
+export function queryPasswordPolicy() {
+    return ajaxGet<PasswordPolicy>("/api/password/policy");
+}
+
+export function changePassword(currentPassword: string, newPassword: string) {
+    return ajax.post<{}>("/api/password/change", {currentPassword, newPassword}).pipe(switchMap(filterErrors));
+}
+
+export function createPasswordReset(userId: number) {
+    return ajax.post<PasswordResetLink>("/api/admin/password-reset", {userId}).pipe(switchMap(filterErrors));
+}
+
+export function requestPasswordReset(userName: string) {
+    return ajax.post<{}>("/password-reset", {userName}).pipe(switchMap(filterErrors));
+}
+
+export interface UserProfile {
+    user: User,
+    bio: string | null,
+    timezone: string | null,
+    locale: string | null,
+}
+
+export interface UpdateProfile {
+    displayName?: string,
+    avatarFileId?: string,
+    bio?: string,
+    timezone?: string,
+    locale?: string,
+}
+
+export interface ProfileComment {
+    id: number,
+    article: ArticleInfo,
+    createTime: number,
+    content: string,
+}
+
+export interface ProfileComments {
+    comments: ProfileComment[],
+}
+
+export function queryProfile(userId: number) {
+    return ajaxGet<UserProfile>(`/api/profile?userId=${userId}`);
+}
+
+export function updateProfile(profile: UpdateProfile) {
+    return ajax.post<{}>("/api/profile", profile).pipe(switchMap(filterErrors));
+}
+
+export function queryProfileThreads(userId: number, page: number) {
+    return ajaxGet<Thread[]>(`/api/profile/threads?userId=${userId}&page=${page}`);
+}
+
+export function queryProfileMessages(userId: number, page: number) {
+    return ajaxGet<MessageSearchResults>(`/api/profile/messages?userId=${userId}&page=${page}`);
+}
+
+export function queryProfileArticles(userId: number, page: number) {
+    return ajaxGet<Articles>(`/api/profile/articles?userId=${userId}&page=${page}`);
+}
+
+export function queryProfileComments(userId: number, page: number) {
+    return ajaxGet<ProfileComments>(`/api/profile/comments?userId=${userId}&page=${page}`);
+}
 
 export interface MessageSearchParams {
     query: string,
@@ -240,8 +462,27 @@ export function queryUserSessions() {
     return ajax.getJSON<UserSessions>("/api/current-user-sessions");
 }
 
-export function queryArticles() {
-    return ajax.getJSON<Articles>("/api/article/list");
+export function queryArticles(tags: string[] = []) {
+    const query = tags.length > 0 ? `?tags=${encodeURIComponent(tags.join(","))}` : "";
+    return ajax.getJSON<Articles>(`/api/article/list${query}`);
+}
+
+export interface TagCount {
+    name: string,
+    threads: number,
+    articles: number,
+}
+
+export interface Tags {
+    tags: TagCount[],
+}
+
+export function queryTags() {
+    return ajax.getJSON<Tags>("/api/tags");
+}
+
+export function searchTags(prefix: string) {
+    return ajax.getJSON<Tags>(`/api/tags/search?prefix=${encodeURIComponent(prefix)}`);
 }
 
 export function queryFavoriteArticles() {
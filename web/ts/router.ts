@@ -8,6 +8,7 @@ export const SUBSECTION_PATH = "/forum/ss/:subSectionId";
 export const THREAD_PATH = "/forum/t/:threadId";
 export const MESSAGE_SEARCH_PATH = "/forum/ms/:searchString";
 export const USER_PROFILE_PATH = "/forum/user";
+export const DRAFTS_PATH = "/forum/drafts";
 export const SIGNUP_PATH = "/signup";
 export const ARTICLES_PATH = "/articles";
 export const ARTICLE_PATH = "/article/a/:articleId";
@@ -37,6 +38,8 @@ export type PageAddress = {
     searchString: string,
 } | {
     template: typeof USER_PROFILE_PATH,
+} | {
+    template: typeof DRAFTS_PATH,
 } | {
     template: typeof SIGNUP_PATH,
 } | {
@@ -190,6 +193,12 @@ class CurrentAddress {
             }
         }
 
+        if ((parsedPath = tryParsePath(router.pathSegments, DRAFTS_PATH))) {
+            return {
+                template: DRAFTS_PATH
+            }
+        }
+
         if ((parsedPath = tryParsePath(router.pathSegments, SIGNUP_PATH))) {
             return {
                 template: SIGNUP_PATH